@@ -19,9 +19,12 @@ use {
     },
     solend_program::{
         self,
-        instruction::{init_lending_market, init_reserve, update_reserve_config},
+        instruction::{
+            close_obligation, init_lending_market, init_reserve, transfer_obligation_ownership,
+            update_reserve_config,
+        },
         math::WAD,
-        state::{LendingMarket, Reserve, ReserveConfig, ReserveFees},
+        state::{LendingMarket, Obligation, Reserve, ReserveConfig, ReserveFees},
     },
     spl_token::{
         amount_to_ui_amount,
@@ -70,6 +73,14 @@ struct PartialReserveConfig {
     pub protocol_liquidation_fee: Option<u8>,
     /// Protocol take rate is the amount borrowed interest protocol recieves, as a percentage  
     pub protocol_take_rate: Option<u8>,
+    /// Deposit limit in the lending market's quote currency
+    pub deposit_limit_value: Option<u64>,
+    /// Borrow limit in the lending market's quote currency
+    pub borrow_limit_value: Option<u64>,
+    /// Maximum share of the reserve collateral a single obligation can deposit, as a percentage
+    pub max_obligation_deposit_percentage: Option<u8>,
+    /// Maximum amount of liquidity a single obligation can borrow
+    pub max_obligation_borrow: Option<u64>,
 }
 
 /// Reserve Fees with optional fields
@@ -80,6 +91,10 @@ struct PartialReserveFees {
     pub flash_loan_fee_wad: Option<u64>,
     /// Amount of fee going to host account, if provided in liquidate and repay
     pub host_fee_percentage: Option<u8>,
+    /// Fee assessed on deposited liquidity, expressed as a Wad.
+    pub deposit_fee_wad: Option<u64>,
+    /// Fee assessed on redeemed liquidity, expressed as a Wad.
+    pub redeem_fee_wad: Option<u64>,
 }
 
 type Error = Box<dyn std::error::Error>;
@@ -243,6 +258,16 @@ fn main() {
                         .required(true)
                         .help("Initial amount of liquidity to deposit into the new reserve"),
                 )
+                .arg(
+                    Arg::with_name("initial_collateral_ratio")
+                        .long("initial-collateral-ratio")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("1")
+                        .help("Collateral tokens minted per liquidity token while the reserve is empty"),
+                )
                 .arg(
                     Arg::with_name("pyth_product")
                         .long("pyth-product")
@@ -370,6 +395,26 @@ fn main() {
                         .default_value("20")
                         .help("Amount of fee going to host account: [0, 100]"),
                 )
+                .arg(
+                    Arg::with_name("deposit_fee")
+                        .long("deposit-fee")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("0")
+                        .help("Fee assessed on deposit, expressed as a percentage: [0, 1)"),
+                )
+                .arg(
+                    Arg::with_name("redeem_fee")
+                        .long("redeem-fee")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .default_value("0")
+                        .help("Fee assessed on redeem, expressed as a percentage: [0, 1)"),
+                )
                 .arg(
                     Arg::with_name("protocol_liquidation_fee")
                         .long("protocol-liquidation-fee")
@@ -409,6 +454,46 @@ fn main() {
                         .default_value("18446744073709551615")
                         .help("Borrow limit"),
                 )
+                .arg(
+                    Arg::with_name("deposit_limit_value")
+                        .long("deposit-limit-value")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Deposit limit in the lending market's quote currency, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("borrow_limit_value")
+                        .long("borrow-limit-value")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Borrow limit in the lending market's quote currency, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("max_obligation_deposit_percentage")
+                        .long("max-obligation-deposit-percentage")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Maximum share of the reserve collateral a single obligation can deposit, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("max_obligation_borrow")
+                        .long("max-obligation-borrow")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(true)
+                        .default_value("0")
+                        .help("Maximum amount a single obligation can borrow, 0 for no limit"),
+                )
         )
         .subcommand(
             SubCommand::with_name("update-reserve")
@@ -531,6 +616,24 @@ fn main() {
                         .required(false)
                         .help("Amount of fee going to host account: [0, 100]"),
                 )
+                .arg(
+                    Arg::with_name("deposit_fee")
+                        .long("deposit-fee")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Fee assessed on deposit, expressed as a percentage: [0, 1)"),
+                )
+                .arg(
+                    Arg::with_name("redeem_fee")
+                        .long("redeem-fee")
+                        .validator(is_parsable::<f64>)
+                        .value_name("DECIMAL_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Fee assessed on redeem, expressed as a percentage: [0, 1)"),
+                )
                 .arg(
                     Arg::with_name("protocol_liquidation_fee")
                         .long("protocol-liquidation-fee")
@@ -567,6 +670,42 @@ fn main() {
                         .required(false)
                         .help("Borrow Limit"),
                 )
+                .arg(
+                    Arg::with_name("deposit_limit_value")
+                        .long("deposit-limit-value")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Deposit Limit in the lending market's quote currency, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("borrow_limit_value")
+                        .long("borrow-limit-value")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Borrow Limit in the lending market's quote currency, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("max_obligation_deposit_percentage")
+                        .long("max-obligation-deposit-percentage")
+                        .validator(is_parsable::<u8>)
+                        .value_name("INTEGER_PERCENT")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Maximum share of the reserve collateral a single obligation can deposit, 0 for no limit"),
+                )
+                .arg(
+                    Arg::with_name("max_obligation_borrow")
+                        .long("max-obligation-borrow")
+                        .validator(is_parsable::<u64>)
+                        .value_name("INTEGER")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Maximum amount a single obligation can borrow, 0 for no limit"),
+                )
                 .arg(
                     Arg::with_name("fee_receiver")
                         .long("fee-receiver")
@@ -604,6 +743,70 @@ fn main() {
                         .help("Switchboard price feed account: https://switchboard.xyz/#/explorer"),
                 )
         )
+        .subcommand(
+            SubCommand::with_name("close-obligation")
+                .about("Close an empty obligation and reclaim its rent")
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("obligation_owner")
+                        .long("obligation-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Owner of the obligation"),
+                )
+                .arg(
+                    Arg::with_name("destination")
+                        .long("destination")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(false)
+                        .help("Account to receive the obligation rent lamports, defaults to the obligation owner"),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("transfer-obligation")
+                .about("Transfer an obligation to a new owner")
+                .arg(
+                    Arg::with_name("obligation")
+                        .long("obligation")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Obligation address"),
+                )
+                // @TODO: use is_valid_signer
+                .arg(
+                    Arg::with_name("obligation_owner")
+                        .long("obligation-owner")
+                        .validator(is_keypair)
+                        .value_name("KEYPAIR")
+                        .takes_value(true)
+                        .required(true)
+                        .help("Current owner of the obligation"),
+                )
+                .arg(
+                    Arg::with_name("new_owner")
+                        .long("new-owner")
+                        .validator(is_pubkey)
+                        .value_name("PUBKEY")
+                        .takes_value(true)
+                        .required(true)
+                        .help("New owner of the obligation"),
+                )
+        )
         .get_matches();
 
     let mut wallet_manager = None;
@@ -666,6 +869,8 @@ fn main() {
             let lending_market_pubkey = pubkey_of(arg_matches, "lending_market").unwrap();
             let source_liquidity_pubkey = pubkey_of(arg_matches, "source_liquidity").unwrap();
             let ui_amount = value_of(arg_matches, "liquidity_amount").unwrap();
+            let initial_collateral_ratio =
+                value_of(arg_matches, "initial_collateral_ratio").unwrap();
             let pyth_product_pubkey = pubkey_of(arg_matches, "pyth_product").unwrap();
             let pyth_price_pubkey = pubkey_of(arg_matches, "pyth_price").unwrap();
             let switchboard_feed_pubkey = pubkey_of(arg_matches, "switchboard_feed").unwrap();
@@ -680,11 +885,20 @@ fn main() {
             let borrow_fee = value_of::<f64>(arg_matches, "borrow_fee").unwrap();
             let flash_loan_fee = value_of::<f64>(arg_matches, "flash_loan_fee").unwrap();
             let host_fee_percentage = value_of(arg_matches, "host_fee_percentage").unwrap();
+            let deposit_fee = value_of::<f64>(arg_matches, "deposit_fee").unwrap();
+            let redeem_fee = value_of::<f64>(arg_matches, "redeem_fee").unwrap();
             let deposit_limit = value_of(arg_matches, "deposit_limit").unwrap();
             let borrow_limit = value_of(arg_matches, "borrow_limit").unwrap();
+            let deposit_limit_value = value_of(arg_matches, "deposit_limit_value").unwrap();
+            let borrow_limit_value = value_of(arg_matches, "borrow_limit_value").unwrap();
+            let max_obligation_deposit_percentage =
+                value_of(arg_matches, "max_obligation_deposit_percentage").unwrap();
+            let max_obligation_borrow = value_of(arg_matches, "max_obligation_borrow").unwrap();
 
             let borrow_fee_wad = (borrow_fee * WAD as f64) as u64;
             let flash_loan_fee_wad = (flash_loan_fee * WAD as f64) as u64;
+            let deposit_fee_wad = (deposit_fee * WAD as f64) as u64;
+            let redeem_fee_wad = (redeem_fee * WAD as f64) as u64;
 
             let liquidity_fee_receiver_keypair = Keypair::new();
             let protocol_liquidation_fee =
@@ -707,6 +921,8 @@ fn main() {
             let liquidity_amount = ui_amount_to_amount(ui_amount, source_liquidity_mint.decimals);
             let deposit_limit = ui_amount_to_amount(deposit_limit, source_liquidity_mint.decimals);
             let borrow_limit = ui_amount_to_amount(borrow_limit, source_liquidity_mint.decimals);
+            let max_obligation_borrow =
+                ui_amount_to_amount(max_obligation_borrow, source_liquidity_mint.decimals);
 
             command_add_reserve(
                 &mut config,
@@ -723,13 +939,20 @@ fn main() {
                         borrow_fee_wad,
                         flash_loan_fee_wad,
                         host_fee_percentage,
+                        deposit_fee_wad,
+                        redeem_fee_wad,
                     },
                     deposit_limit,
                     borrow_limit,
                     fee_receiver: liquidity_fee_receiver_keypair.pubkey(),
                     protocol_liquidation_fee,
                     protocol_take_rate,
+                    deposit_limit_value,
+                    borrow_limit_value,
+                    max_obligation_deposit_percentage,
+                    max_obligation_borrow,
                 },
+                initial_collateral_ratio,
                 source_liquidity_pubkey,
                 source_liquidity_owner_keypair,
                 lending_market_pubkey,
@@ -756,8 +979,15 @@ fn main() {
             let borrow_fee = value_of::<f64>(arg_matches, "borrow_fee");
             let flash_loan_fee = value_of::<f64>(arg_matches, "flash_loan_fee");
             let host_fee_percentage = value_of(arg_matches, "host_fee_percentage");
+            let deposit_fee = value_of::<f64>(arg_matches, "deposit_fee");
+            let redeem_fee = value_of::<f64>(arg_matches, "redeem_fee");
             let deposit_limit = value_of(arg_matches, "deposit_limit");
             let borrow_limit = value_of(arg_matches, "borrow_limit");
+            let deposit_limit_value = value_of(arg_matches, "deposit_limit_value");
+            let borrow_limit_value = value_of(arg_matches, "borrow_limit_value");
+            let max_obligation_deposit_percentage =
+                value_of(arg_matches, "max_obligation_deposit_percentage");
+            let max_obligation_borrow = value_of(arg_matches, "max_obligation_borrow");
             let fee_receiver = pubkey_of(arg_matches, "fee_receiver");
             let protocol_liquidation_fee = value_of(arg_matches, "protocol_liquidation_fee");
             let protocol_take_rate = value_of(arg_matches, "protocol_take_rate");
@@ -767,6 +997,8 @@ fn main() {
 
             let borrow_fee_wad = borrow_fee.map(|fee| (fee * WAD as f64) as u64);
             let flash_loan_fee_wad = flash_loan_fee.map(|fee| (fee * WAD as f64) as u64);
+            let deposit_fee_wad = deposit_fee.map(|fee| (fee * WAD as f64) as u64);
+            let redeem_fee_wad = redeem_fee.map(|fee| (fee * WAD as f64) as u64);
 
             command_update_reserve(
                 &mut config,
@@ -782,12 +1014,18 @@ fn main() {
                         borrow_fee_wad,
                         flash_loan_fee_wad,
                         host_fee_percentage,
+                        deposit_fee_wad,
+                        redeem_fee_wad,
                     },
                     deposit_limit,
                     borrow_limit,
                     fee_receiver,
                     protocol_liquidation_fee,
                     protocol_take_rate,
+                    deposit_limit_value,
+                    borrow_limit_value,
+                    max_obligation_deposit_percentage,
+                    max_obligation_borrow,
                 },
                 pyth_product_pubkey,
                 pyth_price_pubkey,
@@ -797,6 +1035,31 @@ fn main() {
                 lending_market_owner_keypair,
             )
         }
+        ("close-obligation", Some(arg_matches)) => {
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            let obligation_owner_keypair = keypair_of(arg_matches, "obligation_owner").unwrap();
+            let destination_pubkey = pubkey_of(arg_matches, "destination")
+                .unwrap_or_else(|| obligation_owner_keypair.pubkey());
+
+            command_close_obligation(
+                &config,
+                obligation_pubkey,
+                obligation_owner_keypair,
+                destination_pubkey,
+            )
+        }
+        ("transfer-obligation", Some(arg_matches)) => {
+            let obligation_pubkey = pubkey_of(arg_matches, "obligation").unwrap();
+            let obligation_owner_keypair = keypair_of(arg_matches, "obligation_owner").unwrap();
+            let new_owner = pubkey_of(arg_matches, "new_owner").unwrap();
+
+            command_transfer_obligation(
+                &config,
+                obligation_pubkey,
+                obligation_owner_keypair,
+                new_owner,
+            )
+        }
         _ => unreachable!(),
     }
     .map_err(|err| {
@@ -874,6 +1137,7 @@ fn command_add_reserve(
     config: &mut Config,
     liquidity_amount: u64,
     reserve_config: ReserveConfig,
+    initial_collateral_ratio: u64,
     source_liquidity_pubkey: Pubkey,
     source_liquidity_owner_keypair: Keypair,
     lending_market_pubkey: Pubkey,
@@ -1009,6 +1273,7 @@ fn command_add_reserve(
                 config.lending_program_id,
                 liquidity_amount,
                 reserve_config,
+                initial_collateral_ratio,
                 source_liquidity_pubkey,
                 user_collateral_keypair.pubkey(),
                 reserve_keypair.pubkey(),
@@ -1178,6 +1443,24 @@ fn command_update_reserve(
         reserve.config.fees.host_fee_percentage = reserve_config.fees.host_fee_percentage.unwrap();
     }
 
+    if reserve_config.fees.deposit_fee_wad.is_some() {
+        println!(
+            "Updating deposit_fee_wad from {} to {}",
+            reserve.config.fees.deposit_fee_wad,
+            reserve_config.fees.deposit_fee_wad.unwrap(),
+        );
+        reserve.config.fees.deposit_fee_wad = reserve_config.fees.deposit_fee_wad.unwrap();
+    }
+
+    if reserve_config.fees.redeem_fee_wad.is_some() {
+        println!(
+            "Updating redeem_fee_wad from {} to {}",
+            reserve.config.fees.redeem_fee_wad,
+            reserve_config.fees.redeem_fee_wad.unwrap(),
+        );
+        reserve.config.fees.redeem_fee_wad = reserve_config.fees.redeem_fee_wad.unwrap();
+    }
+
     if reserve_config.deposit_limit.is_some() {
         println!(
             "Updating deposit_limit from {} to {}",
@@ -1232,6 +1515,43 @@ fn command_update_reserve(
         reserve.config.protocol_take_rate = reserve_config.protocol_take_rate.unwrap();
     }
 
+    if reserve_config.deposit_limit_value.is_some() {
+        println!(
+            "Updating deposit_limit_value from {} to {}",
+            reserve.config.deposit_limit_value,
+            reserve_config.deposit_limit_value.unwrap(),
+        );
+        reserve.config.deposit_limit_value = reserve_config.deposit_limit_value.unwrap();
+    }
+
+    if reserve_config.borrow_limit_value.is_some() {
+        println!(
+            "Updating borrow_limit_value from {} to {}",
+            reserve.config.borrow_limit_value,
+            reserve_config.borrow_limit_value.unwrap(),
+        );
+        reserve.config.borrow_limit_value = reserve_config.borrow_limit_value.unwrap();
+    }
+
+    if reserve_config.max_obligation_deposit_percentage.is_some() {
+        println!(
+            "Updating max_obligation_deposit_percentage from {} to {}",
+            reserve.config.max_obligation_deposit_percentage,
+            reserve_config.max_obligation_deposit_percentage.unwrap(),
+        );
+        reserve.config.max_obligation_deposit_percentage =
+            reserve_config.max_obligation_deposit_percentage.unwrap();
+    }
+
+    if reserve_config.max_obligation_borrow.is_some() {
+        println!(
+            "Updating max_obligation_borrow from {} to {}",
+            reserve.config.max_obligation_borrow,
+            reserve_config.max_obligation_borrow.unwrap(),
+        );
+        reserve.config.max_obligation_borrow = reserve_config.max_obligation_borrow.unwrap();
+    }
+
     let mut new_pyth_product_pubkey = solend_program::NULL_PUBKEY;
     if pyth_price_pubkey.is_some() {
         println!(
@@ -1277,6 +1597,78 @@ fn command_update_reserve(
     Ok(())
 }
 
+fn command_close_obligation(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    obligation_owner_keypair: Keypair,
+    destination_pubkey: Pubkey,
+) -> CommandResult {
+    let obligation_account = config.rpc_client.get_account(&obligation_pubkey)?;
+    let obligation = Obligation::unpack(obligation_account.data.borrow())?;
+    println!(
+        "Closing obligation {} and sending {} SOL to {}",
+        obligation_pubkey,
+        lamports_to_sol(obligation_account.lamports),
+        destination_pubkey
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[close_obligation(
+            config.lending_program_id,
+            obligation_pubkey,
+            obligation.lending_market,
+            obligation_owner_keypair.pubkey(),
+            destination_pubkey,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(transaction.message()))?;
+
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), &obligation_owner_keypair],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
+fn command_transfer_obligation(
+    config: &Config,
+    obligation_pubkey: Pubkey,
+    obligation_owner_keypair: Keypair,
+    new_owner: Pubkey,
+) -> CommandResult {
+    let obligation_account = config.rpc_client.get_account(&obligation_pubkey)?;
+    let obligation = Obligation::unpack(obligation_account.data.borrow())?;
+    println!(
+        "Transferring obligation {} from {} to {}",
+        obligation_pubkey, obligation.owner, new_owner
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[transfer_obligation_ownership(
+            config.lending_program_id,
+            obligation_pubkey,
+            obligation.lending_market,
+            obligation_owner_keypair.pubkey(),
+            new_owner,
+        )],
+        Some(&config.fee_payer.pubkey()),
+    );
+
+    let (recent_blockhash, fee_calculator) = config.rpc_client.get_recent_blockhash()?;
+    check_fee_payer_balance(config, fee_calculator.calculate_fee(transaction.message()))?;
+
+    transaction.sign(
+        &vec![config.fee_payer.as_ref(), &obligation_owner_keypair],
+        recent_blockhash,
+    );
+    send_transaction(config, transaction)?;
+    Ok(())
+}
+
 // HELPERS
 
 fn check_fee_payer_balance(config: &Config, required_balance: u64) -> Result<(), Error> {
@@ -167,6 +167,49 @@ pub enum LendingError {
     /// Insufficent protocol fees to redeem or no liquidity availible to process redeem
     #[error("Insufficent protocol fees to claim or no liquidity availible")]
     InsufficientProtocolFeesToRedeem,
+    /// Amount received is less than the minimum amount specified
+    #[error("Amount received is less than the minimum amount specified")]
+    ExceededSlippage,
+    /// Obligation still has deposits or borrows
+    #[error("Obligation deposits and borrows must be empty")]
+    ObligationNotEmpty,
+    /// Flash repay does not match a flash borrow
+    #[error("Flash repay does not match a flash borrow in this transaction")]
+    InvalidFlashRepay,
+
+    // 50
+    /// No matching flash repay found for a flash borrow
+    #[error("No matching flash repay found for flash borrow")]
+    NoFlashRepayFound,
+    /// Multiple flash borrows in one transaction
+    #[error("Multiple flash borrows not allowed in the same transaction")]
+    MultipleFlashBorrows,
+    /// Flash borrow and repay must be top-level instructions
+    #[error("Flash loans cannot be invoked via CPI")]
+    FlashLoanCpi,
+    /// Flash loan whitelist has no room for another receiver program
+    #[error("Flash loan whitelist is full")]
+    FlashLoanWhitelistFull,
+    /// Bad debt can only be written off once all of the obligation collateral is liquidated
+    #[error("Obligation still has collateral to liquidate")]
+    ObligationHasCollateral,
+
+    // 55
+    /// Reserve is reduce only or deprecated
+    #[error("Reserve is not active and only allows reducing positions")]
+    ReserveNotActive,
+    /// Reserve still has liquidity, collateral, borrows or fees
+    #[error("Reserve liquidity, collateral, borrows and fees must be empty")]
+    ReserveNotEmpty,
+    /// Token close account failed
+    #[error("Token close account failed")]
+    TokenCloseAccountFailed,
+    /// Token set authority failed
+    #[error("Token set authority failed")]
+    TokenSetAuthorityFailed,
+    /// Reserve outflow rate limit exceeded
+    #[error("Outflow rate limit exceeded")]
+    OutflowRateLimitExceeded,
 }
 
 impl From<LendingError> for ProgramError {
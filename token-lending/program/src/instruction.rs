@@ -2,16 +2,21 @@
 
 use crate::{
     error::LendingError,
-    state::{ReserveConfig, ReserveFees},
+    state::{
+        FeeDistributionReceiver, RateLimiterConfig, ReserveConfig, ReserveFees, ReserveStatus,
+    },
 };
 use solana_program::{
     instruction::{AccountMeta, Instruction},
     msg,
     program_error::ProgramError,
     pubkey::{Pubkey, PUBKEY_BYTES},
-    sysvar,
+    system_program, sysvar,
+};
+use std::{
+    convert::{TryFrom, TryInto},
+    mem::size_of,
 };
-use std::{convert::TryInto, mem::size_of};
 
 /// Instructions supported by the lending program.
 #[derive(Clone, Debug, PartialEq)]
@@ -74,8 +79,12 @@ pub enum LendingInstruction {
     InitReserve {
         /// Initial amount of liquidity to deposit into the new reserve
         liquidity_amount: u64,
-        /// Reserve configuration values
+        /// Reserve configuration values. Fields appended after the original config layout may be
+        /// omitted and default to zero.
         config: ReserveConfig,
+        /// Collateral tokens minted per liquidity token while the reserve is empty, which sets
+        /// the granularity of the reserve's collateral token. Defaults to 1 when omitted.
+        initial_collateral_ratio: u64,
     },
 
     // 3
@@ -121,7 +130,7 @@ pub enum LendingInstruction {
     ///   0. `[writable]` Source collateral token account.
     ///                     $authority can transfer $collateral_amount.
     ///   1. `[writable]` Destination liquidity token account.
-    ///   2. `[writable]` Reserve account.
+    ///   2. `[writable]` Reserve account - refreshed if its outflows are rate limited.
     ///   3. `[writable]` Reserve collateral SPL Token mint.
     ///   4. `[writable]` Reserve liquidity supply SPL Token account.
     ///   5. `[]` Lending market account.
@@ -145,6 +154,8 @@ pub enum LendingInstruction {
     ///   3. `[]` Clock sysvar.
     ///   4. `[]` Rent sysvar.
     ///   5. `[]` Token program id.
+    ///   6. `[optional]` Referrer which accrues the host fees of the obligation's borrows.
+    ///                     Cannot be the obligation owner.
     InitObligation,
 
     // 7
@@ -172,7 +183,7 @@ pub enum LendingInstruction {
     ///   2. `[writable]` Deposit reserve account.
     ///   3. `[writable]` Obligation account.
     ///   4. `[]` Lending market account.
-    ///   5. `[signer]` Obligation owner.
+    ///   5. `[signer]` Obligation owner or delegate.
     ///   6. `[signer]` User transfer authority ($authority).
     ///   7. `[]` Clock sysvar.
     ///   8. `[]` Token program id.
@@ -193,7 +204,7 @@ pub enum LendingInstruction {
     ///   3. `[writable]` Obligation account - refreshed.
     ///   4. `[]` Lending market account.
     ///   5. `[]` Derived lending market authority.
-    ///   6. `[signer]` Obligation owner.
+    ///   6. `[signer]` Obligation owner or delegate.
     ///   7. `[]` Clock sysvar.
     ///   8. `[]` Token program id.
     WithdrawObligationCollateral {
@@ -216,14 +227,14 @@ pub enum LendingInstruction {
     ///   4. `[writable]` Obligation account - refreshed.
     ///   5. `[]` Lending market account.
     ///   6. `[]` Derived lending market authority.
-    ///   7. `[signer]` Obligation owner.
+    ///   7. `[signer]` Obligation owner or delegate.
     ///   8. `[]` Clock sysvar.
     ///   9. `[]` Token program id.
     ///   10 `[optional, writable]` Host fee receiver account.
+    ///                     Must be the referrer fees account if the obligation has a referrer.
     BorrowObligationLiquidity {
         /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
         liquidity_amount: u64,
-        // @TODO: slippage constraint - https://git.io/JmV67
     },
 
     // 11
@@ -292,6 +303,8 @@ pub enum LendingInstruction {
     ///   8. `[]` Flash loan receiver program id.
     ///             Must implement an instruction that has tag of 0 and a signature of `(amount: u64)`
     ///             This instruction must return the amount to the source liquidity account.
+    ///   9. `[optional]` Lending market flash loan whitelist account.
+    ///                     Not passed to the receiving program.
     ///   .. `[any]` Additional accounts expected by the receiving program's `ReceiveFlashLoan` instruction.
     ///
     ///   The flash loan receiver program that is to be invoked should contain an instruction with
@@ -328,7 +341,7 @@ pub enum LendingInstruction {
     ///   6. `[]` Derived lending market authority.
     ///   7. `[writable]` Destination deposit reserve collateral supply SPL Token account.
     ///   8. `[writable]` Obligation account.
-    ///   9. `[signer]` Obligation owner.
+    ///   9. `[signer]` Obligation owner or delegate.
     ///   10 `[]` Pyth price oracle account.
     ///   11 `[]` Switchboard price feed oracle account.
     ///   12 `[signer]` User transfer authority ($authority).
@@ -354,7 +367,7 @@ pub enum LendingInstruction {
     ///   6. `[writable]` User liquidity token account.
     ///   7. `[writable]` Reserve collateral SPL Token mint.
     ///   8. `[writable]` Reserve liquidity supply SPL Token account.
-    ///   9. `[signer]` Obligation owner
+    ///   9. `[signer]` Obligation owner or delegate
     ///   10 `[signer]` User transfer authority ($authority).
     ///   11. `[]` Clock sysvar.
     ///   12. `[]` Token program id.
@@ -376,7 +389,8 @@ pub enum LendingInstruction {
     ///   6 `[]` Pyth price key.
     ///   7 `[]` Switchboard key.
     UpdateReserveConfig {
-        /// Reserve config to update to
+        /// Reserve config to update to. Fields appended after the original config layout may be
+        /// omitted and default to zero.
         config: ReserveConfig,
     },
 
@@ -418,479 +432,2409 @@ pub enum LendingInstruction {
     ///   4. `[]` Derived lending market authority.
     ///   5. `[]` Token program id.
     RedeemFees,
-}
-
-impl LendingInstruction {
-    /// Unpacks a byte buffer into a [LendingInstruction](enum.LendingInstruction.html).
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (&tag, rest) = input
-            .split_first()
-            .ok_or(LendingError::InstructionUnpackError)?;
-        Ok(match tag {
-            0 => {
-                let (owner, rest) = Self::unpack_pubkey(rest)?;
-                let (quote_currency, _rest) = Self::unpack_bytes32(rest)?;
-                Self::InitLendingMarket {
-                    owner,
-                    quote_currency: *quote_currency,
-                }
-            }
-            1 => {
-                let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
-                Self::SetLendingMarketOwner { new_owner }
-            }
-            2 => {
-                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
-                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
-                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
-                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
-                let (deposit_limit, rest) = Self::unpack_u64(rest)?;
-                let (borrow_limit, rest) = Self::unpack_u64(rest)?;
-                let (fee_receiver, rest) = Self::unpack_pubkey(rest)?;
-                let (protocol_liquidation_fee, rest) = Self::unpack_u8(rest)?;
-                let (protocol_take_rate, _rest) = Self::unpack_u8(rest)?;
-                Self::InitReserve {
-                    liquidity_amount,
-                    config: ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees: ReserveFees {
-                            borrow_fee_wad,
-                            flash_loan_fee_wad,
-                            host_fee_percentage,
-                        },
-                        deposit_limit,
-                        borrow_limit,
-                        fee_receiver,
-                        protocol_liquidation_fee,
-                        protocol_take_rate,
-                    },
-                }
-            }
-            3 => Self::RefreshReserve,
-            4 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositReserveLiquidity { liquidity_amount }
-            }
-            5 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::RedeemReserveCollateral { collateral_amount }
-            }
-            6 => Self::InitObligation,
-            7 => Self::RefreshObligation,
-            8 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositObligationCollateral { collateral_amount }
-            }
-            9 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawObligationCollateral { collateral_amount }
-            }
-            10 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::BorrowObligationLiquidity { liquidity_amount }
-            }
-            11 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::RepayObligationLiquidity { liquidity_amount }
-            }
-            12 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::LiquidateObligation { liquidity_amount }
-            }
-            13 => {
-                let (amount, _rest) = Self::unpack_u64(rest)?;
-                Self::FlashLoan { amount }
-            }
-            14 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount }
-            }
-            15 => {
-                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount }
-            }
-            16 => {
-                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
-                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
-                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
-                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
-                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
-                let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
-                let (deposit_limit, rest) = Self::unpack_u64(rest)?;
-                let (borrow_limit, rest) = Self::unpack_u64(rest)?;
-                let (fee_receiver, rest) = Self::unpack_pubkey(rest)?;
-                let (protocol_liquidation_fee, rest) = Self::unpack_u8(rest)?;
-                let (protocol_take_rate, _rest) = Self::unpack_u8(rest)?;
-                Self::UpdateReserveConfig {
-                    config: ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees: ReserveFees {
-                            borrow_fee_wad,
-                            flash_loan_fee_wad,
-                            host_fee_percentage,
-                        },
-                        deposit_limit,
-                        borrow_limit,
-                        fee_receiver,
-                        protocol_liquidation_fee,
-                        protocol_take_rate,
-                    },
-                }
-            }
-            17 => {
-                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount }
-            }
-            18 => Self::RedeemFees,
-            _ => {
-                msg!("Instruction cannot be unpacked");
-                return Err(LendingError::InstructionUnpackError.into());
-            }
-        })
-    }
 
-    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
-        if input.len() < 8 {
-            msg!("u64 cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (bytes, rest) = input.split_at(8);
-        let value = bytes
-            .get(..8)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u64::from_le_bytes)
-            .ok_or(LendingError::InstructionUnpackError)?;
-        Ok((value, rest))
-    }
+    // 19
+    /// Repay borrowed liquidity to one or more reserves to receive collateral at a discount from
+    /// an unhealthy obligation. The close factor is applied across the whole batch rather than to
+    /// each liquidation. Requires a refreshed obligation and reserves.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account - refreshed.
+    ///   1. `[]` Lending market account.
+    ///   2. `[]` Derived lending market authority.
+    ///   3. `[signer]` User transfer authority ($authority).
+    ///   4. `[]` Token program id.
+    ///
+    ///   For each liquidity amount, in order:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///   2. `[writable]` Repay reserve account - refreshed.
+    ///   3. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   4. `[]` Withdraw reserve account - refreshed.
+    ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    LiquidateObligationBatch {
+        /// Amounts of liquidity to repay - u64::MAX for up to 100% of borrowed amount
+        liquidity_amounts: Vec<u64>,
+    },
 
-    fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
-        if input.is_empty() {
-            msg!("u8 cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (bytes, rest) = input.split_at(1);
-        let value = bytes
-            .get(..1)
-            .and_then(|slice| slice.try_into().ok())
-            .map(u8::from_le_bytes)
-            .ok_or(LendingError::InstructionUnpackError)?;
-        Ok((value, rest))
-    }
+    // 20
+    /// Borrow liquidity from a reserve by depositing collateral tokens, failing if less than
+    /// $min_receive_amount is received after fees. Requires a refreshed obligation and reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source borrow reserve liquidity supply SPL Token account.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by borrow reserve liquidity mint.
+    ///   2. `[writable]` Borrow reserve account - refreshed.
+    ///   3. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   4. `[writable]` Obligation account - refreshed.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[signer]` Obligation owner or delegate.
+    ///   8. `[]` Clock sysvar.
+    ///   9. `[]` Token program id.
+    ///   10 `[optional, writable]` Host fee receiver account.
+    ///                     Must be the referrer fees account if the obligation has a referrer.
+    BorrowObligationLiquidityWithMinReceive {
+        /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power
+        liquidity_amount: u64,
+        /// Minimum amount of liquidity to receive after fees
+        min_receive_amount: u64,
+    },
 
-    fn unpack_bytes32(input: &[u8]) -> Result<(&[u8; 32], &[u8]), ProgramError> {
-        if input.len() < 32 {
-            msg!("32 bytes cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (bytes, rest) = input.split_at(32);
-        Ok((
-            bytes
-                .try_into()
-                .map_err(|_| LendingError::InstructionUnpackError)?,
-            rest,
-        ))
-    }
+    // 21
+    /// Repay borrowed liquidity to a reserve to receive collateral at a discount from an unhealthy
+    /// obligation, failing if less than $min_collateral_received is received. Requires a refreshed
+    /// obligation and reserves.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount.
+    ///   1. `[writable]` Destination collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///   2. `[writable]` Repay reserve account - refreshed.
+    ///   3. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   4. `[]` Withdraw reserve account - refreshed.
+    ///   5. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   6. `[writable]` Obligation account - refreshed.
+    ///   7. `[]` Lending market account.
+    ///   8. `[]` Derived lending market authority.
+    ///   9. `[signer]` User transfer authority ($authority).
+    ///   10 `[]` Clock sysvar.
+    ///   11 `[]` Token program id.
+    LiquidateObligationWithMinCollateral {
+        /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
+        liquidity_amount: u64,
+        /// Minimum amount of collateral to receive
+        min_collateral_received: u64,
+    },
 
-    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
-        if input.len() < PUBKEY_BYTES {
-            msg!("Pubkey cannot be unpacked");
-            return Err(LendingError::InstructionUnpackError.into());
-        }
-        let (key, rest) = input.split_at(PUBKEY_BYTES);
-        let pk = Pubkey::new(key);
+    // 22
+    /// Redeem collateral from a reserve in exchange for liquidity, failing if less than
+    /// $min_liquidity_out is received.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source collateral token account.
+    ///                     $authority can transfer $collateral_amount.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///   2. `[writable]` Reserve account - refreshed if its outflows are rate limited.
+    ///   3. `[writable]` Reserve collateral SPL Token mint.
+    ///   4. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   5. `[]` Lending market account.
+    ///   6. `[]` Derived lending market authority.
+    ///   7. `[signer]` User transfer authority ($authority).
+    ///   8. `[]` Clock sysvar.
+    ///   9. `[]` Token program id.
+    RedeemReserveCollateralWithMinLiquidity {
+        /// Amount of collateral tokens to redeem in exchange for liquidity
+        collateral_amount: u64,
+        /// Minimum amount of liquidity to receive
+        min_liquidity_out: u64,
+    },
+
+    // 23
+    /// Move a legacy obligation into a new account using the current, larger obligation layout
+    /// and program version. The legacy obligation account is closed and its lamports are sent to
+    /// the destination.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Legacy obligation account.
+    ///   1. `[writable]` New obligation account - uninitialized.
+    ///   2. `[]` Lending market account.
+    ///   3. `[signer]` Obligation owner.
+    ///   4. `[writable]` Destination account for the legacy obligation lamports.
+    ///   5. `[]` Rent sysvar.
+    UpgradeObligation,
+
+    // 24
+    /// Close an obligation with no deposits or borrows and reclaim its rent lamports.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Obligation owner.
+    ///   3. `[writable]` Destination account for the obligation lamports.
+    CloseObligation,
+
+    // 25
+    /// Initializes a new lending market obligation at the address derived from the lending
+    /// market, obligation owner and index. See `find_obligation_address`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account - uninitialized, derived address.
+    ///   1. `[]` Lending market account.
+    ///   2. `[writable, signer]` Obligation owner, pays for the obligation account.
+    ///   3. `[]` Clock sysvar.
+    ///   4. `[]` Rent sysvar.
+    ///   5. `[]` System program id.
+    ///   6. `[optional]` Referrer which accrues the host fees of the obligation's borrows.
+    ///                     Cannot be the obligation owner.
+    InitDerivedObligation {
+        /// Index of the obligation among the owner's obligations in the lending market
+        index: u8,
+    },
+
+    // 26
+    /// Sets the delegate authority of an obligation and the permissions granted to it. Repaying
+    /// is permissionless, so a delegate with no permissions can only repay.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Obligation owner.
+    SetObligationDelegate {
+        /// The new delegate, or the default pubkey to remove the delegate
+        delegate: Pubkey,
+        /// Bitmask of `OBLIGATION_DELEGATE_*` permissions granted to the delegate
+        permissions: u8,
+    },
+
+    // 27
+    /// Transfers an obligation to a new owner. The obligation delegate is removed, and derived
+    /// obligations keep the address derived from the previous owner.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Current obligation owner.
+    TransferObligationOwnership {
+        /// The new owner
+        new_owner: Pubkey,
+    },
+
+    // 28
+    /// Deposit collateral into one reserve and withdraw collateral from another for the same
+    /// obligation. The obligation only needs to be healthy after both legs of the swap. Requires
+    /// a refreshed obligation and reserves.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source deposit collateral token account.
+    ///                     Minted by deposit reserve collateral mint.
+    ///                     $authority can transfer $deposit_collateral_amount.
+    ///   1. `[writable]` Deposit reserve collateral supply SPL Token account.
+    ///   2. `[]` Deposit reserve account - refreshed.
+    ///   3. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    ///   4. `[writable]` Destination withdraw collateral token account.
+    ///                     Minted by withdraw reserve collateral mint.
+    ///   5. `[]` Withdraw reserve account - refreshed.
+    ///   6. `[writable]` Obligation account - refreshed.
+    ///   7. `[]` Lending market account.
+    ///   8. `[]` Derived lending market authority.
+    ///   9. `[signer]` Obligation owner or delegate.
+    ///   10 `[signer]` User transfer authority ($authority).
+    ///   11 `[]` Clock sysvar.
+    ///   12 `[]` Token program id.
+    SwapObligationCollateral {
+        /// Amount of collateral tokens to deposit
+        deposit_collateral_amount: u64,
+        /// Amount of collateral tokens to withdraw - u64::MAX for all deposited collateral
+        withdraw_collateral_amount: u64,
+    },
+
+    // 29
+    /// Repay borrowed liquidity to one reserve and borrow liquidity from another for the same
+    /// obligation. The obligation only needs to be healthy after both legs of the swap, and the
+    /// borrow reserve fees are charged as with BorrowObligationLiquidity. Requires a refreshed
+    /// obligation and reserves.
+    ///
+    /// The repay liquidity can itself be flash borrowed, so refinancing a borrow does not require
+    /// holding the repay asset up front.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source repay liquidity token account.
+    ///                     Minted by repay reserve liquidity mint.
+    ///                     $authority can transfer $repay_liquidity_amount.
+    ///   1. `[writable]` Repay reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Repay reserve account - refreshed.
+    ///   3. `[writable]` Borrow reserve liquidity supply SPL Token account.
+    ///   4. `[writable]` Destination borrow liquidity token account.
+    ///                     Minted by borrow reserve liquidity mint.
+    ///   5. `[writable]` Borrow reserve account - refreshed.
+    ///   6. `[writable]` Borrow reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   7. `[writable]` Obligation account - refreshed.
+    ///   8. `[]` Lending market account.
+    ///   9. `[]` Derived lending market authority.
+    ///   10 `[signer]` Obligation owner or delegate.
+    ///   11 `[signer]` User transfer authority ($authority).
+    ///   12 `[]` Clock sysvar.
+    ///   13 `[]` Token program id.
+    ///   14 `[optional, writable]` Host fee receiver account.
+    ///                     Must be the referrer fees account if the obligation has a referrer.
+    SwapObligationDebt {
+        /// Amount of liquidity to repay - u64::MAX for 100% of borrowed amount
+        repay_liquidity_amount: u64,
+        /// Amount of liquidity to borrow - u64::MAX for 100% of borrowing power after repaying
+        borrow_liquidity_amount: u64,
+    },
+
+    // 30
+    /// Flash borrow reserve liquidity. A `FlashRepayReserveLiquidity` instruction for the same
+    /// reserve and amount must follow later in the same transaction, which is verified with the
+    /// instructions sysvar. Must be a top-level instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Must match the reserve liquidity supply.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Instructions sysvar.
+    ///   6. `[]` Token program id.
+    FlashBorrowReserveLiquidity {
+        /// Amount of liquidity to flash borrow
+        liquidity_amount: u64,
+    },
+
+    // 31
+    /// Flash repay reserve liquidity borrowed by a preceding `FlashBorrowReserveLiquidity`
+    /// instruction, plus the flash loan fees. Must be a top-level instruction.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///                     $authority can transfer $liquidity_amount plus fees.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Must match the reserve liquidity supply.
+    ///   2. `[writable]` Reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   3. `[writable]` Host fee receiver account.
+    ///   4. `[writable]` Reserve account.
+    ///   5. `[]` Lending market account.
+    ///   6. `[signer]` User transfer authority ($authority).
+    ///   7. `[]` Instructions sysvar.
+    ///   8. `[]` Token program id.
+    FlashRepayReserveLiquidity {
+        /// Amount of liquidity to repay, excluding fees - must match the flash borrow
+        liquidity_amount: u64,
+        /// Index of the `FlashBorrowReserveLiquidity` instruction in this transaction
+        borrow_instruction_index: u8,
+    },
+
+    // 32
+    /// Make a flash loan from one or more reserves of the same lending market. The receiver
+    /// program is invoked once with all of the loans and must return each of them plus fees.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` Lending market account.
+    ///   1. `[]` Derived lending market authority.
+    ///   2. `[]` Token program id.
+    ///   3. `[]` Flash loan receiver program id.
+    ///             Must implement an instruction that has tag of 1 and a signature of
+    ///             `(repay_amounts: Vec<u64>)`. This instruction must return each amount to
+    ///             the matching source liquidity account.
+    ///   4. `[optional]` Lending market flash loan whitelist account.
+    ///                     Not passed to the receiving program.
+    ///
+    ///   For each liquidity amount, in order:
+    ///
+    ///   0. `[writable]` Source liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///                     Must match the reserve liquidity supply.
+    ///   1. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   2. `[writable]` Reserve account.
+    ///   3. `[writable]` Flash loan fee receiver account.
+    ///                     Must match the reserve liquidity fee receiver.
+    ///   4. `[writable]` Host fee receiver.
+    ///
+    ///   .. `[any]` Additional accounts expected by the receiving program's
+    ///              `ReceiveBatchFlashLoan` instruction.
+    ///
+    ///   The flash loan receiver program that is to be invoked should contain an instruction with
+    ///   tag `1` and accept the total amount (including fee) of each loan that needs to be paid
+    ///   back. The accounts that are passed to this instruction are:
+    ///
+    ///   For each repay amount, in order:
+    ///
+    ///   0. `[writable]` Source liquidity (matching the destination from above).
+    ///   1. `[writable]` Destination liquidity (matching the source from above).
+    ///
+    ///   .. `[]` Token program id.
+    ///   .. `[any]` Additional accounts provided to the lending program's `FlashLoanBatch`
+    ///              instruction above.
+    ///   ReceiveBatchFlashLoan {
+    ///       // Amounts that must be repaid by the receiver program, in order
+    ///       repay_amounts: Vec<u64>
+    ///   }
+    FlashLoanBatch {
+        /// The amounts that are to be borrowed - u64::MAX for up to 100% of available liquidity
+        liquidity_amounts: Vec<u64>,
+    },
+
+    // 33
+    /// Add a receiver program to the lending market flash loan whitelist, or update its flash
+    /// loan fee. Whitelisted receiver programs pay the lower of their fee and the reserve flash
+    /// loan fee. The whitelist account is created on first use.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Flash loan whitelist account - derived from the lending market.
+    ///   1. `[]` Lending market account.
+    ///   2. `[writable, signer]` Lending market owner - pays to create the whitelist account.
+    ///   3. `[]` Rent sysvar.
+    ///   4. `[]` System program.
+    AddFlashLoanWhitelistEntry {
+        /// Flash loan receiver program id
+        receiver_program_id: Pubkey,
+        /// Flash loan fee, expressed as a Wad
+        flash_loan_fee_wad: u64,
+    },
+
+    // 34
+    /// Remove a receiver program from the lending market flash loan whitelist.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Flash loan whitelist account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    RemoveFlashLoanWhitelistEntry {
+        /// Flash loan receiver program id
+        receiver_program_id: Pubkey,
+    },
+
+    // 35
+    /// Initializes the referrer fees account which accrues a referrer's host fees for a reserve.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Referrer fees account - uninitialized, derived from the reserve and
+    ///                     referrer. See `find_referrer_fees_address`.
+    ///   1. `[]` Reserve account.
+    ///   2. `[]` Reserve liquidity mint.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Referrer.
+    ///   6. `[writable, signer]` Funding account, pays for the referrer fees account.
+    ///   7. `[]` Rent sysvar.
+    ///   8. `[]` System program id.
+    ///   9. `[]` Token program id.
+    InitReferrerFeesAccount,
+
+    // 36
+    /// Claim the host fees accrued in a referrer fees account.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Referrer fees account.
+    ///   1. `[]` Reserve account.
+    ///   2. `[writable]` Destination liquidity token account.
+    ///                     Minted by reserve liquidity mint.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[signer]` Referrer.
+    ///   6. `[]` Token program id.
+    ClaimReferralFees,
+
+    // 37
+    /// Mint the accumulated protocol fees of a reserve as collateral tokens, so they earn interest
+    /// and can be redeemed regardless of utilization. An alternative to `RedeemFees`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account - refreshed.
+    ///   1. `[writable]` Reserve collateral SPL Token mint.
+    ///   2. `[writable]` Destination collateral token account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[signer]` Lending market owner.
+    ///   5. `[]` Derived lending market authority.
+    ///   6. `[]` Token program id.
+    CollectProtocolFees,
+
+    // 38
+    /// Sets the receivers that share the owner portion of reserve fees. The fees are paid to the
+    /// reserve liquidity fee receivers and split with `DistributeFees`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Current owner.
+    SetLendingMarketFeeDistribution {
+        /// Fee distribution receivers, in the order their token accounts are passed to
+        /// `DistributeFees`
+        fee_distribution: Vec<FeeDistributionReceiver>,
+    },
+
+    // 39
+    /// Sets the percentage of reserve protocol fees set aside for the lending market insurance
+    /// fund. Reserves pick up the new percentage when they are passed to this instruction, and
+    /// new reserves when they are initialized.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Lending market account.
+    ///   1. `[signer]` Current owner.
+    ///   .. `[writable]` Reserve accounts - refreshed.
+    SetLendingMarketInsuranceFundFee {
+        /// Percentage of protocol fees set aside for the insurance fund
+        insurance_fund_fee_percentage: u8,
+    },
+
+    // 40
+    /// Initializes the lending market insurance fund account which holds the insurance fees of
+    /// the reserves with the same liquidity mint.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Insurance fund account - uninitialized, derived from the lending market
+    ///                     and the reserve liquidity mint. See `find_insurance_fund_address`.
+    ///   1. `[]` Reserve account.
+    ///   2. `[]` Reserve liquidity mint.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[writable, signer]` Funding account, pays for the insurance fund account.
+    ///   6. `[]` Rent sysvar.
+    ///   7. `[]` System program id.
+    ///   8. `[]` Token program id.
+    InitInsuranceFundAccount,
+
+    // 41
+    /// Move the accumulated insurance fees of a reserve into the lending market insurance fund
+    /// account for its liquidity mint.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account - refreshed.
+    ///   1. `[writable]` Insurance fund account.
+    ///   2. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Token program id.
+    RedeemInsuranceFees,
+
+    // 42
+    /// Write off the remaining borrow of an obligation which has no collateral left. The lending
+    /// market insurance fund repays as much of the debt as it can, and the rest is socialized
+    /// across depositors through the collateral exchange rate.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Obligation account - refreshed.
+    ///   1. `[writable]` Borrow reserve account - refreshed.
+    ///   2. `[writable]` Borrow reserve liquidity supply SPL Token account.
+    ///   3. `[writable]` Insurance fund account for the borrow reserve liquidity mint.
+    ///   4. `[]` Lending market account.
+    ///   5. `[]` Derived lending market authority.
+    ///   6. `[signer]` Lending market owner.
+    ///   7. `[]` Token program id.
+    WriteOffBadDebt,
+
+    // 43
+    /// Sets the lifecycle status of a reserve. Reserves which are not active only allow
+    /// repaying, withdrawing, redeeming and liquidating.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    SetReserveStatus {
+        /// New reserve status
+        status: ReserveStatus,
+    },
+
+    // 44
+    /// Close a deprecated reserve once its liquidity, collateral, borrows and fees are empty.
+    /// Any tokens left in the liquidity supply and fee receiver are swept to the destination
+    /// liquidity account before the token accounts are closed. SPL Token mints can't be closed,
+    /// so the collateral mint authority is revoked instead.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[writable]` Reserve liquidity supply SPL Token account.
+    ///   2. `[writable]` Reserve liquidity fee receiver account.
+    ///   3. `[writable]` Reserve collateral SPL Token mint.
+    ///   4. `[writable]` Reserve collateral supply SPL Token account.
+    ///   5. `[writable]` Destination liquidity token account.
+    ///   6. `[writable]` Destination account, receives the rent of the closed accounts.
+    ///   7. `[]` Lending market account.
+    ///   8. `[]` Derived lending market authority.
+    ///   9. `[signer]` Lending market owner.
+    ///   10. `[]` Token program id.
+    CloseReserve,
+
+    // 45
+    /// Upgrade a lending market, reserve, obligation or flash loan whitelist account written by
+    /// an older program version to the current version. Anyone can migrate an account.
+    ///
+    /// Accounts are rewritten in place at their current length. Legacy length obligations are
+    /// moved to the current obligation layout with `UpgradeObligation`.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Account to migrate.
+    MigrateAccount,
+
+    // 46
+    /// Sets the outflow rate limiter of a reserve and resets its window. Redeeming, withdrawing
+    /// and redeeming, borrowing and flash borrowing fail once the value leaving the reserve
+    /// within the window would exceed the maximum outflow.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[writable]` Reserve account.
+    ///   1. `[]` Lending market account.
+    ///   2. `[signer]` Lending market owner.
+    SetReserveRateLimiter {
+        /// New rate limiter configuration
+        config: RateLimiterConfig,
+    },
+
+    // 47
+    /// Splits the balance of a reserve liquidity fee receiver between the lending market fee
+    /// distribution receivers and sends the remainder to the lending market owner. Anyone can
+    /// distribute fees.
+    ///
+    /// Accounts expected by this instruction:
+    ///
+    ///   0. `[]` Reserve account.
+    ///   1. `[writable]` Reserve liquidity fee receiver account.
+    ///                     Must be the fee account specified at InitReserve.
+    ///   2. `[writable]` Lending market owner liquidity token account.
+    ///                     Minted by reserve liquidity mint, owned by the lending market owner.
+    ///   3. `[]` Lending market account.
+    ///   4. `[]` Derived lending market authority.
+    ///   5. `[]` Token program id.
+    ///   .. `[writable]` Fee distribution receiver liquidity token accounts, one for each lending
+    ///                     market fee distribution receiver, in order.
+    DistributeFees,
+}
+
+impl LendingInstruction {
+    /// Unpacks a byte buffer into a [LendingInstruction](enum.LendingInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input
+            .split_first()
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok(match tag {
+            0 => {
+                let (owner, rest) = Self::unpack_pubkey(rest)?;
+                let (quote_currency, _rest) = Self::unpack_bytes32(rest)?;
+                Self::InitLendingMarket {
+                    owner,
+                    quote_currency: *quote_currency,
+                }
+            }
+            1 => {
+                let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
+                Self::SetLendingMarketOwner { new_owner }
+            }
+            2 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
+                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
+                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
+                let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
+                let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+                let (deposit_limit, rest) = Self::unpack_u64(rest)?;
+                let (borrow_limit, rest) = Self::unpack_u64(rest)?;
+                let (fee_receiver, rest) = Self::unpack_pubkey(rest)?;
+                let (protocol_liquidation_fee, rest) = Self::unpack_u8(rest)?;
+                let (protocol_take_rate, rest) = Self::unpack_u8(rest)?;
+                // fields appended to the original config layout are optional
+                let (deposit_fee_wad, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (redeem_fee_wad, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (deposit_limit_value, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (borrow_limit_value, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (max_obligation_deposit_percentage, rest) = Self::unpack_optional_u8(rest, 0)?;
+                let (max_obligation_borrow, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (initial_collateral_ratio, _rest) = Self::unpack_optional_u64(rest, 1)?;
+                Self::InitReserve {
+                    liquidity_amount,
+                    config: ReserveConfig {
+                        optimal_utilization_rate,
+                        loan_to_value_ratio,
+                        liquidation_bonus,
+                        liquidation_threshold,
+                        min_borrow_rate,
+                        optimal_borrow_rate,
+                        max_borrow_rate,
+                        fees: ReserveFees {
+                            borrow_fee_wad,
+                            flash_loan_fee_wad,
+                            host_fee_percentage,
+                            deposit_fee_wad,
+                            redeem_fee_wad,
+                        },
+                        deposit_limit,
+                        borrow_limit,
+                        fee_receiver,
+                        protocol_liquidation_fee,
+                        protocol_take_rate,
+                        deposit_limit_value,
+                        borrow_limit_value,
+                        max_obligation_deposit_percentage,
+                        max_obligation_borrow,
+                    },
+                    initial_collateral_ratio,
+                }
+            }
+            3 => Self::RefreshReserve,
+            4 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositReserveLiquidity { liquidity_amount }
+            }
+            5 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RedeemReserveCollateral { collateral_amount }
+            }
+            6 => Self::InitObligation,
+            7 => Self::RefreshObligation,
+            8 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositObligationCollateral { collateral_amount }
+            }
+            9 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawObligationCollateral { collateral_amount }
+            }
+            10 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::BorrowObligationLiquidity { liquidity_amount }
+            }
+            11 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RepayObligationLiquidity { liquidity_amount }
+            }
+            12 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::LiquidateObligation { liquidity_amount }
+            }
+            13 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashLoan { amount }
+            }
+            14 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount }
+            }
+            15 => {
+                let (collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount }
+            }
+            16 => {
+                let (optimal_utilization_rate, rest) = Self::unpack_u8(rest)?;
+                let (loan_to_value_ratio, rest) = Self::unpack_u8(rest)?;
+                let (liquidation_bonus, rest) = Self::unpack_u8(rest)?;
+                let (liquidation_threshold, rest) = Self::unpack_u8(rest)?;
+                let (min_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (optimal_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (max_borrow_rate, rest) = Self::unpack_u8(rest)?;
+                let (borrow_fee_wad, rest) = Self::unpack_u64(rest)?;
+                let (flash_loan_fee_wad, rest) = Self::unpack_u64(rest)?;
+                let (host_fee_percentage, rest) = Self::unpack_u8(rest)?;
+                let (deposit_limit, rest) = Self::unpack_u64(rest)?;
+                let (borrow_limit, rest) = Self::unpack_u64(rest)?;
+                let (fee_receiver, rest) = Self::unpack_pubkey(rest)?;
+                let (protocol_liquidation_fee, rest) = Self::unpack_u8(rest)?;
+                let (protocol_take_rate, rest) = Self::unpack_u8(rest)?;
+                // fields appended to the original config layout are optional
+                let (deposit_fee_wad, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (redeem_fee_wad, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (deposit_limit_value, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (borrow_limit_value, rest) = Self::unpack_optional_u64(rest, 0)?;
+                let (max_obligation_deposit_percentage, rest) = Self::unpack_optional_u8(rest, 0)?;
+                let (max_obligation_borrow, _rest) = Self::unpack_optional_u64(rest, 0)?;
+                Self::UpdateReserveConfig {
+                    config: ReserveConfig {
+                        optimal_utilization_rate,
+                        loan_to_value_ratio,
+                        liquidation_bonus,
+                        liquidation_threshold,
+                        min_borrow_rate,
+                        optimal_borrow_rate,
+                        max_borrow_rate,
+                        fees: ReserveFees {
+                            borrow_fee_wad,
+                            flash_loan_fee_wad,
+                            host_fee_percentage,
+                            deposit_fee_wad,
+                            redeem_fee_wad,
+                        },
+                        deposit_limit,
+                        borrow_limit,
+                        fee_receiver,
+                        protocol_liquidation_fee,
+                        protocol_take_rate,
+                        deposit_limit_value,
+                        borrow_limit_value,
+                        max_obligation_deposit_percentage,
+                        max_obligation_borrow,
+                    },
+                }
+            }
+            17 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount }
+            }
+            18 => Self::RedeemFees,
+            19 => {
+                let (count, mut rest) = Self::unpack_u8(rest)?;
+                let mut liquidity_amounts = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (liquidity_amount, next) = Self::unpack_u64(rest)?;
+                    liquidity_amounts.push(liquidity_amount);
+                    rest = next;
+                }
+                Self::LiquidateObligationBatch { liquidity_amounts }
+            }
+            20 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_receive_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::BorrowObligationLiquidityWithMinReceive {
+                    liquidity_amount,
+                    min_receive_amount,
+                }
+            }
+            21 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_collateral_received, _rest) = Self::unpack_u64(rest)?;
+                Self::LiquidateObligationWithMinCollateral {
+                    liquidity_amount,
+                    min_collateral_received,
+                }
+            }
+            22 => {
+                let (collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (min_liquidity_out, _rest) = Self::unpack_u64(rest)?;
+                Self::RedeemReserveCollateralWithMinLiquidity {
+                    collateral_amount,
+                    min_liquidity_out,
+                }
+            }
+            23 => Self::UpgradeObligation,
+            24 => Self::CloseObligation,
+            25 => {
+                let (index, _rest) = Self::unpack_u8(rest)?;
+                Self::InitDerivedObligation { index }
+            }
+            26 => {
+                let (delegate, rest) = Self::unpack_pubkey(rest)?;
+                let (permissions, _rest) = Self::unpack_u8(rest)?;
+                Self::SetObligationDelegate {
+                    delegate,
+                    permissions,
+                }
+            }
+            27 => {
+                let (new_owner, _rest) = Self::unpack_pubkey(rest)?;
+                Self::TransferObligationOwnership { new_owner }
+            }
+            28 => {
+                let (deposit_collateral_amount, rest) = Self::unpack_u64(rest)?;
+                let (withdraw_collateral_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::SwapObligationCollateral {
+                    deposit_collateral_amount,
+                    withdraw_collateral_amount,
+                }
+            }
+            29 => {
+                let (repay_liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (borrow_liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::SwapObligationDebt {
+                    repay_liquidity_amount,
+                    borrow_liquidity_amount,
+                }
+            }
+            30 => {
+                let (liquidity_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::FlashBorrowReserveLiquidity { liquidity_amount }
+            }
+            31 => {
+                let (liquidity_amount, rest) = Self::unpack_u64(rest)?;
+                let (borrow_instruction_index, _rest) = Self::unpack_u8(rest)?;
+                Self::FlashRepayReserveLiquidity {
+                    liquidity_amount,
+                    borrow_instruction_index,
+                }
+            }
+            32 => {
+                let (count, mut rest) = Self::unpack_u8(rest)?;
+                let mut liquidity_amounts = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (liquidity_amount, next) = Self::unpack_u64(rest)?;
+                    liquidity_amounts.push(liquidity_amount);
+                    rest = next;
+                }
+                Self::FlashLoanBatch { liquidity_amounts }
+            }
+            33 => {
+                let (receiver_program_id, rest) = Self::unpack_pubkey(rest)?;
+                let (flash_loan_fee_wad, _rest) = Self::unpack_u64(rest)?;
+                Self::AddFlashLoanWhitelistEntry {
+                    receiver_program_id,
+                    flash_loan_fee_wad,
+                }
+            }
+            34 => {
+                let (receiver_program_id, _rest) = Self::unpack_pubkey(rest)?;
+                Self::RemoveFlashLoanWhitelistEntry {
+                    receiver_program_id,
+                }
+            }
+            35 => Self::InitReferrerFeesAccount,
+            36 => Self::ClaimReferralFees,
+            37 => Self::CollectProtocolFees,
+            38 => {
+                let (count, mut rest) = Self::unpack_u8(rest)?;
+                let mut fee_distribution = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    let (owner, next) = Self::unpack_pubkey(rest)?;
+                    let (fee_percentage, next) = Self::unpack_u8(next)?;
+                    fee_distribution.push(FeeDistributionReceiver {
+                        owner,
+                        fee_percentage,
+                    });
+                    rest = next;
+                }
+                Self::SetLendingMarketFeeDistribution { fee_distribution }
+            }
+            39 => {
+                let (insurance_fund_fee_percentage, _rest) = Self::unpack_u8(rest)?;
+                Self::SetLendingMarketInsuranceFundFee {
+                    insurance_fund_fee_percentage,
+                }
+            }
+            40 => Self::InitInsuranceFundAccount,
+            41 => Self::RedeemInsuranceFees,
+            42 => Self::WriteOffBadDebt,
+            43 => {
+                let (status, _rest) = Self::unpack_u8(rest)?;
+                let status = ReserveStatus::try_from(status)
+                    .map_err(|_| LendingError::InstructionUnpackError)?;
+                Self::SetReserveStatus { status }
+            }
+            44 => Self::CloseReserve,
+            45 => Self::MigrateAccount,
+            46 => {
+                let (window_duration, rest) = Self::unpack_u64(rest)?;
+                let (max_outflow, _rest) = Self::unpack_u64(rest)?;
+                Self::SetReserveRateLimiter {
+                    config: RateLimiterConfig {
+                        window_duration,
+                        max_outflow,
+                    },
+                }
+            }
+            47 => Self::DistributeFees,
+            _ => {
+                msg!("Instruction cannot be unpacked");
+                return Err(LendingError::InstructionUnpackError.into());
+            }
+        })
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() < 8 {
+            msg!("u64 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(8);
+        let value = bytes
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
+    fn unpack_optional_u64(input: &[u8], default: u64) -> Result<(u64, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((default, input));
+        }
+        Self::unpack_u64(input)
+    }
+
+    fn unpack_u8(input: &[u8]) -> Result<(u8, &[u8]), ProgramError> {
+        if input.is_empty() {
+            msg!("u8 cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(1);
+        let value = bytes
+            .get(..1)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u8::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+        Ok((value, rest))
+    }
+
+    fn unpack_optional_u8(input: &[u8], default: u8) -> Result<(u8, &[u8]), ProgramError> {
+        if input.is_empty() {
+            return Ok((default, input));
+        }
+        Self::unpack_u8(input)
+    }
+
+    fn unpack_bytes32(input: &[u8]) -> Result<(&[u8; 32], &[u8]), ProgramError> {
+        if input.len() < 32 {
+            msg!("32 bytes cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (bytes, rest) = input.split_at(32);
+        Ok((
+            bytes
+                .try_into()
+                .map_err(|_| LendingError::InstructionUnpackError)?,
+            rest,
+        ))
+    }
+
+    fn unpack_pubkey(input: &[u8]) -> Result<(Pubkey, &[u8]), ProgramError> {
+        if input.len() < PUBKEY_BYTES {
+            msg!("Pubkey cannot be unpacked");
+            return Err(LendingError::InstructionUnpackError.into());
+        }
+        let (key, rest) = input.split_at(PUBKEY_BYTES);
+        let pk = Pubkey::new(key);
         Ok((pk, rest))
     }
 
-    /// Packs a [LendingInstruction](enum.LendingInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match *self {
-            Self::InitLendingMarket {
-                owner,
-                quote_currency,
-            } => {
-                buf.push(0);
-                buf.extend_from_slice(owner.as_ref());
-                buf.extend_from_slice(quote_currency.as_ref());
-            }
-            Self::SetLendingMarketOwner { new_owner } => {
-                buf.push(1);
-                buf.extend_from_slice(new_owner.as_ref());
-            }
-            Self::InitReserve {
-                liquidity_amount,
-                config:
-                    ReserveConfig {
-                        optimal_utilization_rate,
-                        loan_to_value_ratio,
-                        liquidation_bonus,
-                        liquidation_threshold,
-                        min_borrow_rate,
-                        optimal_borrow_rate,
-                        max_borrow_rate,
-                        fees:
-                            ReserveFees {
-                                borrow_fee_wad,
-                                flash_loan_fee_wad,
-                                host_fee_percentage,
-                            },
-                        deposit_limit,
-                        borrow_limit,
-                        fee_receiver,
-                        protocol_liquidation_fee,
-                        protocol_take_rate,
-                    },
-            } => {
-                buf.push(2);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-                buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
-                buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
-                buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
-                buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&flash_loan_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
-                buf.extend_from_slice(&deposit_limit.to_le_bytes());
-                buf.extend_from_slice(&borrow_limit.to_le_bytes());
-                buf.extend_from_slice(&fee_receiver.to_bytes());
-                buf.extend_from_slice(&protocol_liquidation_fee.to_le_bytes());
-                buf.extend_from_slice(&protocol_take_rate.to_le_bytes());
-            }
-            Self::RefreshReserve => {
-                buf.push(3);
-            }
-            Self::DepositReserveLiquidity { liquidity_amount } => {
-                buf.push(4);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::RedeemReserveCollateral { collateral_amount } => {
-                buf.push(5);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::InitObligation => {
-                buf.push(6);
-            }
-            Self::RefreshObligation => {
-                buf.push(7);
-            }
-            Self::DepositObligationCollateral { collateral_amount } => {
-                buf.push(8);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::WithdrawObligationCollateral { collateral_amount } => {
-                buf.push(9);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::BorrowObligationLiquidity { liquidity_amount } => {
-                buf.push(10);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::RepayObligationLiquidity { liquidity_amount } => {
-                buf.push(11);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::LiquidateObligation { liquidity_amount } => {
-                buf.push(12);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::FlashLoan { amount } => {
-                buf.push(13);
-                buf.extend_from_slice(&amount.to_le_bytes());
-            }
-            Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
-                buf.push(14);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount } => {
-                buf.push(15);
-                buf.extend_from_slice(&collateral_amount.to_le_bytes());
-            }
-            Self::UpdateReserveConfig { config } => {
-                buf.push(16);
-                buf.extend_from_slice(&config.optimal_utilization_rate.to_le_bytes());
-                buf.extend_from_slice(&config.loan_to_value_ratio.to_le_bytes());
-                buf.extend_from_slice(&config.liquidation_bonus.to_le_bytes());
-                buf.extend_from_slice(&config.liquidation_threshold.to_le_bytes());
-                buf.extend_from_slice(&config.min_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&config.optimal_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&config.max_borrow_rate.to_le_bytes());
-                buf.extend_from_slice(&config.fees.borrow_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&config.fees.flash_loan_fee_wad.to_le_bytes());
-                buf.extend_from_slice(&config.fees.host_fee_percentage.to_le_bytes());
-                buf.extend_from_slice(&config.deposit_limit.to_le_bytes());
-                buf.extend_from_slice(&config.borrow_limit.to_le_bytes());
-                buf.extend_from_slice(&config.fee_receiver.to_bytes());
-                buf.extend_from_slice(&config.protocol_liquidation_fee.to_le_bytes());
-                buf.extend_from_slice(&config.protocol_take_rate.to_le_bytes());
-            }
-            Self::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount } => {
-                buf.push(17);
-                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
-            }
-            Self::RedeemFees {} => {
-                buf.push(18);
-            }
-        }
-        buf
+    /// Packs a [LendingInstruction](enum.LendingInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match self {
+            Self::InitLendingMarket {
+                owner,
+                quote_currency,
+            } => {
+                buf.push(0);
+                buf.extend_from_slice(owner.as_ref());
+                buf.extend_from_slice(quote_currency.as_ref());
+            }
+            Self::SetLendingMarketOwner { new_owner } => {
+                buf.push(1);
+                buf.extend_from_slice(new_owner.as_ref());
+            }
+            Self::InitReserve {
+                liquidity_amount,
+                config:
+                    ReserveConfig {
+                        optimal_utilization_rate,
+                        loan_to_value_ratio,
+                        liquidation_bonus,
+                        liquidation_threshold,
+                        min_borrow_rate,
+                        optimal_borrow_rate,
+                        max_borrow_rate,
+                        fees:
+                            ReserveFees {
+                                borrow_fee_wad,
+                                flash_loan_fee_wad,
+                                host_fee_percentage,
+                                deposit_fee_wad,
+                                redeem_fee_wad,
+                            },
+                        deposit_limit,
+                        borrow_limit,
+                        fee_receiver,
+                        protocol_liquidation_fee,
+                        protocol_take_rate,
+                        deposit_limit_value,
+                        borrow_limit_value,
+                        max_obligation_deposit_percentage,
+                        max_obligation_borrow,
+                    },
+                initial_collateral_ratio,
+            } => {
+                buf.push(2);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&optimal_utilization_rate.to_le_bytes());
+                buf.extend_from_slice(&loan_to_value_ratio.to_le_bytes());
+                buf.extend_from_slice(&liquidation_bonus.to_le_bytes());
+                buf.extend_from_slice(&liquidation_threshold.to_le_bytes());
+                buf.extend_from_slice(&min_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&optimal_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&max_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&borrow_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&flash_loan_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&host_fee_percentage.to_le_bytes());
+                buf.extend_from_slice(&deposit_limit.to_le_bytes());
+                buf.extend_from_slice(&borrow_limit.to_le_bytes());
+                buf.extend_from_slice(&fee_receiver.to_bytes());
+                buf.extend_from_slice(&protocol_liquidation_fee.to_le_bytes());
+                buf.extend_from_slice(&protocol_take_rate.to_le_bytes());
+                buf.extend_from_slice(&deposit_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&redeem_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&deposit_limit_value.to_le_bytes());
+                buf.extend_from_slice(&borrow_limit_value.to_le_bytes());
+                buf.extend_from_slice(&max_obligation_deposit_percentage.to_le_bytes());
+                buf.extend_from_slice(&max_obligation_borrow.to_le_bytes());
+                buf.extend_from_slice(&initial_collateral_ratio.to_le_bytes());
+            }
+            Self::RefreshReserve => {
+                buf.push(3);
+            }
+            Self::DepositReserveLiquidity { liquidity_amount } => {
+                buf.push(4);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::RedeemReserveCollateral { collateral_amount } => {
+                buf.push(5);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::InitObligation => {
+                buf.push(6);
+            }
+            Self::RefreshObligation => {
+                buf.push(7);
+            }
+            Self::DepositObligationCollateral { collateral_amount } => {
+                buf.push(8);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::WithdrawObligationCollateral { collateral_amount } => {
+                buf.push(9);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::BorrowObligationLiquidity { liquidity_amount } => {
+                buf.push(10);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::RepayObligationLiquidity { liquidity_amount } => {
+                buf.push(11);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::LiquidateObligation { liquidity_amount } => {
+                buf.push(12);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::FlashLoan { amount } => {
+                buf.push(13);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::DepositReserveLiquidityAndObligationCollateral { liquidity_amount } => {
+                buf.push(14);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::WithdrawObligationCollateralAndRedeemReserveCollateral { collateral_amount } => {
+                buf.push(15);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+            }
+            Self::UpdateReserveConfig { config } => {
+                buf.push(16);
+                buf.extend_from_slice(&config.optimal_utilization_rate.to_le_bytes());
+                buf.extend_from_slice(&config.loan_to_value_ratio.to_le_bytes());
+                buf.extend_from_slice(&config.liquidation_bonus.to_le_bytes());
+                buf.extend_from_slice(&config.liquidation_threshold.to_le_bytes());
+                buf.extend_from_slice(&config.min_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&config.optimal_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&config.max_borrow_rate.to_le_bytes());
+                buf.extend_from_slice(&config.fees.borrow_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&config.fees.flash_loan_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&config.fees.host_fee_percentage.to_le_bytes());
+                buf.extend_from_slice(&config.deposit_limit.to_le_bytes());
+                buf.extend_from_slice(&config.borrow_limit.to_le_bytes());
+                buf.extend_from_slice(&config.fee_receiver.to_bytes());
+                buf.extend_from_slice(&config.protocol_liquidation_fee.to_le_bytes());
+                buf.extend_from_slice(&config.protocol_take_rate.to_le_bytes());
+                buf.extend_from_slice(&config.fees.deposit_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&config.fees.redeem_fee_wad.to_le_bytes());
+                buf.extend_from_slice(&config.deposit_limit_value.to_le_bytes());
+                buf.extend_from_slice(&config.borrow_limit_value.to_le_bytes());
+                buf.extend_from_slice(&config.max_obligation_deposit_percentage.to_le_bytes());
+                buf.extend_from_slice(&config.max_obligation_borrow.to_le_bytes());
+            }
+            Self::LiquidateObligationAndRedeemReserveCollateral { liquidity_amount } => {
+                buf.push(17);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::RedeemFees {} => {
+                buf.push(18);
+            }
+            Self::LiquidateObligationBatch { liquidity_amounts } => {
+                buf.push(19);
+                buf.push(
+                    u8::try_from(liquidity_amounts.len())
+                        .expect("a liquidation batch has at most 255 liquidity amounts"),
+                );
+                for liquidity_amount in liquidity_amounts {
+                    buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                }
+            }
+            Self::BorrowObligationLiquidityWithMinReceive {
+                liquidity_amount,
+                min_receive_amount,
+            } => {
+                buf.push(20);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_receive_amount.to_le_bytes());
+            }
+            Self::LiquidateObligationWithMinCollateral {
+                liquidity_amount,
+                min_collateral_received,
+            } => {
+                buf.push(21);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&min_collateral_received.to_le_bytes());
+            }
+            Self::RedeemReserveCollateralWithMinLiquidity {
+                collateral_amount,
+                min_liquidity_out,
+            } => {
+                buf.push(22);
+                buf.extend_from_slice(&collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&min_liquidity_out.to_le_bytes());
+            }
+            Self::UpgradeObligation => {
+                buf.push(23);
+            }
+            Self::CloseObligation => {
+                buf.push(24);
+            }
+            Self::InitDerivedObligation { index } => {
+                buf.push(25);
+                buf.extend_from_slice(&index.to_le_bytes());
+            }
+            Self::SetObligationDelegate {
+                delegate,
+                permissions,
+            } => {
+                buf.push(26);
+                buf.extend_from_slice(delegate.as_ref());
+                buf.extend_from_slice(&permissions.to_le_bytes());
+            }
+            Self::TransferObligationOwnership { new_owner } => {
+                buf.push(27);
+                buf.extend_from_slice(new_owner.as_ref());
+            }
+            Self::SwapObligationCollateral {
+                deposit_collateral_amount,
+                withdraw_collateral_amount,
+            } => {
+                buf.push(28);
+                buf.extend_from_slice(&deposit_collateral_amount.to_le_bytes());
+                buf.extend_from_slice(&withdraw_collateral_amount.to_le_bytes());
+            }
+            Self::SwapObligationDebt {
+                repay_liquidity_amount,
+                borrow_liquidity_amount,
+            } => {
+                buf.push(29);
+                buf.extend_from_slice(&repay_liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&borrow_liquidity_amount.to_le_bytes());
+            }
+            Self::FlashBorrowReserveLiquidity { liquidity_amount } => {
+                buf.push(30);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+            }
+            Self::FlashRepayReserveLiquidity {
+                liquidity_amount,
+                borrow_instruction_index,
+            } => {
+                buf.push(31);
+                buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                buf.extend_from_slice(&borrow_instruction_index.to_le_bytes());
+            }
+            Self::FlashLoanBatch { liquidity_amounts } => {
+                buf.push(32);
+                buf.push(
+                    u8::try_from(liquidity_amounts.len())
+                        .expect("a flash loan batch has at most 255 liquidity amounts"),
+                );
+                for liquidity_amount in liquidity_amounts {
+                    buf.extend_from_slice(&liquidity_amount.to_le_bytes());
+                }
+            }
+            Self::AddFlashLoanWhitelistEntry {
+                receiver_program_id,
+                flash_loan_fee_wad,
+            } => {
+                buf.push(33);
+                buf.extend_from_slice(receiver_program_id.as_ref());
+                buf.extend_from_slice(&flash_loan_fee_wad.to_le_bytes());
+            }
+            Self::RemoveFlashLoanWhitelistEntry {
+                receiver_program_id,
+            } => {
+                buf.push(34);
+                buf.extend_from_slice(receiver_program_id.as_ref());
+            }
+            Self::InitReferrerFeesAccount => {
+                buf.push(35);
+            }
+            Self::ClaimReferralFees => {
+                buf.push(36);
+            }
+            Self::CollectProtocolFees => {
+                buf.push(37);
+            }
+            Self::SetLendingMarketFeeDistribution { fee_distribution } => {
+                buf.push(38);
+                buf.push(fee_distribution.len() as u8);
+                for receiver in fee_distribution {
+                    buf.extend_from_slice(receiver.owner.as_ref());
+                    buf.push(receiver.fee_percentage);
+                }
+            }
+            Self::SetLendingMarketInsuranceFundFee {
+                insurance_fund_fee_percentage,
+            } => {
+                buf.push(39);
+                buf.extend_from_slice(&insurance_fund_fee_percentage.to_le_bytes());
+            }
+            Self::InitInsuranceFundAccount => {
+                buf.push(40);
+            }
+            Self::RedeemInsuranceFees => {
+                buf.push(41);
+            }
+            Self::WriteOffBadDebt => {
+                buf.push(42);
+            }
+            Self::SetReserveStatus { status } => {
+                buf.push(43);
+                buf.push(*status as u8);
+            }
+            Self::CloseReserve => {
+                buf.push(44);
+            }
+            Self::MigrateAccount => {
+                buf.push(45);
+            }
+            Self::SetReserveRateLimiter { config } => {
+                buf.push(46);
+                buf.extend_from_slice(&config.window_duration.to_le_bytes());
+                buf.extend_from_slice(&config.max_outflow.to_le_bytes());
+            }
+            Self::DistributeFees => {
+                buf.push(47);
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an 'InitLendingMarket' instruction.
+pub fn init_lending_market(
+    program_id: Pubkey,
+    owner: Pubkey,
+    quote_currency: [u8; 32],
+    lending_market_pubkey: Pubkey,
+    oracle_program_id: Pubkey,
+    switchboard_oracle_program_id: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(oracle_program_id, false),
+            AccountMeta::new_readonly(switchboard_oracle_program_id, false),
+        ],
+        data: LendingInstruction::InitLendingMarket {
+            owner,
+            quote_currency,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketOwner' instruction.
+pub fn set_lending_market_owner(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    new_owner: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketFeeDistribution' instruction.
+pub fn set_lending_market_fee_distribution(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    fee_distribution: Vec<FeeDistributionReceiver>,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner, true),
+        ],
+        data: LendingInstruction::SetLendingMarketFeeDistribution { fee_distribution }.pack(),
+    }
+}
+
+/// Creates a 'SetLendingMarketInsuranceFundFee' instruction.
+pub fn set_lending_market_insurance_fund_fee(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
+    insurance_fund_fee_percentage: u8,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner, true),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|reserve_pubkey| AccountMeta::new(reserve_pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::SetLendingMarketInsuranceFundFee {
+            insurance_fund_fee_percentage,
+        }
+        .pack(),
+    }
+}
+
+/// Creates an 'InitReserve' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn init_reserve(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    config: ReserveConfig,
+    initial_collateral_ratio: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    pyth_product_pubkey: Pubkey,
+    pyth_price_pubkey: Pubkey,
+    switchboard_feed_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_collateral_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(config.fee_receiver, false),
+        AccountMeta::new(reserve_collateral_mint_pubkey, false),
+        AccountMeta::new(reserve_collateral_supply_pubkey, false),
+        AccountMeta::new_readonly(pyth_product_pubkey, false),
+        AccountMeta::new_readonly(pyth_price_pubkey, false),
+        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::InitReserve {
+            liquidity_amount,
+            config,
+            initial_collateral_ratio,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `RefreshReserve` instruction
+pub fn refresh_reserve(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
+    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
+) -> Instruction {
+    let accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
+        AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshReserve.pack(),
+    }
+}
+
+/// Creates a 'DepositReserveLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_reserve_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::DepositReserveLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a 'RedeemReserveCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_reserve_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::RedeemReserveCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates an 'InitObligation' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn init_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    referrer_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(referrer_pubkey) = referrer_pubkey {
+        accounts.push(AccountMeta::new_readonly(referrer_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::InitObligation.pack(),
+    }
+}
+
+/// Creates a 'RefreshObligation' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    reserve_pubkeys: Vec<Pubkey>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+    ];
+    accounts.extend(
+        reserve_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
+    );
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RefreshObligation.pack(),
+    }
+}
+
+/// Creates a 'DepositObligationCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_obligation_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(deposit_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::DepositObligationCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a 'DepositReserveLiquidityAndObligationCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn deposit_reserve_liquidity_and_obligation_collateral(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    user_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    destination_deposit_collateral_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
+    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(user_collateral_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new(destination_deposit_collateral_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
+            AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
+            liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'WithdrawObligationCollateralAndRedeemReserveCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_obligation_collateral_and_redeem_reserve_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(withdraw_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
+            collateral_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a 'WithdrawObligationCollateral' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn withdraw_obligation_collateral(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::WithdrawObligationCollateral { collateral_amount }.pack(),
+    }
+}
+
+/// Creates a 'BorrowObligationLiquidity' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn borrow_obligation_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::BorrowObligationLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `RepayObligationLiquidity` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn repay_obligation_liquidity(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `LiquidateObligation` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::LiquidateObligation { liquidity_amount }.pack(),
+    }
+}
+
+/// Creates a `FlashLoan` instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn flash_loan(
+    program_id: Pubkey,
+    amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(source_liquidity_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(host_fee_receiver_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+    ];
+    accounts.extend(flash_loan_receiver_program_accounts);
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::FlashLoan { amount }.pack(),
+    }
+}
+
+/// Creates an 'UpdateReserveConfig' instruction.
+#[allow(clippy::too_many_arguments)]
+pub fn update_reserve_config(
+    program_id: Pubkey,
+    config: ReserveConfig,
+    reserve_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
+    pyth_product_pubkey: Pubkey,
+    pyth_price_pubkey: Pubkey,
+    switchboard_feed_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(pyth_product_pubkey, false),
+        AccountMeta::new_readonly(pyth_price_pubkey, false),
+        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::UpdateReserveConfig { config }.pack(),
+    }
+}
+
+/// Creates a `LiquidateObligationAndRedeemReserveCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation_and_redeem_reserve_collateral(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_mint_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_liquidity_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(repay_reserve_pubkey, false),
+            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(withdraw_reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::LiquidateObligationAndRedeemReserveCollateral {
+            liquidity_amount,
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `RedeemFees` instruction
+pub fn redeem_fees(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    reserve_supply_liquidity_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let accounts = vec![
+        AccountMeta::new(reserve_pubkey, false),
+        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(reserve_supply_liquidity_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::RedeemFees.pack(),
+    }
+}
+
+/// Accounts and amount for a single liquidation within a `LiquidateObligationBatch` instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchLiquidation {
+    /// Amount of liquidity to repay - u64::MAX for up to 100% of borrowed amount
+    pub liquidity_amount: u64,
+    /// Source liquidity token account
+    pub source_liquidity_pubkey: Pubkey,
+    /// Destination collateral token account
+    pub destination_collateral_pubkey: Pubkey,
+    /// Repay reserve account
+    pub repay_reserve_pubkey: Pubkey,
+    /// Repay reserve liquidity supply SPL Token account
+    pub repay_reserve_liquidity_supply_pubkey: Pubkey,
+    /// Withdraw reserve account
+    pub withdraw_reserve_pubkey: Pubkey,
+    /// Withdraw reserve collateral supply SPL Token account
+    pub withdraw_reserve_collateral_supply_pubkey: Pubkey,
+}
+
+/// Creates a `LiquidateObligationBatch` instruction
+///
+/// Panics if more than 255 liquidations are given
+pub fn liquidate_obligation_batch(
+    program_id: Pubkey,
+    liquidations: &[BatchLiquidation],
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    for liquidation in liquidations {
+        accounts.extend(vec![
+            AccountMeta::new(liquidation.source_liquidity_pubkey, false),
+            AccountMeta::new(liquidation.destination_collateral_pubkey, false),
+            AccountMeta::new(liquidation.repay_reserve_pubkey, false),
+            AccountMeta::new(liquidation.repay_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new_readonly(liquidation.withdraw_reserve_pubkey, false),
+            AccountMeta::new(liquidation.withdraw_reserve_collateral_supply_pubkey, false),
+        ]);
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::LiquidateObligationBatch {
+            liquidity_amounts: liquidations
+                .iter()
+                .map(|liquidation| liquidation.liquidity_amount)
+                .collect(),
+        }
+        .pack(),
+    }
+}
+
+/// Creates a `BorrowObligationLiquidityWithMinReceive` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn borrow_obligation_liquidity_with_min_receive(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let mut instruction = borrow_obligation_liquidity(
+        program_id,
+        liquidity_amount,
+        source_liquidity_pubkey,
+        destination_liquidity_pubkey,
+        borrow_reserve_pubkey,
+        borrow_reserve_liquidity_fee_receiver_pubkey,
+        obligation_pubkey,
+        lending_market_pubkey,
+        obligation_owner_pubkey,
+        host_fee_receiver_pubkey,
+    );
+    instruction.data = LendingInstruction::BorrowObligationLiquidityWithMinReceive {
+        liquidity_amount,
+        min_receive_amount,
+    }
+    .pack();
+    instruction
+}
+
+/// Creates a `LiquidateObligationWithMinCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn liquidate_obligation_with_min_collateral(
+    program_id: Pubkey,
+    liquidity_amount: u64,
+    min_collateral_received: u64,
+    source_liquidity_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let mut instruction = liquidate_obligation(
+        program_id,
+        liquidity_amount,
+        source_liquidity_pubkey,
+        destination_collateral_pubkey,
+        repay_reserve_pubkey,
+        repay_reserve_liquidity_supply_pubkey,
+        withdraw_reserve_pubkey,
+        withdraw_reserve_collateral_supply_pubkey,
+        obligation_pubkey,
+        lending_market_pubkey,
+        user_transfer_authority_pubkey,
+    );
+    instruction.data = LendingInstruction::LiquidateObligationWithMinCollateral {
+        liquidity_amount,
+        min_collateral_received,
+    }
+    .pack();
+    instruction
+}
+
+/// Creates a `RedeemReserveCollateralWithMinLiquidity` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_reserve_collateral_with_min_liquidity(
+    program_id: Pubkey,
+    collateral_amount: u64,
+    min_liquidity_out: u64,
+    source_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let mut instruction = redeem_reserve_collateral(
+        program_id,
+        collateral_amount,
+        source_collateral_pubkey,
+        destination_liquidity_pubkey,
+        reserve_pubkey,
+        reserve_collateral_mint_pubkey,
+        reserve_liquidity_supply_pubkey,
+        lending_market_pubkey,
+        user_transfer_authority_pubkey,
+    );
+    instruction.data = LendingInstruction::RedeemReserveCollateralWithMinLiquidity {
+        collateral_amount,
+        min_liquidity_out,
     }
+    .pack();
+    instruction
 }
 
-/// Creates an 'InitLendingMarket' instruction.
-pub fn init_lending_market(
+/// Creates an `UpgradeObligation` instruction
+pub fn upgrade_obligation(
     program_id: Pubkey,
-    owner: Pubkey,
-    quote_currency: [u8; 32],
+    obligation_pubkey: Pubkey,
+    new_obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    oracle_program_id: Pubkey,
-    switchboard_oracle_program_id: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    destination_pubkey: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(lending_market_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(new_obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new(destination_pubkey, false),
             AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-            AccountMeta::new_readonly(oracle_program_id, false),
-            AccountMeta::new_readonly(switchboard_oracle_program_id, false),
         ],
-        data: LendingInstruction::InitLendingMarket {
-            owner,
-            quote_currency,
+        data: LendingInstruction::UpgradeObligation.pack(),
+    }
+}
+
+/// Creates a `CloseObligation` instruction
+pub fn close_obligation(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    destination_pubkey: Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new(destination_pubkey, false),
+        ],
+        data: LendingInstruction::CloseObligation.pack(),
+    }
+}
+
+/// Finds the obligation address derived from the lending market, obligation owner and index
+pub fn find_obligation_address(
+    program_id: &Pubkey,
+    lending_market_pubkey: &Pubkey,
+    obligation_owner_pubkey: &Pubkey,
+    index: u8,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            lending_market_pubkey.as_ref(),
+            obligation_owner_pubkey.as_ref(),
+            &[index],
+        ],
+        program_id,
+    )
+}
+
+/// Creates an `InitDerivedObligation` instruction
+pub fn init_derived_obligation(
+    program_id: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    index: u8,
+    referrer_pubkey: Option<Pubkey>,
+) -> Instruction {
+    let (obligation_pubkey, _bump_seed) = find_obligation_address(
+        &program_id,
+        &lending_market_pubkey,
+        &obligation_owner_pubkey,
+        index,
+    );
+    let mut accounts = vec![
+        AccountMeta::new(obligation_pubkey, false),
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new(obligation_owner_pubkey, true),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(sysvar::rent::id(), false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some(referrer_pubkey) = referrer_pubkey {
+        accounts.push(AccountMeta::new_readonly(referrer_pubkey, false));
+    }
+    Instruction {
+        program_id,
+        accounts,
+        data: LendingInstruction::InitDerivedObligation { index }.pack(),
+    }
+}
+
+/// Creates a `SetObligationDelegate` instruction
+pub fn set_obligation_delegate(
+    program_id: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    delegate: Pubkey,
+    permissions: u8,
+) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        ],
+        data: LendingInstruction::SetObligationDelegate {
+            delegate,
+            permissions,
         }
         .pack(),
     }
 }
 
-/// Creates a 'SetLendingMarketOwner' instruction.
-pub fn set_lending_market_owner(
+/// Creates a `TransferObligationOwnership` instruction
+pub fn transfer_obligation_ownership(
     program_id: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    lending_market_owner: Pubkey,
+    obligation_owner_pubkey: Pubkey,
     new_owner: Pubkey,
 ) -> Instruction {
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_owner, true),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+        ],
+        data: LendingInstruction::TransferObligationOwnership { new_owner }.pack(),
+    }
+}
+
+/// Creates a `SwapObligationCollateral` instruction
+#[allow(clippy::too_many_arguments)]
+pub fn swap_obligation_collateral(
+    program_id: Pubkey,
+    deposit_collateral_amount: u64,
+    withdraw_collateral_amount: u64,
+    source_collateral_pubkey: Pubkey,
+    deposit_reserve_collateral_supply_pubkey: Pubkey,
+    deposit_reserve_pubkey: Pubkey,
+    withdraw_reserve_collateral_supply_pubkey: Pubkey,
+    destination_collateral_pubkey: Pubkey,
+    withdraw_reserve_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
+    user_transfer_authority_pubkey: Pubkey,
+) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    Instruction {
+        program_id,
+        accounts: vec![
+            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(deposit_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new_readonly(deposit_reserve_pubkey, false),
+            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
+            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(obligation_owner_pubkey, true),
+            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
+            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::SetLendingMarketOwner { new_owner }.pack(),
+        data: LendingInstruction::SwapObligationCollateral {
+            deposit_collateral_amount,
+            withdraw_collateral_amount,
+        }
+        .pack(),
     }
 }
 
-/// Creates an 'InitReserve' instruction.
+/// Creates a `SwapObligationDebt` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn init_reserve(
+pub fn swap_obligation_debt(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    config: ReserveConfig,
+    repay_liquidity_amount: u64,
+    borrow_liquidity_amount: u64,
     source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_mint_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
-    reserve_collateral_supply_pubkey: Pubkey,
-    pyth_product_pubkey: Pubkey,
-    pyth_price_pubkey: Pubkey,
-    switchboard_feed_pubkey: Pubkey,
+    repay_reserve_liquidity_supply_pubkey: Pubkey,
+    repay_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    lending_market_owner_pubkey: Pubkey,
+    obligation_owner_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Option<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let accounts = vec![
+    let mut accounts = vec![
         AccountMeta::new(source_liquidity_pubkey, false),
-        AccountMeta::new(destination_collateral_pubkey, false),
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
-        AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-        AccountMeta::new(config.fee_receiver, false),
-        AccountMeta::new(reserve_collateral_mint_pubkey, false),
-        AccountMeta::new(reserve_collateral_supply_pubkey, false),
-        AccountMeta::new_readonly(pyth_product_pubkey, false),
-        AccountMeta::new_readonly(pyth_price_pubkey, false),
-        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
+        AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(repay_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+        AccountMeta::new(destination_liquidity_pubkey, false),
+        AccountMeta::new(borrow_reserve_pubkey, false),
+        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
+        AccountMeta::new(obligation_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        AccountMeta::new_readonly(obligation_owner_pubkey, true),
         AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
         AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(sysvar::rent::id(), false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
+        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
+    }
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::InitReserve {
-            liquidity_amount,
-            config,
+        data: LendingInstruction::SwapObligationDebt {
+            repay_liquidity_amount,
+            borrow_liquidity_amount,
         }
         .pack(),
     }
 }
 
-/// Creates a `RefreshReserve` instruction
-pub fn refresh_reserve(
-    program_id: Pubkey,
-    reserve_pubkey: Pubkey,
-    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
-    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
-) -> Instruction {
-    let accounts = vec![
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
-        AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-    ];
-    Instruction {
-        program_id,
-        accounts,
-        data: LendingInstruction::RefreshReserve.pack(),
-    }
-}
-
-/// Creates a 'DepositReserveLiquidity' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn deposit_reserve_liquidity(
+/// Creates a `FlashBorrowReserveLiquidity` instruction
+pub fn flash_borrow_reserve_liquidity(
     program_id: Pubkey,
     liquidity_amount: u64,
     source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
     reserve_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -900,148 +2844,197 @@ pub fn deposit_reserve_liquidity(
         program_id,
         accounts: vec![
             AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
             AccountMeta::new(reserve_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::DepositReserveLiquidity { liquidity_amount }.pack(),
+        data: LendingInstruction::FlashBorrowReserveLiquidity { liquidity_amount }.pack(),
     }
 }
 
-/// Creates a 'RedeemReserveCollateral' instruction.
+/// Creates a `FlashRepayReserveLiquidity` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn redeem_reserve_collateral(
+pub fn flash_repay_reserve_liquidity(
     program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
+    liquidity_amount: u64,
+    borrow_instruction_index: u8,
+    source_liquidity_pubkey: Pubkey,
     destination_liquidity_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    host_fee_receiver_pubkey: Pubkey,
     reserve_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
-        &program_id,
-    );
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(source_liquidity_pubkey, false),
             AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(host_fee_receiver_pubkey, false),
             AccountMeta::new(reserve_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(sysvar::instructions::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::RedeemReserveCollateral { collateral_amount }.pack(),
+        data: LendingInstruction::FlashRepayReserveLiquidity {
+            liquidity_amount,
+            borrow_instruction_index,
+        }
+        .pack(),
     }
 }
 
-/// Creates an 'InitObligation' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn init_obligation(
+/// Accounts and amount for a single loan within a `FlashLoanBatch` instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BatchFlashLoan {
+    /// Amount of liquidity to flash loan - u64::MAX for up to 100% of available liquidity
+    pub liquidity_amount: u64,
+    /// Reserve liquidity supply SPL Token account
+    pub source_liquidity_pubkey: Pubkey,
+    /// Destination liquidity token account
+    pub destination_liquidity_pubkey: Pubkey,
+    /// Reserve account
+    pub reserve_pubkey: Pubkey,
+    /// Reserve liquidity fee receiver account
+    pub reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    /// Host fee receiver account
+    pub host_fee_receiver_pubkey: Pubkey,
+}
+
+/// Creates a `FlashLoanBatch` instruction
+///
+/// Panics if more than 255 loans are given
+pub fn flash_loan_batch(
     program_id: Pubkey,
-    obligation_pubkey: Pubkey,
+    loans: &[BatchFlashLoan],
     lending_market_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
+    flash_loan_receiver_program_id: Pubkey,
+    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
 ) -> Instruction {
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
+    let mut accounts = vec![
+        AccountMeta::new_readonly(lending_market_pubkey, false),
+        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
+    ];
+    for loan in loans {
+        accounts.extend(vec![
+            AccountMeta::new(loan.source_liquidity_pubkey, false),
+            AccountMeta::new(loan.destination_liquidity_pubkey, false),
+            AccountMeta::new(loan.reserve_pubkey, false),
+            AccountMeta::new(loan.reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(loan.host_fee_receiver_pubkey, false),
+        ]);
+    }
+    accounts.extend(flash_loan_receiver_program_accounts);
     Instruction {
         program_id,
-        accounts: vec![
-            AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(sysvar::rent::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
-        ],
-        data: LendingInstruction::InitObligation.pack(),
+        accounts,
+        data: LendingInstruction::FlashLoanBatch {
+            liquidity_amounts: loans.iter().map(|loan| loan.liquidity_amount).collect(),
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'RefreshObligation' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn refresh_obligation(
+/// Finds the flash loan whitelist address derived from the lending market
+pub fn find_flash_loan_whitelist_address(
+    program_id: &Pubkey,
+    lending_market_pubkey: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[lending_market_pubkey.as_ref(), b"flash_loan_whitelist"],
+        program_id,
+    )
+}
+
+/// Creates an `AddFlashLoanWhitelistEntry` instruction
+pub fn add_flash_loan_whitelist_entry(
     program_id: Pubkey,
-    obligation_pubkey: Pubkey,
-    reserve_pubkeys: Vec<Pubkey>,
+    receiver_program_id: Pubkey,
+    flash_loan_fee_wad: u64,
+    lending_market_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
 ) -> Instruction {
-    let mut accounts = vec![
-        AccountMeta::new(obligation_pubkey, false),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-    ];
-    accounts.extend(
-        reserve_pubkeys
-            .into_iter()
-            .map(|pubkey| AccountMeta::new_readonly(pubkey, false)),
-    );
+    let (flash_loan_whitelist_pubkey, _bump_seed) =
+        find_flash_loan_whitelist_address(&program_id, &lending_market_pubkey);
     Instruction {
         program_id,
-        accounts,
-        data: LendingInstruction::RefreshObligation.pack(),
+        accounts: vec![
+            AccountMeta::new(flash_loan_whitelist_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new(lending_market_owner, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+        ],
+        data: LendingInstruction::AddFlashLoanWhitelistEntry {
+            receiver_program_id,
+            flash_loan_fee_wad,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'DepositObligationCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn deposit_obligation_collateral(
+/// Creates a `RemoveFlashLoanWhitelistEntry` instruction
+pub fn remove_flash_loan_whitelist_entry(
     program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    deposit_reserve_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
+    receiver_program_id: Pubkey,
     lending_market_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
 ) -> Instruction {
+    let (flash_loan_whitelist_pubkey, _bump_seed) =
+        find_flash_loan_whitelist_address(&program_id, &lending_market_pubkey);
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(deposit_reserve_pubkey, false),
-            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(flash_loan_whitelist_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_owner, true),
         ],
-        data: LendingInstruction::DepositObligationCollateral { collateral_amount }.pack(),
+        data: LendingInstruction::RemoveFlashLoanWhitelistEntry {
+            receiver_program_id,
+        }
+        .pack(),
     }
 }
 
-/// Creates a 'DepositReserveLiquidityAndObligationCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn deposit_reserve_liquidity_and_obligation_collateral(
+/// Finds the referrer fees account address derived from the reserve and referrer
+pub fn find_referrer_fees_address(
+    program_id: &Pubkey,
+    reserve_pubkey: &Pubkey,
+    referrer_pubkey: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            reserve_pubkey.as_ref(),
+            referrer_pubkey.as_ref(),
+            b"referrer_fees",
+        ],
+        program_id,
+    )
+}
+
+/// Creates an `InitReferrerFeesAccount` instruction
+pub fn init_referrer_fees_account(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    user_collateral_pubkey: Pubkey,
     reserve_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    destination_deposit_collateral_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
-    reserve_liquidity_pyth_oracle_pubkey: Pubkey,
-    reserve_liquidity_switchboard_oracle_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    referrer_pubkey: Pubkey,
+    funding_pubkey: Pubkey,
 ) -> Instruction {
+    let (referrer_fees_pubkey, _bump_seed) =
+        find_referrer_fees_address(&program_id, &reserve_pubkey, &referrer_pubkey);
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
@@ -1049,45 +3042,31 @@ pub fn deposit_reserve_liquidity_and_obligation_collateral(
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(user_collateral_pubkey, false),
-            AccountMeta::new(reserve_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(referrer_fees_pubkey, false),
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new(destination_deposit_collateral_pubkey, false),
-            AccountMeta::new(obligation_pubkey, false),
-            AccountMeta::new(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(reserve_liquidity_pyth_oracle_pubkey, false),
-            AccountMeta::new_readonly(reserve_liquidity_switchboard_oracle_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(referrer_pubkey, false),
+            AccountMeta::new(funding_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
-            liquidity_amount,
-        }
-        .pack(),
+        data: LendingInstruction::InitReferrerFeesAccount.pack(),
     }
 }
-
-/// Creates a 'WithdrawObligationCollateralAndRedeemReserveCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn withdraw_obligation_collateral_and_redeem_reserve_collateral(
-    program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
-    lending_market_pubkey: Pubkey,
+
+/// Creates a `ClaimReferralFees` instruction
+pub fn claim_referral_fees(
+    program_id: Pubkey,
+    reserve_pubkey: Pubkey,
     destination_liquidity_pubkey: Pubkey,
-    reserve_collateral_mint_pubkey: Pubkey,
-    reserve_liquidity_supply_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    lending_market_pubkey: Pubkey,
+    referrer_pubkey: Pubkey,
 ) -> Instruction {
+    let (referrer_fees_pubkey, _bump_seed) =
+        find_referrer_fees_address(&program_id, &reserve_pubkey, &referrer_pubkey);
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
@@ -1095,38 +3074,26 @@ pub fn withdraw_obligation_collateral_and_redeem_reserve_collateral(
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(withdraw_reserve_pubkey, false),
-            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(referrer_fees_pubkey, false),
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new(destination_liquidity_pubkey, false),
-            AccountMeta::new(reserve_collateral_mint_pubkey, false),
-            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(referrer_pubkey, true),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
-            collateral_amount,
-        }
-        .pack(),
+        data: LendingInstruction::ClaimReferralFees.pack(),
     }
 }
 
-/// Creates a 'WithdrawObligationCollateral' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn withdraw_obligation_collateral(
+/// Creates a `CollectProtocolFees` instruction
+pub fn collect_protocol_fees(
     program_id: Pubkey,
-    collateral_amount: u64,
-    source_collateral_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
     destination_collateral_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
@@ -1135,103 +3102,114 @@ pub fn withdraw_obligation_collateral(
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_collateral_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
             AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
-            AccountMeta::new(obligation_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(obligation_owner_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::WithdrawObligationCollateral { collateral_amount }.pack(),
+        data: LendingInstruction::CollectProtocolFees.pack(),
     }
 }
 
-/// Creates a 'BorrowObligationLiquidity' instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn borrow_obligation_liquidity(
+/// Finds the address of the lending market insurance fund account holding the liquidity mint
+pub fn find_insurance_fund_address(
+    program_id: &Pubkey,
+    lending_market_pubkey: &Pubkey,
+    liquidity_mint_pubkey: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            lending_market_pubkey.as_ref(),
+            liquidity_mint_pubkey.as_ref(),
+            b"insurance_fund",
+        ],
+        program_id,
+    )
+}
+
+/// Creates an `InitInsuranceFundAccount` instruction
+pub fn init_insurance_fund_account(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_liquidity_pubkey: Pubkey,
-    borrow_reserve_pubkey: Pubkey,
-    borrow_reserve_liquidity_fee_receiver_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    obligation_owner_pubkey: Pubkey,
-    host_fee_receiver_pubkey: Option<Pubkey>,
+    funding_pubkey: Pubkey,
 ) -> Instruction {
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        &program_id,
+        &lending_market_pubkey,
+        &reserve_liquidity_mint_pubkey,
+    );
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let mut accounts = vec![
-        AccountMeta::new(source_liquidity_pubkey, false),
-        AccountMeta::new(destination_liquidity_pubkey, false),
-        AccountMeta::new(borrow_reserve_pubkey, false),
-        AccountMeta::new(borrow_reserve_liquidity_fee_receiver_pubkey, false),
-        AccountMeta::new(obligation_pubkey, false),
-        AccountMeta::new_readonly(lending_market_pubkey, false),
-        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(obligation_owner_pubkey, true),
-        AccountMeta::new_readonly(sysvar::clock::id(), false),
-        AccountMeta::new_readonly(spl_token::id(), false),
-    ];
-    if let Some(host_fee_receiver_pubkey) = host_fee_receiver_pubkey {
-        accounts.push(AccountMeta::new(host_fee_receiver_pubkey, false));
-    }
     Instruction {
         program_id,
-        accounts,
-        data: LendingInstruction::BorrowObligationLiquidity { liquidity_amount }.pack(),
+        accounts: vec![
+            AccountMeta::new(insurance_fund_pubkey, false),
+            AccountMeta::new_readonly(reserve_pubkey, false),
+            AccountMeta::new_readonly(reserve_liquidity_mint_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new(funding_pubkey, true),
+            AccountMeta::new_readonly(sysvar::rent::id(), false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::InitInsuranceFundAccount.pack(),
     }
 }
 
-/// Creates a `RepayObligationLiquidity` instruction
-#[allow(clippy::too_many_arguments)]
-pub fn repay_obligation_liquidity(
+/// Creates a `RedeemInsuranceFees` instruction
+pub fn redeem_insurance_fees(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_liquidity_pubkey: Pubkey,
-    repay_reserve_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
+    reserve_pubkey: Pubkey,
+    reserve_liquidity_mint_pubkey: Pubkey,
+    reserve_supply_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
 ) -> Instruction {
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        &program_id,
+        &lending_market_pubkey,
+        &reserve_liquidity_mint_pubkey,
+    );
+    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
+        &program_id,
+    );
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_liquidity_pubkey, false),
-            AccountMeta::new(repay_reserve_pubkey, false),
-            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(insurance_fund_pubkey, false),
+            AccountMeta::new(reserve_supply_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::RepayObligationLiquidity { liquidity_amount }.pack(),
+        data: LendingInstruction::RedeemInsuranceFees.pack(),
     }
 }
 
-/// Creates a `LiquidateObligation` instruction
-#[allow(clippy::too_many_arguments)]
-pub fn liquidate_obligation(
+/// Creates a `WriteOffBadDebt` instruction
+pub fn write_off_bad_debt(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    repay_reserve_pubkey: Pubkey,
-    repay_reserve_liquidity_supply_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
-    withdraw_reserve_collateral_supply_pubkey: Pubkey,
     obligation_pubkey: Pubkey,
+    borrow_reserve_pubkey: Pubkey,
+    borrow_reserve_liquidity_mint_pubkey: Pubkey,
+    borrow_reserve_liquidity_supply_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    lending_market_owner: Pubkey,
 ) -> Instruction {
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        &program_id,
+        &lending_market_pubkey,
+        &borrow_reserve_liquidity_mint_pubkey,
+    );
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
@@ -1239,164 +3217,132 @@ pub fn liquidate_obligation(
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(repay_reserve_pubkey, false),
-            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new_readonly(withdraw_reserve_pubkey, false),
-            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
             AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(borrow_reserve_pubkey, false),
+            AccountMeta::new(borrow_reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(insurance_fund_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
             AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(sysvar::clock::id(), false),
+            AccountMeta::new_readonly(lending_market_owner, true),
             AccountMeta::new_readonly(spl_token::id(), false),
         ],
-        data: LendingInstruction::LiquidateObligation { liquidity_amount }.pack(),
+        data: LendingInstruction::WriteOffBadDebt.pack(),
     }
 }
 
-/// Creates a `FlashLoan` instruction.
-#[allow(clippy::too_many_arguments)]
-pub fn flash_loan(
+/// Creates a `SetReserveStatus` instruction
+pub fn set_reserve_status(
     program_id: Pubkey,
-    amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_liquidity_pubkey: Pubkey,
+    status: ReserveStatus,
     reserve_pubkey: Pubkey,
-    reserve_liquidity_fee_receiver_pubkey: Pubkey,
-    host_fee_receiver_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    flash_loan_receiver_program_id: Pubkey,
-    flash_loan_receiver_program_accounts: Vec<AccountMeta>,
+    lending_market_owner_pubkey: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
-        &program_id,
-    );
-    let mut accounts = vec![
-        AccountMeta::new(source_liquidity_pubkey, false),
-        AccountMeta::new(destination_liquidity_pubkey, false),
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
-        AccountMeta::new(host_fee_receiver_pubkey, false),
-        AccountMeta::new_readonly(lending_market_pubkey, false),
-        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(spl_token::id(), false),
-        AccountMeta::new_readonly(flash_loan_receiver_program_id, false),
-    ];
-    accounts.extend(flash_loan_receiver_program_accounts);
     Instruction {
         program_id,
-        accounts,
-        data: LendingInstruction::FlashLoan { amount }.pack(),
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+        ],
+        data: LendingInstruction::SetReserveStatus { status }.pack(),
     }
 }
 
-/// Creates an 'UpdateReserveConfig' instruction.
+/// Creates a `CloseReserve` instruction
 #[allow(clippy::too_many_arguments)]
-pub fn update_reserve_config(
+pub fn close_reserve(
     program_id: Pubkey,
-    config: ReserveConfig,
     reserve_pubkey: Pubkey,
+    reserve_liquidity_supply_pubkey: Pubkey,
+    reserve_liquidity_fee_receiver_pubkey: Pubkey,
+    reserve_collateral_mint_pubkey: Pubkey,
+    reserve_collateral_supply_pubkey: Pubkey,
+    destination_liquidity_pubkey: Pubkey,
+    destination_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
     lending_market_owner_pubkey: Pubkey,
-    pyth_product_pubkey: Pubkey,
-    pyth_price_pubkey: Pubkey,
-    switchboard_feed_pubkey: Pubkey,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let accounts = vec![
-        AccountMeta::new(reserve_pubkey, false),
-        AccountMeta::new_readonly(lending_market_pubkey, false),
-        AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-        AccountMeta::new_readonly(lending_market_owner_pubkey, true),
-        AccountMeta::new_readonly(pyth_product_pubkey, false),
-        AccountMeta::new_readonly(pyth_price_pubkey, false),
-        AccountMeta::new_readonly(switchboard_feed_pubkey, false),
-    ];
     Instruction {
         program_id,
-        accounts,
-        data: LendingInstruction::UpdateReserveConfig { config }.pack(),
+        accounts: vec![
+            AccountMeta::new(reserve_pubkey, false),
+            AccountMeta::new(reserve_liquidity_supply_pubkey, false),
+            AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
+            AccountMeta::new(reserve_collateral_mint_pubkey, false),
+            AccountMeta::new(reserve_collateral_supply_pubkey, false),
+            AccountMeta::new(destination_liquidity_pubkey, false),
+            AccountMeta::new(destination_pubkey, false),
+            AccountMeta::new_readonly(lending_market_pubkey, false),
+            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+        data: LendingInstruction::CloseReserve.pack(),
     }
 }
 
-/// Creates a `LiquidateObligationAndRedeemReserveCollateral` instruction
-#[allow(clippy::too_many_arguments)]
-pub fn liquidate_obligation_and_redeem_reserve_collateral(
+/// Creates a `MigrateAccount` instruction
+pub fn migrate_account(program_id: Pubkey, account_pubkey: Pubkey) -> Instruction {
+    Instruction {
+        program_id,
+        accounts: vec![AccountMeta::new(account_pubkey, false)],
+        data: LendingInstruction::MigrateAccount.pack(),
+    }
+}
+
+/// Creates a `SetReserveRateLimiter` instruction
+pub fn set_reserve_rate_limiter(
     program_id: Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_pubkey: Pubkey,
-    destination_collateral_pubkey: Pubkey,
-    destination_liquidity_pubkey: Pubkey,
-    repay_reserve_pubkey: Pubkey,
-    repay_reserve_liquidity_supply_pubkey: Pubkey,
-    withdraw_reserve_pubkey: Pubkey,
-    withdraw_reserve_collateral_mint_pubkey: Pubkey,
-    withdraw_reserve_collateral_supply_pubkey: Pubkey,
-    withdraw_reserve_liquidity_supply_pubkey: Pubkey,
-    withdraw_reserve_liquidity_fee_receiver_pubkey: Pubkey,
-    obligation_pubkey: Pubkey,
+    config: RateLimiterConfig,
+    reserve_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
-    user_transfer_authority_pubkey: Pubkey,
+    lending_market_owner_pubkey: Pubkey,
 ) -> Instruction {
-    let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
-        &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
-        &program_id,
-    );
     Instruction {
         program_id,
         accounts: vec![
-            AccountMeta::new(source_liquidity_pubkey, false),
-            AccountMeta::new(destination_collateral_pubkey, false),
-            AccountMeta::new(destination_liquidity_pubkey, false),
-            AccountMeta::new(repay_reserve_pubkey, false),
-            AccountMeta::new(repay_reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(withdraw_reserve_pubkey, false),
-            AccountMeta::new(withdraw_reserve_collateral_mint_pubkey, false),
-            AccountMeta::new(withdraw_reserve_collateral_supply_pubkey, false),
-            AccountMeta::new(withdraw_reserve_liquidity_supply_pubkey, false),
-            AccountMeta::new(withdraw_reserve_liquidity_fee_receiver_pubkey, false),
-            AccountMeta::new(obligation_pubkey, false),
+            AccountMeta::new(reserve_pubkey, false),
             AccountMeta::new_readonly(lending_market_pubkey, false),
-            AccountMeta::new_readonly(lending_market_authority_pubkey, false),
-            AccountMeta::new_readonly(user_transfer_authority_pubkey, true),
-            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new_readonly(lending_market_owner_pubkey, true),
         ],
-        data: LendingInstruction::LiquidateObligationAndRedeemReserveCollateral {
-            liquidity_amount,
-        }
-        .pack(),
+        data: LendingInstruction::SetReserveRateLimiter { config }.pack(),
     }
 }
 
-/// Creates a `RedeemFees` instruction
-pub fn redeem_fees(
+/// Creates a `DistributeFees` instruction
+pub fn distribute_fees(
     program_id: Pubkey,
     reserve_pubkey: Pubkey,
     reserve_liquidity_fee_receiver_pubkey: Pubkey,
-    reserve_supply_liquidity_pubkey: Pubkey,
+    owner_liquidity_pubkey: Pubkey,
     lending_market_pubkey: Pubkey,
+    fee_distribution_receiver_pubkeys: Vec<Pubkey>,
 ) -> Instruction {
     let (lending_market_authority_pubkey, _bump_seed) = Pubkey::find_program_address(
         &[&lending_market_pubkey.to_bytes()[..PUBKEY_BYTES]],
         &program_id,
     );
-    let accounts = vec![
-        AccountMeta::new(reserve_pubkey, false),
+    let mut accounts = vec![
+        AccountMeta::new_readonly(reserve_pubkey, false),
         AccountMeta::new(reserve_liquidity_fee_receiver_pubkey, false),
-        AccountMeta::new(reserve_supply_liquidity_pubkey, false),
+        AccountMeta::new(owner_liquidity_pubkey, false),
         AccountMeta::new_readonly(lending_market_pubkey, false),
         AccountMeta::new_readonly(lending_market_authority_pubkey, false),
         AccountMeta::new_readonly(spl_token::id(), false),
     ];
+    accounts.extend(
+        fee_distribution_receiver_pubkeys
+            .into_iter()
+            .map(|pubkey| AccountMeta::new(pubkey, false)),
+    );
     Instruction {
         program_id,
         accounts,
-        data: LendingInstruction::RedeemFees.pack(),
+        data: LendingInstruction::DistributeFees.pack(),
     }
 }
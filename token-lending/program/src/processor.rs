@@ -3,14 +3,20 @@
 use crate::{
     self as solend_program,
     error::LendingError,
-    instruction::LendingInstruction,
+    instruction::{
+        find_flash_loan_whitelist_address, find_insurance_fund_address, find_obligation_address,
+        find_referrer_fees_address, LendingInstruction,
+    },
     math::{Decimal, Rate, TryAdd, TryDiv, TryMul, TrySub, WAD},
     pyth,
     state::{
-        CalculateBorrowResult, CalculateLiquidationResult, CalculateRepayResult,
+        preview_liquidation, CalculateBorrowResult, CalculateFlashLoanResult, CalculateRepayResult,
+        FeeCalculation, FeeDistributionReceiver, FlashLoanWhitelist, FlashLoanWhitelistEntry,
         InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
-        NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, Reserve,
-        ReserveCollateral, ReserveConfig, ReserveLiquidity,
+        LiquidationPreview, Migrate, NewReserveCollateralParams, NewReserveLiquidityParams,
+        Obligation, RateLimiter, RateLimiterConfig, Reserve, ReserveCollateral, ReserveConfig,
+        ReserveLiquidity, ReserveStatus, MAX_FEE_DISTRIBUTION_RECEIVERS, OBLIGATION_DELEGATE_ALL,
+        OBLIGATION_DELEGATE_BORROW, OBLIGATION_DELEGATE_DEPOSIT, OBLIGATION_DELEGATE_WITHDRAW,
     },
 };
 use num_traits::FromPrimitive;
@@ -24,11 +30,20 @@ use solana_program::{
     program_error::{PrintProgramError, ProgramError},
     program_pack::{IsInitialized, Pack},
     pubkey::Pubkey,
-    sysvar::{clock::Clock, rent::Rent, Sysvar},
+    system_instruction,
+    sysvar::{
+        clock::Clock,
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        rent::Rent,
+        Sysvar,
+    },
 };
 use spl_token::solana_program::instruction::AccountMeta;
 use spl_token::state::{Account, Mint};
-use std::{convert::TryInto, result::Result};
+use std::{
+    convert::{TryFrom, TryInto},
+    result::Result,
+};
 use switchboard_program::{
     get_aggregator, get_aggregator_result, AggregatorState, RoundResult, SwitchboardAccountType,
 };
@@ -66,9 +81,16 @@ pub fn process_instruction(
         LendingInstruction::InitReserve {
             liquidity_amount,
             config,
+            initial_collateral_ratio,
         } => {
             msg!("Instruction: Init Reserve");
-            process_init_reserve(program_id, liquidity_amount, config, accounts)
+            process_init_reserve(
+                program_id,
+                liquidity_amount,
+                config,
+                initial_collateral_ratio,
+                accounts,
+            )
         }
         LendingInstruction::RefreshReserve => {
             msg!("Instruction: Refresh Reserve");
@@ -80,7 +102,7 @@ pub fn process_instruction(
         }
         LendingInstruction::RedeemReserveCollateral { collateral_amount } => {
             msg!("Instruction: Redeem Reserve Collateral");
-            process_redeem_reserve_collateral(program_id, collateral_amount, accounts)
+            process_redeem_reserve_collateral(program_id, collateral_amount, 0, accounts)
         }
         LendingInstruction::InitObligation => {
             msg!("Instruction: Init Obligation");
@@ -100,7 +122,7 @@ pub fn process_instruction(
         }
         LendingInstruction::BorrowObligationLiquidity { liquidity_amount } => {
             msg!("Instruction: Borrow Obligation Liquidity");
-            process_borrow_obligation_liquidity(program_id, liquidity_amount, accounts)
+            process_borrow_obligation_liquidity(program_id, liquidity_amount, 0, accounts)
         }
         LendingInstruction::RepayObligationLiquidity { liquidity_amount } => {
             msg!("Instruction: Repay Obligation Liquidity");
@@ -108,7 +130,7 @@ pub fn process_instruction(
         }
         LendingInstruction::LiquidateObligation { liquidity_amount } => {
             msg!("Instruction: Liquidate Obligation");
-            process_liquidate_obligation(program_id, liquidity_amount, accounts)
+            process_liquidate_obligation(program_id, liquidity_amount, 0, accounts)
         }
         LendingInstruction::FlashLoan { amount } => {
             msg!("Instruction: Flash Loan");
@@ -148,6 +170,189 @@ pub fn process_instruction(
             msg!("Instruction: RedeemFees");
             process_redeem_fees(program_id, accounts)
         }
+        LendingInstruction::LiquidateObligationBatch { liquidity_amounts } => {
+            msg!("Instruction: Liquidate Obligation Batch");
+            process_liquidate_obligation_batch(program_id, &liquidity_amounts, accounts)
+        }
+        LendingInstruction::BorrowObligationLiquidityWithMinReceive {
+            liquidity_amount,
+            min_receive_amount,
+        } => {
+            msg!("Instruction: Borrow Obligation Liquidity With Min Receive");
+            process_borrow_obligation_liquidity(
+                program_id,
+                liquidity_amount,
+                min_receive_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::LiquidateObligationWithMinCollateral {
+            liquidity_amount,
+            min_collateral_received,
+        } => {
+            msg!("Instruction: Liquidate Obligation With Min Collateral");
+            process_liquidate_obligation(
+                program_id,
+                liquidity_amount,
+                min_collateral_received,
+                accounts,
+            )
+        }
+        LendingInstruction::RedeemReserveCollateralWithMinLiquidity {
+            collateral_amount,
+            min_liquidity_out,
+        } => {
+            msg!("Instruction: Redeem Reserve Collateral With Min Liquidity");
+            process_redeem_reserve_collateral(
+                program_id,
+                collateral_amount,
+                min_liquidity_out,
+                accounts,
+            )
+        }
+        LendingInstruction::UpgradeObligation => {
+            msg!("Instruction: Upgrade Obligation");
+            process_upgrade_obligation(program_id, accounts)
+        }
+        LendingInstruction::CloseObligation => {
+            msg!("Instruction: Close Obligation");
+            process_close_obligation(program_id, accounts)
+        }
+        LendingInstruction::InitDerivedObligation { index } => {
+            msg!("Instruction: Init Derived Obligation");
+            process_init_derived_obligation(program_id, index, accounts)
+        }
+        LendingInstruction::SetObligationDelegate {
+            delegate,
+            permissions,
+        } => {
+            msg!("Instruction: Set Obligation Delegate");
+            process_set_obligation_delegate(program_id, delegate, permissions, accounts)
+        }
+        LendingInstruction::TransferObligationOwnership { new_owner } => {
+            msg!("Instruction: Transfer Obligation Ownership");
+            process_transfer_obligation_ownership(program_id, new_owner, accounts)
+        }
+        LendingInstruction::SwapObligationCollateral {
+            deposit_collateral_amount,
+            withdraw_collateral_amount,
+        } => {
+            msg!("Instruction: Swap Obligation Collateral");
+            process_swap_obligation_collateral(
+                program_id,
+                deposit_collateral_amount,
+                withdraw_collateral_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::SwapObligationDebt {
+            repay_liquidity_amount,
+            borrow_liquidity_amount,
+        } => {
+            msg!("Instruction: Swap Obligation Debt");
+            process_swap_obligation_debt(
+                program_id,
+                repay_liquidity_amount,
+                borrow_liquidity_amount,
+                accounts,
+            )
+        }
+        LendingInstruction::FlashBorrowReserveLiquidity { liquidity_amount } => {
+            msg!("Instruction: Flash Borrow Reserve Liquidity");
+            process_flash_borrow_reserve_liquidity(program_id, liquidity_amount, accounts)
+        }
+        LendingInstruction::FlashRepayReserveLiquidity {
+            liquidity_amount,
+            borrow_instruction_index,
+        } => {
+            msg!("Instruction: Flash Repay Reserve Liquidity");
+            process_flash_repay_reserve_liquidity(
+                program_id,
+                liquidity_amount,
+                borrow_instruction_index,
+                accounts,
+            )
+        }
+        LendingInstruction::FlashLoanBatch { liquidity_amounts } => {
+            msg!("Instruction: Flash Loan Batch");
+            process_flash_loan_batch(program_id, &liquidity_amounts, accounts)
+        }
+        LendingInstruction::AddFlashLoanWhitelistEntry {
+            receiver_program_id,
+            flash_loan_fee_wad,
+        } => {
+            msg!("Instruction: Add Flash Loan Whitelist Entry");
+            process_add_flash_loan_whitelist_entry(
+                program_id,
+                receiver_program_id,
+                flash_loan_fee_wad,
+                accounts,
+            )
+        }
+        LendingInstruction::RemoveFlashLoanWhitelistEntry {
+            receiver_program_id,
+        } => {
+            msg!("Instruction: Remove Flash Loan Whitelist Entry");
+            process_remove_flash_loan_whitelist_entry(program_id, receiver_program_id, accounts)
+        }
+        LendingInstruction::InitReferrerFeesAccount => {
+            msg!("Instruction: Init Referrer Fees Account");
+            process_init_referrer_fees_account(program_id, accounts)
+        }
+        LendingInstruction::ClaimReferralFees => {
+            msg!("Instruction: Claim Referral Fees");
+            process_claim_referral_fees(program_id, accounts)
+        }
+        LendingInstruction::CollectProtocolFees => {
+            msg!("Instruction: Collect Protocol Fees");
+            process_collect_protocol_fees(program_id, accounts)
+        }
+        LendingInstruction::SetLendingMarketFeeDistribution { fee_distribution } => {
+            msg!("Instruction: Set Lending Market Fee Distribution");
+            process_set_lending_market_fee_distribution(program_id, fee_distribution, accounts)
+        }
+        LendingInstruction::SetLendingMarketInsuranceFundFee {
+            insurance_fund_fee_percentage,
+        } => {
+            msg!("Instruction: Set Lending Market Insurance Fund Fee");
+            process_set_lending_market_insurance_fund_fee(
+                program_id,
+                insurance_fund_fee_percentage,
+                accounts,
+            )
+        }
+        LendingInstruction::InitInsuranceFundAccount => {
+            msg!("Instruction: Init Insurance Fund Account");
+            process_init_insurance_fund_account(program_id, accounts)
+        }
+        LendingInstruction::RedeemInsuranceFees => {
+            msg!("Instruction: Redeem Insurance Fees");
+            process_redeem_insurance_fees(program_id, accounts)
+        }
+        LendingInstruction::WriteOffBadDebt => {
+            msg!("Instruction: Write Off Bad Debt");
+            process_write_off_bad_debt(program_id, accounts)
+        }
+        LendingInstruction::SetReserveStatus { status } => {
+            msg!("Instruction: Set Reserve Status");
+            process_set_reserve_status(program_id, status, accounts)
+        }
+        LendingInstruction::CloseReserve => {
+            msg!("Instruction: Close Reserve");
+            process_close_reserve(program_id, accounts)
+        }
+        LendingInstruction::MigrateAccount => {
+            msg!("Instruction: Migrate Account");
+            process_migrate_account(program_id, accounts)
+        }
+        LendingInstruction::SetReserveRateLimiter { config } => {
+            msg!("Instruction: Set Reserve Rate Limiter");
+            process_set_reserve_rate_limiter(program_id, config, accounts)
+        }
+        LendingInstruction::DistributeFees => {
+            msg!("Instruction: Distribute Fees");
+            process_distribute_fees(program_id, accounts)
+        }
     }
 }
 
@@ -214,16 +419,128 @@ fn process_set_lending_market_owner(
     Ok(())
 }
 
+fn process_set_lending_market_fee_distribution(
+    program_id: &Pubkey,
+    fee_distribution: Vec<FeeDistributionReceiver>,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if fee_distribution.len() > MAX_FEE_DISTRIBUTION_RECEIVERS {
+        msg!(
+            "Fee distribution cannot have more than {} receivers",
+            MAX_FEE_DISTRIBUTION_RECEIVERS
+        );
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if fee_distribution
+        .iter()
+        .any(|receiver| receiver.owner == Pubkey::default())
+    {
+        msg!("Fee distribution receiver owner must be set");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    let total_fee_percentage: u32 = fee_distribution
+        .iter()
+        .map(|receiver| receiver.fee_percentage as u32)
+        .sum();
+    if total_fee_percentage > 100 {
+        msg!("Fee distribution percentages must sum to at most 100");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    lending_market.fee_distribution = fee_distribution;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_set_lending_market_insurance_fund_fee(
+    program_id: &Pubkey,
+    insurance_fund_fee_percentage: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if insurance_fund_fee_percentage > 100 {
+        msg!("Insurance fund fee percentage must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
+
+    let mut lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    // interest accrued so far is split with the previous percentage
+    for reserve_info in account_info_iter {
+        let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        if reserve_info.owner != program_id {
+            msg!("Reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if &reserve.lending_market != lending_market_info.key {
+            msg!("Reserve lending market does not match the lending market provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if reserve.last_update.is_stale(clock.slot)? {
+            msg!("Reserve is stale and must be refreshed in the current slot");
+            return Err(LendingError::ReserveStale.into());
+        }
+
+        reserve.insurance_fund_fee_percentage = insurance_fund_fee_percentage;
+        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    }
+
+    lending_market.insurance_fund_fee_percentage = insurance_fund_fee_percentage;
+    LendingMarket::pack(lending_market, &mut lending_market_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
 fn process_init_reserve(
     program_id: &Pubkey,
     liquidity_amount: u64,
     config: ReserveConfig,
+    initial_collateral_ratio: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     if liquidity_amount == 0 {
         msg!("Reserve must be initialized with liquidity");
         return Err(LendingError::InvalidAmount.into());
     }
+    if initial_collateral_ratio == 0 {
+        msg!("Initial collateral ratio must be greater than 0");
+        return Err(LendingError::InvalidConfig.into());
+    }
     validate_reserve_config(config)?;
     let account_info_iter = &mut accounts.iter().peekable();
     let source_liquidity_info = next_account_info(account_info_iter)?;
@@ -328,9 +645,11 @@ fn process_init_reserve(
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
             mint_pubkey: *reserve_collateral_mint_info.key,
             supply_pubkey: *reserve_collateral_supply_info.key,
+            initial_collateral_ratio,
         }),
         config,
     });
+    reserve.insurance_fund_fee_percentage = lending_market.insurance_fund_fee_percentage;
 
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -553,6 +872,10 @@ fn _deposit_reserve_liquidity<'a>(
         msg!("Reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if !reserve.is_active() {
+        msg!("Reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
         &[lending_market.bump_seed],
@@ -576,6 +899,10 @@ fn _deposit_reserve_liquidity<'a>(
         msg!("Cannot deposit liquidity above the reserve deposit limit");
         return Err(LendingError::InvalidAmount.into());
     }
+    if reserve.exceeds_deposit_value_limit(liquidity_amount)? {
+        msg!("Cannot deposit liquidity above the reserve deposit value limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
 
     let collateral_amount = reserve.deposit_liquidity(liquidity_amount)?;
     reserve.last_update.mark_stale();
@@ -605,6 +932,7 @@ fn _deposit_reserve_liquidity<'a>(
 fn process_redeem_reserve_collateral(
     program_id: &Pubkey,
     collateral_amount: u64,
+    min_liquidity_out: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     if collateral_amount == 0 {
@@ -624,8 +952,15 @@ fn process_redeem_reserve_collateral(
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
+    // rate limited outflows are valued at the market price, which only a full refresh updates
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve.rate_limiter.is_enabled() && reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot while its outflows are rate limited");
+        return Err(LendingError::ReserveStale.into());
+    }
+
     _refresh_reserve_interest(program_id, reserve_info, clock)?;
-    _redeem_reserve_collateral(
+    let liquidity_amount = _redeem_reserve_collateral(
         program_id,
         collateral_amount,
         source_collateral_info,
@@ -638,7 +973,16 @@ fn process_redeem_reserve_collateral(
         user_transfer_authority_info,
         clock,
         token_program_id,
+        true,
     )?;
+    if liquidity_amount < min_liquidity_out {
+        msg!(
+            "Redeemed liquidity amount {} is less than the minimum {}",
+            liquidity_amount,
+            min_liquidity_out
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
@@ -660,6 +1004,7 @@ fn _redeem_reserve_collateral<'a>(
     user_transfer_authority_info: &AccountInfo<'a>,
     clock: &Clock,
     token_program_id: &AccountInfo<'a>,
+    check_rate_limit: bool,
 ) -> Result<u64, ProgramError> {
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
@@ -715,6 +1060,11 @@ fn _redeem_reserve_collateral<'a>(
     }
 
     let liquidity_amount = reserve.redeem_collateral(collateral_amount)?;
+    // liquidations are exempt so that unhealthy obligations can always be closed out
+    if check_rate_limit {
+        let outflow_value = reserve.market_value(liquidity_amount.into())?;
+        reserve.rate_limiter.update(clock.slot, outflow_value)?;
+    }
     reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
@@ -748,6 +1098,9 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
+    let referrer = next_account_info(account_info_iter)
+        .map(|referrer_info| *referrer_info.key)
+        .unwrap_or_default();
 
     assert_rent_exempt(rent, obligation_info)?;
     let mut obligation = assert_uninitialized::<Obligation>(obligation_info)?;
@@ -770,6 +1123,10 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         msg!("Obligation owner provided must be a signer");
         return Err(LendingError::InvalidSigner.into());
     }
+    if &referrer == obligation_owner_info.key {
+        msg!("Obligation owner cannot be its own referrer");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
 
     obligation.init(InitObligationParams {
         current_slot: clock.slot,
@@ -777,79 +1134,331 @@ fn process_init_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> Pro
         owner: *obligation_owner_info.key,
         deposits: vec![],
         borrows: vec![],
+        referrer,
     });
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     Ok(())
 }
 
-fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter().peekable();
+fn process_init_derived_obligation(
+    program_id: &Pubkey,
+    index: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
     let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let referrer = next_account_info(account_info_iter)
+        .map(|referrer_info| *referrer_info.key)
+        .unwrap_or_default();
 
-    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
-    if obligation_info.owner != program_id {
-        msg!("Obligation provided is not owned by the lending program");
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
+    LendingMarket::unpack(&lending_market_info.data.borrow())?;
 
-    let mut deposited_value = Decimal::zero();
-    let mut borrowed_value = Decimal::zero();
-    let mut allowed_borrow_value = Decimal::zero();
-    let mut unhealthy_borrow_value = Decimal::zero();
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &referrer == obligation_owner_info.key {
+        msg!("Obligation owner cannot be its own referrer");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
 
-    for (index, collateral) in obligation.deposits.iter_mut().enumerate() {
-        let deposit_reserve_info = next_account_info(account_info_iter)?;
-        if deposit_reserve_info.owner != program_id {
-            msg!(
-                "Deposit reserve provided for collateral {} is not owned by the lending program",
-                index
-            );
-            return Err(LendingError::InvalidAccountOwner.into());
-        }
-        if collateral.deposit_reserve != *deposit_reserve_info.key {
-            msg!(
-                "Deposit reserve of collateral {} does not match the deposit reserve provided",
-                index
-            );
-            return Err(LendingError::InvalidAccountInput.into());
-        }
+    let (obligation_pubkey, bump_seed) = find_obligation_address(
+        program_id,
+        lending_market_info.key,
+        obligation_owner_info.key,
+        index,
+    );
+    if &obligation_pubkey != obligation_info.key {
+        msg!("Obligation provided does not match the derived obligation address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
 
-        let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
-        if deposit_reserve.last_update.is_stale(clock.slot)? {
-            msg!(
-                "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
-                index
-            );
-            return Err(LendingError::ReserveStale.into());
-        }
+    let obligation_signer_seeds: &[&[_]] = &[
+        lending_market_info.key.as_ref(),
+        obligation_owner_info.key.as_ref(),
+        &[index],
+        &[bump_seed],
+    ];
+    create_pda_account(CreatePdaAccountParams {
+        payer: obligation_owner_info.clone(),
+        account: obligation_info.clone(),
+        space: Obligation::LEN,
+        owner: program_id,
+        rent,
+        signer_seeds: obligation_signer_seeds,
+        system_program: system_program_info.clone(),
+    })?;
 
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(deposit_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
+    let obligation = Obligation::new(InitObligationParams {
+        current_slot: clock.slot,
+        lending_market: *lending_market_info.key,
+        owner: *obligation_owner_info.key,
+        deposits: vec![],
+        borrows: vec![],
+        referrer,
+    });
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
-        let market_value = deposit_reserve
-            .collateral_exchange_rate()?
-            .decimal_collateral_to_liquidity(collateral.deposited_amount.into())?
-            .try_mul(deposit_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
-        collateral.market_value = market_value;
+    Ok(())
+}
 
-        let loan_to_value_rate = Rate::from_percent(deposit_reserve.config.loan_to_value_ratio);
-        let liquidation_threshold_rate =
-            Rate::from_percent(deposit_reserve.config.liquidation_threshold);
+fn process_upgrade_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let new_obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
 
-        deposited_value = deposited_value.try_add(market_value)?;
-        allowed_borrow_value =
-            allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
-        unhealthy_borrow_value =
-            unhealthy_borrow_value.try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
     }
-
-    for (index, liquidity) in obligation.borrows.iter_mut().enumerate() {
-        let borrow_reserve_info = next_account_info(account_info_iter)?;
+    if obligation_info.data_len() == Obligation::LEN {
+        msg!("Obligation provided already uses the current obligation layout");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    assert_rent_exempt(rent, new_obligation_info)?;
+    assert_uninitialized::<Obligation>(new_obligation_info)?;
+    if new_obligation_info.owner != program_id {
+        msg!("New obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if new_obligation_info.data_len() != Obligation::LEN {
+        msg!("New obligation provided must use the current obligation layout");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    obligation.migrate()?;
+    Obligation::pack(obligation, &mut new_obligation_info.data.borrow_mut())?;
+    close_account(obligation_info, destination_info)
+}
+
+fn process_close_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if !obligation.deposits.is_empty() || !obligation.borrows.is_empty() {
+        msg!("Obligation cannot be closed while it has deposits or borrows");
+        return Err(LendingError::ObligationNotEmpty.into());
+    }
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    close_account(obligation_info, destination_info)
+}
+
+fn process_set_obligation_delegate(
+    program_id: &Pubkey,
+    delegate: Pubkey,
+    permissions: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if permissions & !OBLIGATION_DELEGATE_ALL != 0 {
+        msg!("Obligation delegate permissions provided are invalid");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if delegate == obligation.owner {
+        msg!("Obligation owner cannot be the obligation delegate");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    obligation.delegate = delegate;
+    obligation.delegate_permissions = if delegate == Pubkey::default() {
+        0
+    } else {
+        permissions
+    };
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_transfer_obligation_ownership(
+    program_id: &Pubkey,
+    new_owner: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &obligation.owner != obligation_owner_info.key {
+        msg!("Obligation owner does not match the obligation owner provided");
+        return Err(LendingError::InvalidObligationOwner.into());
+    }
+    if !obligation_owner_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    obligation.owner = new_owner;
+    obligation.delegate = Pubkey::default();
+    obligation.delegate_permissions = 0;
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter().peekable();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    let mut deposited_value = Decimal::zero();
+    let mut borrowed_value = Decimal::zero();
+    let mut allowed_borrow_value = Decimal::zero();
+    let mut unhealthy_borrow_value = Decimal::zero();
+
+    for (index, collateral) in obligation.deposits.iter_mut().enumerate() {
+        let deposit_reserve_info = next_account_info(account_info_iter)?;
+        if deposit_reserve_info.owner != program_id {
+            msg!(
+                "Deposit reserve provided for collateral {} is not owned by the lending program",
+                index
+            );
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if collateral.deposit_reserve != *deposit_reserve_info.key {
+            msg!(
+                "Deposit reserve of collateral {} does not match the deposit reserve provided",
+                index
+            );
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+
+        let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+        if deposit_reserve.last_update.is_stale(clock.slot)? {
+            msg!(
+                "Deposit reserve provided for collateral {} is stale and must be refreshed in the current slot",
+                index
+            );
+            return Err(LendingError::ReserveStale.into());
+        }
+
+        let market_value = deposit_reserve.collateral_market_value(collateral.deposited_amount)?;
+        collateral.market_value = market_value;
+
+        // deprecated collateral no longer supports borrowing so positions are unwound
+        let loan_to_value_rate = if deposit_reserve.status == ReserveStatus::Deprecated {
+            Rate::zero()
+        } else {
+            Rate::from_percent(deposit_reserve.config.loan_to_value_ratio)
+        };
+        let liquidation_threshold_rate =
+            Rate::from_percent(deposit_reserve.config.liquidation_threshold);
+
+        deposited_value = deposited_value.try_add(market_value)?;
+        allowed_borrow_value =
+            allowed_borrow_value.try_add(market_value.try_mul(loan_to_value_rate)?)?;
+        unhealthy_borrow_value =
+            unhealthy_borrow_value.try_add(market_value.try_mul(liquidation_threshold_rate)?)?;
+    }
+
+    for (index, liquidity) in obligation.borrows.iter_mut().enumerate() {
+        let borrow_reserve_info = next_account_info(account_info_iter)?;
         if borrow_reserve_info.owner != program_id {
             msg!(
                 "Borrow reserve provided for liquidity {} is not owned by the lending program",
@@ -876,15 +1485,7 @@ fn process_refresh_obligation(program_id: &Pubkey, accounts: &[AccountInfo]) ->
 
         liquidity.accrue_interest(borrow_reserve.liquidity.cumulative_borrow_rate_wads)?;
 
-        // @TODO: add lookup table https://git.io/JOCYq
-        let decimals = 10u64
-            .checked_pow(borrow_reserve.liquidity.mint_decimals as u32)
-            .ok_or(LendingError::MathOverflow)?;
-
-        let market_value = liquidity
-            .borrowed_amount_wads
-            .try_mul(borrow_reserve.liquidity.market_price)?
-            .try_div(decimals)?;
+        let market_value = borrow_reserve.market_value(liquidity.borrowed_amount_wads)?;
         liquidity.market_value = market_value;
 
         borrowed_value = borrowed_value.try_add(market_value)?;
@@ -994,6 +1595,10 @@ fn _deposit_obligation_collateral<'a>(
         msg!("Deposit reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if !deposit_reserve.is_active() {
+        msg!("Deposit reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
     if deposit_reserve.config.loan_to_value_ratio == 0 {
         msg!("Deposit reserve has collateral disabled for borrowing");
         return Err(LendingError::ReserveCollateralDisabled.into());
@@ -1008,18 +1613,19 @@ fn _deposit_obligation_collateral<'a>(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
-        return Err(LendingError::InvalidObligationOwner.into());
-    }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
-        return Err(LendingError::InvalidSigner.into());
-    }
+    assert_obligation_authority(
+        &obligation,
+        obligation_owner_info,
+        OBLIGATION_DELEGATE_DEPOSIT,
+        None,
+    )?;
 
-    obligation
-        .find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?
-        .deposit(collateral_amount)?;
+    let collateral = obligation.find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?;
+    collateral.deposit(collateral_amount)?;
+    if deposit_reserve.exceeds_obligation_deposit_limit(collateral.deposited_amount)? {
+        msg!("Cannot deposit collateral above the obligation deposit limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
     obligation.last_update.mark_stale();
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
     spl_token_transfer(TokenTransferParams {
@@ -1190,14 +1796,12 @@ fn _withdraw_obligation_collateral<'a>(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
-        return Err(LendingError::InvalidObligationOwner.into());
-    }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
-        return Err(LendingError::InvalidSigner.into());
-    }
+    assert_obligation_authority(
+        &obligation,
+        obligation_owner_info,
+        OBLIGATION_DELEGATE_WITHDRAW,
+        Some(destination_collateral_info),
+    )?;
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
@@ -1284,25 +1888,29 @@ fn _withdraw_obligation_collateral<'a>(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_borrow_obligation_liquidity(
+fn process_swap_obligation_collateral(
     program_id: &Pubkey,
-    liquidity_amount: u64,
+    deposit_collateral_amount: u64,
+    withdraw_collateral_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Liquidity amount provided cannot be zero");
+    if deposit_collateral_amount == 0 || withdraw_collateral_amount == 0 {
+        msg!("Collateral amounts provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
     let account_info_iter = &mut accounts.iter();
-    let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_info = next_account_info(account_info_iter)?;
-    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let source_collateral_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
@@ -1316,17 +1924,200 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
-    if borrow_reserve_info.owner != program_id {
-        msg!("Borrow reserve provided is not owned by the lending program");
+    if deposit_reserve_info.key == withdraw_reserve_info.key {
+        msg!("Deposit reserve and withdraw reserve provided must be different");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+    if deposit_reserve_info.owner != program_id {
+        msg!("Deposit reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &borrow_reserve.lending_market != lending_market_info.key {
-        msg!("Borrow reserve lending market does not match the lending market provided");
+    if &deposit_reserve.lending_market != lending_market_info.key {
+        msg!("Deposit reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
-        msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
+    if &deposit_reserve.collateral.supply_pubkey == source_collateral_info.key {
+        msg!("Deposit reserve collateral supply cannot be used as the source collateral provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &deposit_reserve.collateral.supply_pubkey != deposit_reserve_collateral_supply_info.key {
+        msg!("Deposit reserve collateral supply does not match the deposit reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if deposit_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Deposit reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+    if !deposit_reserve.is_active() {
+        msg!("Deposit reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+    if deposit_reserve.config.loan_to_value_ratio == 0 {
+        msg!("Deposit reserve has collateral disabled for borrowing");
+        return Err(LendingError::ReserveCollateralDisabled.into());
+    }
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if withdraw_reserve_info.owner != program_id {
+        msg!("Withdraw reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &withdraw_reserve.lending_market != lending_market_info.key {
+        msg!("Withdraw reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
+        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if withdraw_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    assert_obligation_authority(
+        &obligation,
+        obligation_owner_info,
+        OBLIGATION_DELEGATE_DEPOSIT | OBLIGATION_DELEGATE_WITHDRAW,
+        Some(destination_collateral_info),
+    )?;
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    let (collateral, collateral_index) =
+        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+    if collateral.deposited_amount == 0 {
+        msg!("Collateral deposited amount is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let withdraw_amount = withdraw_collateral_amount.min(collateral.deposited_amount);
+    if !obligation.borrows.is_empty() {
+        let withdraw_pct = Decimal::from(withdraw_amount).try_div(collateral.deposited_amount)?;
+        let withdraw_value = collateral.market_value.try_mul(withdraw_pct)?;
+        let deposit_value = deposit_reserve.collateral_market_value(deposit_collateral_amount)?;
+
+        // only the obligation after both legs of the swap must be healthy
+        let allowed_borrow_value = obligation
+            .allowed_borrow_value
+            .try_add(deposit_value.try_mul(Rate::from_percent(
+                deposit_reserve.config.loan_to_value_ratio,
+            ))?)?
+            .try_sub(withdraw_value.try_mul(Rate::from_percent(
+                withdraw_reserve.config.loan_to_value_ratio,
+            ))?)?;
+        if obligation.borrowed_value > allowed_borrow_value {
+            msg!("Withdraw value cannot exceed the value of the collateral deposited in the swap and the remaining borrowing power");
+            return Err(LendingError::WithdrawTooLarge.into());
+        }
+    }
+
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    let collateral = obligation.find_or_add_collateral_to_deposits(*deposit_reserve_info.key)?;
+    collateral.deposit(deposit_collateral_amount)?;
+    if deposit_reserve.exceeds_obligation_deposit_limit(collateral.deposited_amount)? {
+        msg!("Cannot deposit collateral above the obligation deposit limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_collateral_info.clone(),
+        destination: deposit_reserve_collateral_supply_info.clone(),
+        amount: deposit_collateral_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: withdraw_reserve_collateral_supply_info.clone(),
+        destination: destination_collateral_info.clone(),
+        amount: withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_borrow_obligation_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    min_receive_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Borrow reserve liquidity supply must be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
     if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
@@ -1343,6 +2134,10 @@ fn process_borrow_obligation_liquidity(
         msg!("Borrow reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if !borrow_reserve.is_active() {
+        msg!("Borrow reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
     if liquidity_amount != u64::MAX
         && Decimal::from(liquidity_amount)
             .try_add(borrow_reserve.liquidity.borrowed_amount_wads)?
@@ -1352,6 +2147,16 @@ fn process_borrow_obligation_liquidity(
         msg!("Cannot borrow above the borrow limit");
         return Err(LendingError::InvalidAmount.into());
     }
+    if liquidity_amount != u64::MAX
+        && borrow_reserve.config.borrow_limit_value != 0
+        && borrow_reserve.market_value(
+            Decimal::from(liquidity_amount)
+                .try_add(borrow_reserve.liquidity.borrowed_amount_wads)?,
+        )? > Decimal::from(borrow_reserve.config.borrow_limit_value)
+    {
+        msg!("Cannot borrow above the borrow value limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
@@ -1362,14 +2167,12 @@ fn process_borrow_obligation_liquidity(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &obligation.owner != obligation_owner_info.key {
-        msg!("Obligation owner does not match the obligation owner provided");
-        return Err(LendingError::InvalidObligationOwner.into());
-    }
-    if !obligation_owner_info.is_signer {
-        msg!("Obligation owner provided must be a signer");
-        return Err(LendingError::InvalidSigner.into());
-    }
+    assert_obligation_authority(
+        &obligation,
+        obligation_owner_info,
+        OBLIGATION_DELEGATE_BORROW,
+        Some(destination_liquidity_info),
+    )?;
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
@@ -1402,9 +2205,22 @@ fn process_borrow_obligation_liquidity(
         return Err(LendingError::BorrowTooLarge.into());
     }
 
-    let remaining_reserve_capacity = Decimal::from(borrow_reserve.config.borrow_limit)
-        .try_sub(borrow_reserve.liquidity.borrowed_amount_wads)
-        .unwrap_or_else(|_| Decimal::zero());
+    let obligation_borrowed_amount = obligation
+        .borrows
+        .iter()
+        .find(|liquidity| liquidity.borrow_reserve == *borrow_reserve_info.key)
+        .map_or_else(Decimal::zero, |liquidity| liquidity.borrowed_amount_wads);
+    let remaining_obligation_capacity =
+        borrow_reserve.remaining_obligation_borrow_capacity(obligation_borrowed_amount);
+    if liquidity_amount != u64::MAX
+        && Decimal::from(liquidity_amount) > remaining_obligation_capacity
+    {
+        msg!("Cannot borrow above the obligation borrow limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    let remaining_reserve_capacity = borrow_reserve
+        .remaining_borrow_capacity()?
+        .min(remaining_obligation_capacity);
 
     let CalculateBorrowResult {
         borrow_amount,
@@ -1421,9 +2237,21 @@ fn process_borrow_obligation_liquidity(
         msg!("Borrow amount is too small to receive liquidity after fees");
         return Err(LendingError::BorrowTooSmall.into());
     }
+    if receive_amount < min_receive_amount {
+        msg!(
+            "Borrow receive amount {} is less than the minimum {}",
+            receive_amount,
+            min_receive_amount
+        );
+        return Err(LendingError::ExceededSlippage.into());
+    }
 
     let cumulative_borrow_rate_wads = borrow_reserve.liquidity.cumulative_borrow_rate_wads;
 
+    let outflow_value = borrow_reserve.market_value(borrow_amount)?;
+    borrow_reserve
+        .rate_limiter
+        .update(clock.slot, outflow_value)?;
     borrow_reserve.liquidity.borrow(borrow_amount)?;
     borrow_reserve.last_update.mark_stale();
     Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
@@ -1433,10 +2261,16 @@ fn process_borrow_obligation_liquidity(
 
     obligation_liquidity.borrow(borrow_amount)?;
     obligation.last_update.mark_stale();
+    let host_fee_receiver_info = obligation_host_fee_receiver(
+        program_id,
+        &obligation,
+        borrow_reserve_info,
+        next_account_info(account_info_iter).ok(),
+    )?;
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     let mut owner_fee = borrow_fee;
-    if let Ok(host_fee_receiver_info) = next_account_info(account_info_iter) {
+    if let Some(host_fee_receiver_info) = host_fee_receiver_info {
         if host_fee > 0 {
             owner_fee = owner_fee
                 .checked_sub(host_fee)
@@ -1579,66 +2413,33 @@ fn process_repay_obligation_liquidity(
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_liquidate_obligation(
+fn process_swap_obligation_debt(
     program_id: &Pubkey,
-    liquidity_amount: u64,
+    repay_liquidity_amount: u64,
+    borrow_liquidity_amount: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Liquidity amount provided cannot be zero");
+    if repay_liquidity_amount == 0 || borrow_liquidity_amount == 0 {
+        msg!("Liquidity amounts provided cannot be zero");
         return Err(LendingError::InvalidAmount.into());
     }
 
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_collateral_info = next_account_info(account_info_iter)?;
-    let repay_reserve_info = next_account_info(account_info_iter)?;
     let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
     let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
 
-    _liquidate_obligation(
-        program_id,
-        liquidity_amount,
-        source_liquidity_info,
-        destination_collateral_info,
-        repay_reserve_info,
-        repay_reserve_liquidity_supply_info,
-        withdraw_reserve_info,
-        withdraw_reserve_collateral_supply_info,
-        obligation_info,
-        lending_market_info,
-        lending_market_authority_info,
-        user_transfer_authority_info,
-        clock,
-        token_program_id,
-    )?;
-    Ok(())
-}
-
-#[allow(clippy::too_many_arguments)]
-fn _liquidate_obligation<'a>(
-    program_id: &Pubkey,
-    liquidity_amount: u64,
-    source_liquidity_info: &AccountInfo<'a>,
-    destination_collateral_info: &AccountInfo<'a>,
-    repay_reserve_info: &AccountInfo<'a>,
-    repay_reserve_liquidity_supply_info: &AccountInfo<'a>,
-    withdraw_reserve_info: &AccountInfo<'a>,
-    withdraw_reserve_collateral_supply_info: &AccountInfo<'a>,
-    obligation_info: &AccountInfo<'a>,
-    lending_market_info: &AccountInfo<'a>,
-    lending_market_authority_info: &AccountInfo<'a>,
-    user_transfer_authority_info: &AccountInfo<'a>,
-    clock: &Clock,
-    token_program_id: &AccountInfo<'a>,
-) -> Result<u64, ProgramError> {
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
         msg!("Lending market provided is not owned by the lending program");
@@ -1649,6 +2450,11 @@ fn _liquidate_obligation<'a>(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
+    if repay_reserve_info.key == borrow_reserve_info.key {
+        msg!("Repay reserve and borrow reserve provided must be different");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
     let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
     if repay_reserve_info.owner != program_id {
         msg!("Repay reserve provided is not owned by the lending program");
@@ -1658,18 +2464,12 @@ fn _liquidate_obligation<'a>(
         msg!("Repay reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
-        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
     if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
         msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
-        msg!(
-            "Repay reserve collateral supply cannot be used as the destination collateral provided"
-        );
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
     if repay_reserve.last_update.is_stale(clock.slot)? {
@@ -1677,31 +2477,56 @@ fn _liquidate_obligation<'a>(
         return Err(LendingError::ReserveStale.into());
     }
 
-    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
-    if withdraw_reserve_info.owner != program_id {
-        msg!("Withdraw reserve provided is not owned by the lending program");
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &withdraw_reserve.lending_market != lending_market_info.key {
-        msg!("Withdraw reserve lending market does not match the lending market provided");
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
-        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+    if &borrow_reserve.liquidity.supply_pubkey != borrow_reserve_liquidity_supply_info.key {
+        msg!("Borrow reserve liquidity supply does not match the borrow reserve liquidity supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
-        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
+    if &borrow_reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!(
+            "Borrow reserve liquidity supply cannot be used as the destination liquidity provided"
+        );
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
-        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+    if &borrow_reserve.config.fee_receiver != borrow_reserve_liquidity_fee_receiver_info.key {
+        msg!("Borrow reserve liquidity fee receiver does not match the borrow reserve liquidity fee receiver provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if withdraw_reserve.last_update.is_stale(clock.slot)? {
-        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+    if borrow_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Borrow reserve is stale and must be refreshed in the current slot");
         return Err(LendingError::ReserveStale.into());
     }
+    if !borrow_reserve.is_active() {
+        msg!("Borrow reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+    if borrow_liquidity_amount != u64::MAX
+        && Decimal::from(borrow_liquidity_amount)
+            .try_add(borrow_reserve.liquidity.borrowed_amount_wads)?
+            .try_floor_u64()?
+            > borrow_reserve.config.borrow_limit
+    {
+        msg!("Cannot borrow above the borrow limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if borrow_liquidity_amount != u64::MAX
+        && borrow_reserve.config.borrow_limit_value != 0
+        && borrow_reserve.market_value(
+            Decimal::from(borrow_liquidity_amount)
+                .try_add(borrow_reserve.liquidity.borrowed_amount_wads)?,
+        )? > Decimal::from(borrow_reserve.config.borrow_limit_value)
+    {
+        msg!("Cannot borrow above the borrow value limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
 
     let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
     if obligation_info.owner != program_id {
@@ -1712,37 +2537,24 @@ fn _liquidate_obligation<'a>(
         msg!("Obligation lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    assert_obligation_authority(
+        &obligation,
+        obligation_owner_info,
+        OBLIGATION_DELEGATE_BORROW,
+        Some(destination_liquidity_info),
+    )?;
     if obligation.last_update.is_stale(clock.slot)? {
         msg!("Obligation is stale and must be refreshed in the current slot");
         return Err(LendingError::ObligationStale.into());
     }
-    if obligation.deposited_value == Decimal::zero() {
-        msg!("Obligation deposited value is zero");
-        return Err(LendingError::ObligationDepositsZero.into());
-    }
-    if obligation.borrowed_value == Decimal::zero() {
-        msg!("Obligation borrowed value is zero");
-        return Err(LendingError::ObligationBorrowsZero.into());
-    }
-    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
-        msg!("Obligation is healthy and cannot be liquidated");
-        return Err(LendingError::ObligationHealthy.into());
-    }
 
     let (liquidity, liquidity_index) =
         obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
-    if liquidity.market_value == Decimal::zero() {
-        msg!("Obligation borrow value is zero");
+    if liquidity.borrowed_amount_wads == Decimal::zero() {
+        msg!("Liquidity borrowed amount is zero");
         return Err(LendingError::ObligationLiquidityEmpty.into());
     }
 
-    let (collateral, collateral_index) =
-        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
-    if collateral.market_value == Decimal::zero() {
-        msg!("Obligation deposit value is zero");
-        return Err(LendingError::ObligationCollateralEmpty.into());
-    }
-
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
         &[lending_market.bump_seed],
@@ -1756,33 +2568,90 @@ fn _liquidate_obligation<'a>(
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let CalculateLiquidationResult {
+    let CalculateRepayResult {
         settle_amount,
         repay_amount,
-        withdraw_amount,
-    } = withdraw_reserve.calculate_liquidation(
-        liquidity_amount,
-        &obligation,
-        liquidity,
-        collateral,
-    )?;
+    } = repay_reserve.calculate_repay(repay_liquidity_amount, liquidity.borrowed_amount_wads)?;
 
     if repay_amount == 0 {
-        msg!("Liquidation is too small to transfer liquidity");
-        return Err(LendingError::LiquidationTooSmall.into());
+        msg!("Repay amount is too small to transfer liquidity");
+        return Err(LendingError::RepayTooSmall.into());
     }
-    if withdraw_amount == 0 {
-        msg!("Liquidation is too small to receive collateral");
-        return Err(LendingError::LiquidationTooSmall.into());
+
+    // only the obligation after both legs of the swap must be healthy
+    let repay_value = repay_reserve.market_value(settle_amount)?;
+    let remaining_borrow_value = obligation
+        .allowed_borrow_value
+        .try_sub(
+            obligation
+                .borrowed_value
+                .try_sub(repay_value)
+                .unwrap_or_else(|_| Decimal::zero()),
+        )
+        .unwrap_or_else(|_| Decimal::zero());
+    if remaining_borrow_value == Decimal::zero() {
+        msg!("Remaining borrow value is zero");
+        return Err(LendingError::BorrowTooLarge.into());
+    }
+
+    let obligation_borrowed_amount = obligation
+        .borrows
+        .iter()
+        .find(|liquidity| liquidity.borrow_reserve == *borrow_reserve_info.key)
+        .map_or_else(Decimal::zero, |liquidity| liquidity.borrowed_amount_wads);
+    let remaining_obligation_capacity =
+        borrow_reserve.remaining_obligation_borrow_capacity(obligation_borrowed_amount);
+    if borrow_liquidity_amount != u64::MAX
+        && Decimal::from(borrow_liquidity_amount) > remaining_obligation_capacity
+    {
+        msg!("Cannot borrow above the obligation borrow limit");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    let remaining_reserve_capacity = borrow_reserve
+        .remaining_borrow_capacity()?
+        .min(remaining_obligation_capacity);
+
+    let CalculateBorrowResult {
+        borrow_amount,
+        receive_amount,
+        borrow_fee,
+        host_fee,
+    } = borrow_reserve.calculate_borrow(
+        borrow_liquidity_amount,
+        remaining_borrow_value,
+        remaining_reserve_capacity,
+    )?;
+
+    if receive_amount == 0 {
+        msg!("Borrow amount is too small to receive liquidity after fees");
+        return Err(LendingError::BorrowTooSmall.into());
     }
 
     repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
     repay_reserve.last_update.mark_stale();
     Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
 
+    let cumulative_borrow_rate_wads = borrow_reserve.liquidity.cumulative_borrow_rate_wads;
+
+    let outflow_value = borrow_reserve.market_value(borrow_amount)?;
+    borrow_reserve
+        .rate_limiter
+        .update(clock.slot, outflow_value)?;
+    borrow_reserve.liquidity.borrow(borrow_amount)?;
+    borrow_reserve.last_update.mark_stale();
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
     obligation.repay(settle_amount, liquidity_index)?;
-    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation
+        .find_or_add_liquidity_to_borrows(*borrow_reserve_info.key, cumulative_borrow_rate_wads)?
+        .borrow(borrow_amount)?;
     obligation.last_update.mark_stale();
+    let host_fee_receiver_info = obligation_host_fee_receiver(
+        program_id,
+        &obligation,
+        borrow_reserve_info,
+        next_account_info(account_info_iter).ok(),
+    )?;
     Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
 
     spl_token_transfer(TokenTransferParams {
@@ -1794,22 +2663,51 @@ fn _liquidate_obligation<'a>(
         token_program: token_program_id.clone(),
     })?;
 
+    let mut owner_fee = borrow_fee;
+    if let Some(host_fee_receiver_info) = host_fee_receiver_info {
+        if host_fee > 0 {
+            owner_fee = owner_fee
+                .checked_sub(host_fee)
+                .ok_or(LendingError::MathOverflow)?;
+
+            spl_token_transfer(TokenTransferParams {
+                source: borrow_reserve_liquidity_supply_info.clone(),
+                destination: host_fee_receiver_info.clone(),
+                amount: host_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: borrow_reserve_liquidity_supply_info.clone(),
+            destination: borrow_reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
     spl_token_transfer(TokenTransferParams {
-        source: withdraw_reserve_collateral_supply_info.clone(),
-        destination: destination_collateral_info.clone(),
-        amount: withdraw_amount,
+        source: borrow_reserve_liquidity_supply_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: receive_amount,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
     })?;
 
-    Ok(withdraw_amount)
+    Ok(())
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_liquidate_obligation_and_redeem_reserve_collateral(
+fn process_liquidate_obligation(
     program_id: &Pubkey,
     liquidity_amount: u64,
+    min_collateral_received: u64,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     if liquidity_amount == 0 {
@@ -1820,22 +2718,18 @@ fn process_liquidate_obligation_and_redeem_reserve_collateral(
     let account_info_iter = &mut accounts.iter();
     let source_liquidity_info = next_account_info(account_info_iter)?;
     let destination_collateral_info = next_account_info(account_info_iter)?;
-    let destination_liquidity_info = next_account_info(account_info_iter)?;
     let repay_reserve_info = next_account_info(account_info_iter)?;
     let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
     let withdraw_reserve_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
     let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
-    let withdraw_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
     let token_program_id = next_account_info(account_info_iter)?;
-    let clock = &Clock::get()?;
 
-    let withdraw_collateral_amount = _liquidate_obligation(
+    let withdraw_amount = _liquidate_obligation(
         program_id,
         liquidity_amount,
         source_liquidity_info,
@@ -1851,71 +2745,37 @@ fn process_liquidate_obligation_and_redeem_reserve_collateral(
         clock,
         token_program_id,
     )?;
-
-    _refresh_reserve_interest(program_id, withdraw_reserve_info, clock)?;
-    let withdraw_liquidity_amount = _redeem_reserve_collateral(
-        program_id,
-        withdraw_collateral_amount,
-        destination_collateral_info,
-        destination_liquidity_info,
-        withdraw_reserve_info,
-        withdraw_reserve_collateral_mint_info,
-        withdraw_reserve_liquidity_supply_info,
-        lending_market_info,
-        lending_market_authority_info,
-        user_transfer_authority_info,
-        clock,
-        token_program_id,
-    )?;
-    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
-    if &withdraw_reserve.config.fee_receiver != withdraw_reserve_liquidity_fee_receiver_info.key {
-        msg!("Withdraw reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
-        return Err(LendingError::InvalidAccountInput.into());
+    if withdraw_amount < min_collateral_received {
+        msg!(
+            "Liquidation collateral amount {} is less than the minimum {}",
+            withdraw_amount,
+            min_collateral_received
+        );
+        return Err(LendingError::ExceededSlippage.into());
     }
-    let protocol_fee =
-        withdraw_reserve.calculate_protocol_liquidation_fee(withdraw_liquidity_amount)?;
-
-    spl_token_transfer(TokenTransferParams {
-        source: destination_liquidity_info.clone(),
-        destination: withdraw_reserve_liquidity_fee_receiver_info.clone(),
-        amount: protocol_fee,
-        authority: user_transfer_authority_info.clone(),
-        authority_signer_seeds: &[],
-        token_program: token_program_id.clone(),
-    })?;
-
     Ok(())
 }
 
-#[inline(never)] // avoid stack frame limit
-fn process_flash_loan(
+#[allow(clippy::too_many_arguments)]
+fn _liquidate_obligation<'a>(
     program_id: &Pubkey,
     liquidity_amount: u64,
-    accounts: &[AccountInfo],
-) -> ProgramResult {
-    if liquidity_amount == 0 {
-        msg!("Liquidity amount provided cannot be zero");
-        return Err(LendingError::InvalidAmount.into());
-    }
-
-    let account_info_iter = &mut accounts.iter();
-    let source_liquidity_info = next_account_info(account_info_iter)?;
-    let destination_liquidity_info = next_account_info(account_info_iter)?;
-    let reserve_info = next_account_info(account_info_iter)?;
-    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
-    let host_fee_receiver_info = next_account_info(account_info_iter)?;
-    let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let token_program_id = next_account_info(account_info_iter)?;
-    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
-
-    if program_id == flash_loan_receiver_program_id.key {
-        msg!("Lending program cannot be used as the flash loan receiver program provided");
-        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
-    }
-
+    source_liquidity_info: &AccountInfo<'a>,
+    destination_collateral_info: &AccountInfo<'a>,
+    repay_reserve_info: &AccountInfo<'a>,
+    repay_reserve_liquidity_supply_info: &AccountInfo<'a>,
+    withdraw_reserve_info: &AccountInfo<'a>,
+    withdraw_reserve_collateral_supply_info: &AccountInfo<'a>,
+    obligation_info: &AccountInfo<'a>,
+    lending_market_info: &AccountInfo<'a>,
+    lending_market_authority_info: &AccountInfo<'a>,
+    user_transfer_authority_info: &AccountInfo<'a>,
+    clock: &Clock,
+    token_program_id: &AccountInfo<'a>,
+) -> Result<u64, ProgramError> {
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
     if &lending_market.token_program_id != token_program_id.key {
@@ -1923,140 +2783,2033 @@ fn process_flash_loan(
         return Err(LendingError::InvalidTokenProgram.into());
     }
 
-    let authority_signer_seeds = &[
-        lending_market_info.key.as_ref(),
-        &[lending_market.bump_seed],
-    ];
-    let lending_market_authority_pubkey =
-        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
-    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+    let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    if repay_reserve_info.owner != program_id {
+        msg!("Repay reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &repay_reserve.lending_market != lending_market_info.key {
+        msg!("Repay reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+        msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
         msg!(
-            "Derived lending market authority does not match the lending market authority provided"
+            "Repay reserve collateral supply cannot be used as the destination collateral provided"
         );
-        return Err(LendingError::InvalidMarketAuthority.into());
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if repay_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Repay reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
     }
 
-    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
-    if reserve_info.owner != program_id {
-        msg!("Reserve provided is not owned by the lending program");
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if withdraw_reserve_info.owner != program_id {
+        msg!("Withdraw reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-    if &reserve.lending_market != lending_market_info.key {
-        msg!("Invalid reserve lending market account");
+    if &withdraw_reserve.lending_market != lending_market_info.key {
+        msg!("Withdraw reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
-        msg!("Reserve liquidity supply must be used as the source liquidity provided");
+    if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key {
+        msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
-        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+    if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Withdraw reserve liquidity supply cannot be used as the source liquidity provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
+    if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+        msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if withdraw_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
 
-    // @FIXME: if u64::MAX is flash loaned, fees should be inclusive as with ordinary borrows
-    let flash_loan_amount = if liquidity_amount == u64::MAX {
-        reserve.liquidity.available_amount
-    } else {
-        liquidity_amount
-    };
-
-    let flash_loan_amount_decimal = Decimal::from(flash_loan_amount);
-    let (origination_fee, host_fee) = reserve
-        .config
-        .fees
-        .calculate_flash_loan_fees(flash_loan_amount_decimal)?;
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+    let LiquidationPreview {
+        settle_amount,
+        repay_amount,
+        withdraw_amount,
+        ..
+    } = preview_liquidation(
+        &obligation,
+        repay_reserve_info.key,
+        withdraw_reserve_info.key,
+        &withdraw_reserve,
+        liquidity_amount,
+    )?;
+    let (_, liquidity_index) = obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+    let (_, collateral_index) =
+        obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+    repay_reserve.last_update.mark_stale();
+    Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(settle_amount, liquidity_index)?;
+    obligation.withdraw(withdraw_amount, collateral_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: repay_reserve_liquidity_supply_info.clone(),
+        amount: repay_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: withdraw_reserve_collateral_supply_info.clone(),
+        destination: destination_collateral_info.clone(),
+        amount: withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(withdraw_amount)
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_liquidate_obligation_and_redeem_reserve_collateral(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
+
+    let withdraw_collateral_amount = _liquidate_obligation(
+        program_id,
+        liquidity_amount,
+        source_liquidity_info,
+        destination_collateral_info,
+        repay_reserve_info,
+        repay_reserve_liquidity_supply_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_supply_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+    )?;
+
+    _refresh_reserve_interest(program_id, withdraw_reserve_info, clock)?;
+    let withdraw_liquidity_amount = _redeem_reserve_collateral(
+        program_id,
+        withdraw_collateral_amount,
+        destination_collateral_info,
+        destination_liquidity_info,
+        withdraw_reserve_info,
+        withdraw_reserve_collateral_mint_info,
+        withdraw_reserve_liquidity_supply_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+        false,
+    )?;
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    if &withdraw_reserve.config.fee_receiver != withdraw_reserve_liquidity_fee_receiver_info.key {
+        msg!("Withdraw reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    let protocol_fee =
+        withdraw_reserve.calculate_protocol_liquidation_fee(withdraw_liquidity_amount)?;
+    spl_token_transfer(TokenTransferParams {
+        source: destination_liquidity_info.clone(),
+        destination: withdraw_reserve_liquidity_fee_receiver_info.clone(),
+        amount: protocol_fee,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_liquidate_obligation_batch(
+    program_id: &Pubkey,
+    liquidity_amounts: &[u64],
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amounts.is_empty() {
+        msg!("At least one liquidity amount must be provided");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if liquidity_amounts.contains(&0) {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    // the close factor applies to the obligation as it was refreshed, not to each liquidation
+    let mut remaining_liquidation_value = obligation.max_liquidation_value()?;
+    // reserves repaid earlier in the batch are marked stale but remain priced for this slot
+    let mut repaid_reserves: Vec<Pubkey> = Vec::with_capacity(liquidity_amounts.len());
+
+    for liquidity_amount in liquidity_amounts {
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let destination_collateral_info = next_account_info(account_info_iter)?;
+        let repay_reserve_info = next_account_info(account_info_iter)?;
+        let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+        let withdraw_reserve_info = next_account_info(account_info_iter)?;
+        let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+
+        let mut repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+        if repay_reserve_info.owner != program_id {
+            msg!("Repay reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if &repay_reserve.lending_market != lending_market_info.key {
+            msg!("Repay reserve lending market does not match the lending market provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &repay_reserve.liquidity.supply_pubkey != repay_reserve_liquidity_supply_info.key {
+            msg!("Repay reserve liquidity supply does not match the repay reserve liquidity supply provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &repay_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+            msg!("Repay reserve liquidity supply cannot be used as the source liquidity provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &repay_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+            msg!("Repay reserve collateral supply cannot be used as the destination collateral provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if !repaid_reserves.contains(repay_reserve_info.key)
+            && repay_reserve.last_update.is_stale(clock.slot)?
+        {
+            msg!("Repay reserve is stale and must be refreshed in the current slot");
+            return Err(LendingError::ReserveStale.into());
+        }
+
+        let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+        if withdraw_reserve_info.owner != program_id {
+            msg!("Withdraw reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if &withdraw_reserve.lending_market != lending_market_info.key {
+            msg!("Withdraw reserve lending market does not match the lending market provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &withdraw_reserve.collateral.supply_pubkey != withdraw_reserve_collateral_supply_info.key
+        {
+            msg!("Withdraw reserve collateral supply does not match the withdraw reserve collateral supply provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &withdraw_reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+            msg!(
+                "Withdraw reserve liquidity supply cannot be used as the source liquidity provided"
+            );
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &withdraw_reserve.collateral.supply_pubkey == destination_collateral_info.key {
+            msg!("Withdraw reserve collateral supply cannot be used as the destination collateral provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if !repaid_reserves.contains(withdraw_reserve_info.key)
+            && withdraw_reserve.last_update.is_stale(clock.slot)?
+        {
+            msg!("Withdraw reserve is stale and must be refreshed in the current slot");
+            return Err(LendingError::ReserveStale.into());
+        }
+
+        if remaining_liquidation_value == Decimal::zero() {
+            msg!("Liquidation batch has already reached the obligation close factor");
+            return Err(LendingError::LiquidationTooSmall.into());
+        }
+        let (liquidity, liquidity_index) =
+            obligation.find_liquidity_in_borrows(*repay_reserve_info.key)?;
+        // an empty borrow is rejected by the preview below
+        let remaining_liquidation_amount = if liquidity.market_value == Decimal::zero() {
+            *liquidity_amount
+        } else {
+            let remaining_liquidation_pct = remaining_liquidation_value
+                .min(liquidity.market_value)
+                .try_div(liquidity.market_value)?;
+            liquidity
+                .borrowed_amount_wads
+                .try_mul(remaining_liquidation_pct)?
+                .try_floor_u64()?
+        };
+
+        let LiquidationPreview {
+            settle_amount,
+            repay_amount,
+            withdraw_amount,
+            ..
+        } = preview_liquidation(
+            &obligation,
+            repay_reserve_info.key,
+            withdraw_reserve_info.key,
+            &withdraw_reserve,
+            (*liquidity_amount).min(remaining_liquidation_amount),
+        )?;
+
+        let (collateral, collateral_index) =
+            obligation.find_collateral_in_deposits(*withdraw_reserve_info.key)?;
+
+        // keep market values in step so later liquidations of the same borrow or deposit are
+        // priced against what is left of it
+        let settle_value = liquidity
+            .market_value
+            .try_mul(settle_amount)?
+            .try_div(liquidity.borrowed_amount_wads)?
+            .min(liquidity.market_value);
+        let withdraw_value = collateral
+            .market_value
+            .try_mul(withdraw_amount)?
+            .try_div(collateral.deposited_amount)?
+            .min(collateral.market_value);
+        remaining_liquidation_value =
+            remaining_liquidation_value.try_sub(settle_value.min(remaining_liquidation_value))?;
+
+        obligation.borrows[liquidity_index].market_value = obligation.borrows[liquidity_index]
+            .market_value
+            .try_sub(settle_value)?;
+        obligation.deposits[collateral_index].market_value = obligation.deposits[collateral_index]
+            .market_value
+            .try_sub(withdraw_value)?;
+        obligation.repay(settle_amount, liquidity_index)?;
+        obligation.withdraw(withdraw_amount, collateral_index)?;
+
+        repay_reserve.liquidity.repay(repay_amount, settle_amount)?;
+        repay_reserve.last_update.mark_stale();
+        Reserve::pack(repay_reserve, &mut repay_reserve_info.data.borrow_mut())?;
+        repaid_reserves.push(*repay_reserve_info.key);
+
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: repay_reserve_liquidity_supply_info.clone(),
+            amount: repay_amount,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+
+        spl_token_transfer(TokenTransferParams {
+            source: withdraw_reserve_collateral_supply_info.clone(),
+            destination: destination_collateral_info.clone(),
+            amount: withdraw_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_loan(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
+
+    if program_id == flash_loan_receiver_program_id.key {
+        msg!("Lending program cannot be used as the flash loan receiver program provided");
+        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Invalid reserve lending market account");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if !reserve.is_active() {
+        msg!("Reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+
+    let whitelisted_fee_wad = next_flash_loan_whitelist_fee_wad(
+        program_id,
+        lending_market_info,
+        flash_loan_receiver_program_id.key,
+        account_info_iter,
+    )?;
+
+    let CalculateFlashLoanResult {
+        flash_loan_amount,
+        origination_fee,
+        host_fee,
+    } = reserve.calculate_flash_loan(liquidity_amount, whitelisted_fee_wad)?;
+    if flash_loan_amount == 0 {
+        msg!("Flash loan amount is too small to transfer liquidity");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let flash_loan_amount_decimal = Decimal::from(flash_loan_amount);
+
+    let balance_before_flash_loan = Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+    let expected_balance_after_flash_loan = balance_before_flash_loan
+        .checked_add(origination_fee)
+        .ok_or(LendingError::MathOverflow)?;
+    let returned_amount_required = flash_loan_amount
+        .checked_add(origination_fee)
+        .ok_or(LendingError::MathOverflow)?;
+
+    let mut flash_loan_instruction_accounts = vec![
+        AccountMeta::new(*destination_liquidity_info.key, false),
+        AccountMeta::new(*source_liquidity_info.key, false),
+        AccountMeta::new_readonly(*token_program_id.key, false),
+    ];
+    let mut flash_loan_instruction_account_infos = vec![
+        destination_liquidity_info.clone(),
+        flash_loan_receiver_program_id.clone(),
+        source_liquidity_info.clone(),
+        token_program_id.clone(),
+    ];
+    for account_info in account_info_iter {
+        flash_loan_instruction_accounts.push(AccountMeta {
+            pubkey: *account_info.key,
+            is_signer: account_info.is_signer,
+            is_writable: account_info.is_writable,
+        });
+        flash_loan_instruction_account_infos.push(account_info.clone());
+    }
+
+    // flash loans don't require a fresh reserve, so the last refreshed price is used
+    let outflow_value = reserve.market_value(flash_loan_amount_decimal)?;
+    reserve
+        .rate_limiter
+        .update(Clock::get()?.slot, outflow_value)?;
+    reserve.liquidity.borrow(flash_loan_amount_decimal)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: flash_loan_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    const RECEIVE_FLASH_LOAN_INSTRUCTION_DATA_SIZE: usize = 9;
+    // @FIXME: don't use 0 to indicate a flash loan receiver instruction https://git.io/JGzz9
+    const RECEIVE_FLASH_LOAN_INSTRUCTION_TAG: u8 = 0u8;
+
+    let mut data = Vec::with_capacity(RECEIVE_FLASH_LOAN_INSTRUCTION_DATA_SIZE);
+    data.push(RECEIVE_FLASH_LOAN_INSTRUCTION_TAG);
+    data.extend_from_slice(&returned_amount_required.to_le_bytes());
+
+    invoke(
+        &Instruction {
+            program_id: *flash_loan_receiver_program_id.key,
+            accounts: flash_loan_instruction_accounts,
+            data,
+        },
+        &flash_loan_instruction_account_infos[..],
+    )?;
+
+    reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    reserve
+        .liquidity
+        .repay(flash_loan_amount, flash_loan_amount_decimal)?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    let actual_balance_after_flash_loan =
+        Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+    if actual_balance_after_flash_loan < expected_balance_after_flash_loan {
+        msg!("Insufficient reserve liquidity after flash loan");
+        return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
+    }
+
+    let mut owner_fee = origination_fee;
+    if host_fee > 0 {
+        owner_fee = owner_fee
+            .checked_sub(host_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: host_fee_receiver_info.clone(),
+            amount: host_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Accounts and amounts for a single loan within a flash loan batch
+struct BatchFlashLoanState<'a, 'b> {
+    source_liquidity_info: &'a AccountInfo<'b>,
+    reserve_info: &'a AccountInfo<'b>,
+    reserve_liquidity_fee_receiver_info: &'a AccountInfo<'b>,
+    host_fee_receiver_info: &'a AccountInfo<'b>,
+    flash_loan_amount: u64,
+    origination_fee: u64,
+    host_fee: u64,
+    expected_balance_after_flash_loan: u64,
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_loan_batch(
+    program_id: &Pubkey,
+    liquidity_amounts: &[u64],
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amounts.is_empty() {
+        msg!("At least one liquidity amount must be provided");
+        return Err(LendingError::InvalidAmount.into());
+    }
+    if liquidity_amounts.contains(&0) {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let flash_loan_receiver_program_id = next_account_info(account_info_iter)?;
+
+    if program_id == flash_loan_receiver_program_id.key {
+        msg!("Lending program cannot be used as the flash loan receiver program provided");
+        return Err(LendingError::InvalidFlashLoanReceiverProgram.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let whitelisted_fee_wad = next_flash_loan_whitelist_fee_wad(
+        program_id,
+        lending_market_info,
+        flash_loan_receiver_program_id.key,
+        account_info_iter,
+    )?;
+
+    let mut loans: Vec<BatchFlashLoanState> = Vec::with_capacity(liquidity_amounts.len());
+    let mut flash_loan_instruction_accounts = Vec::with_capacity(liquidity_amounts.len() * 2 + 1);
+    let mut flash_loan_instruction_account_infos =
+        Vec::with_capacity(liquidity_amounts.len() * 2 + 2);
+    let mut data = Vec::with_capacity(2 + liquidity_amounts.len() * 8);
+    // @FIXME: don't use a fixed tag to indicate a flash loan receiver instruction https://git.io/JGzz9
+    const RECEIVE_BATCH_FLASH_LOAN_INSTRUCTION_TAG: u8 = 1u8;
+    data.push(RECEIVE_BATCH_FLASH_LOAN_INSTRUCTION_TAG);
+    data.push(u8::try_from(liquidity_amounts.len()).map_err(|_| {
+        msg!("Flash loan batch cannot contain more than 255 loans");
+        LendingError::InvalidAmount
+    })?);
+
+    for liquidity_amount in liquidity_amounts {
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let destination_liquidity_info = next_account_info(account_info_iter)?;
+        let reserve_info = next_account_info(account_info_iter)?;
+        let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+        let host_fee_receiver_info = next_account_info(account_info_iter)?;
+
+        // each reserve supply balance is checked on its own after the receiver returns
+        if loans
+            .iter()
+            .any(|loan| loan.reserve_info.key == reserve_info.key)
+        {
+            msg!("Reserve cannot be flash loaned more than once in a batch");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+
+        let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+        if reserve_info.owner != program_id {
+            msg!("Reserve provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        if &reserve.lending_market != lending_market_info.key {
+            msg!("Reserve lending market does not match the lending market provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+            msg!("Reserve liquidity supply must be used as the source liquidity provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+            msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        if !reserve.is_active() {
+            msg!("Reserve is not active and only allows reducing positions");
+            return Err(LendingError::ReserveNotActive.into());
+        }
+
+        let CalculateFlashLoanResult {
+            flash_loan_amount,
+            origination_fee,
+            host_fee,
+        } = reserve.calculate_flash_loan(*liquidity_amount, whitelisted_fee_wad)?;
+        if flash_loan_amount == 0 {
+            msg!("Flash loan amount is too small to transfer liquidity");
+            return Err(LendingError::InvalidAmount.into());
+        }
+
+        let balance_before_flash_loan =
+            Account::unpack(&source_liquidity_info.data.borrow())?.amount;
+        let expected_balance_after_flash_loan = balance_before_flash_loan
+            .checked_add(origination_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        let returned_amount_required = flash_loan_amount
+            .checked_add(origination_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        data.extend_from_slice(&returned_amount_required.to_le_bytes());
+
+        flash_loan_instruction_accounts
+            .push(AccountMeta::new(*destination_liquidity_info.key, false));
+        flash_loan_instruction_accounts.push(AccountMeta::new(*source_liquidity_info.key, false));
+        flash_loan_instruction_account_infos.push(destination_liquidity_info.clone());
+        flash_loan_instruction_account_infos.push(source_liquidity_info.clone());
+
+        // flash loans don't require a fresh reserve, so the last refreshed price is used
+        let outflow_value = reserve.market_value(Decimal::from(flash_loan_amount))?;
+        reserve
+            .rate_limiter
+            .update(Clock::get()?.slot, outflow_value)?;
+        reserve.liquidity.borrow(Decimal::from(flash_loan_amount))?;
+        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: destination_liquidity_info.clone(),
+            amount: flash_loan_amount,
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+
+        loans.push(BatchFlashLoanState {
+            source_liquidity_info,
+            reserve_info,
+            reserve_liquidity_fee_receiver_info,
+            host_fee_receiver_info,
+            flash_loan_amount,
+            origination_fee,
+            host_fee,
+            expected_balance_after_flash_loan,
+        });
+    }
+
+    flash_loan_instruction_accounts.push(AccountMeta::new_readonly(*token_program_id.key, false));
+    flash_loan_instruction_account_infos.push(flash_loan_receiver_program_id.clone());
+    flash_loan_instruction_account_infos.push(token_program_id.clone());
+    for account_info in account_info_iter {
+        flash_loan_instruction_accounts.push(AccountMeta {
+            pubkey: *account_info.key,
+            is_signer: account_info.is_signer,
+            is_writable: account_info.is_writable,
+        });
+        flash_loan_instruction_account_infos.push(account_info.clone());
+    }
+
+    invoke(
+        &Instruction {
+            program_id: *flash_loan_receiver_program_id.key,
+            accounts: flash_loan_instruction_accounts,
+            data,
+        },
+        &flash_loan_instruction_account_infos[..],
+    )?;
+
+    for loan in loans {
+        let mut reserve = Reserve::unpack(&loan.reserve_info.data.borrow())?;
+        reserve.liquidity.repay(
+            loan.flash_loan_amount,
+            Decimal::from(loan.flash_loan_amount),
+        )?;
+        Reserve::pack(reserve, &mut loan.reserve_info.data.borrow_mut())?;
+
+        let actual_balance_after_flash_loan =
+            Account::unpack(&loan.source_liquidity_info.data.borrow())?.amount;
+        if actual_balance_after_flash_loan < loan.expected_balance_after_flash_loan {
+            msg!("Insufficient reserve liquidity after flash loan");
+            return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
+        }
+
+        let mut owner_fee = loan.origination_fee;
+        if loan.host_fee > 0 {
+            owner_fee = owner_fee
+                .checked_sub(loan.host_fee)
+                .ok_or(LendingError::MathOverflow)?;
+            spl_token_transfer(TokenTransferParams {
+                source: loan.source_liquidity_info.clone(),
+                destination: loan.host_fee_receiver_info.clone(),
+                amount: loan.host_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+
+        if owner_fee > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: loan.source_liquidity_info.clone(),
+                destination: loan.reserve_liquidity_fee_receiver_info.clone(),
+                amount: owner_fee,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Consume the optional flash loan whitelist account which may directly follow the flash loan
+/// receiver program id, returning the whitelisted flash loan fee for the receiver program
+fn next_flash_loan_whitelist_fee_wad(
+    program_id: &Pubkey,
+    lending_market_info: &AccountInfo,
+    flash_loan_receiver_program_id: &Pubkey,
+    account_info_iter: &mut std::slice::Iter<AccountInfo>,
+) -> Result<Option<u64>, ProgramError> {
+    let flash_loan_whitelist_info = match account_info_iter.as_slice().first() {
+        Some(account_info)
+            if account_info.owner == program_id
+                && account_info.data_len() == FlashLoanWhitelist::LEN =>
+        {
+            next_account_info(account_info_iter)?
+        }
+        _ => return Ok(None),
+    };
+
+    let flash_loan_whitelist =
+        FlashLoanWhitelist::unpack(&flash_loan_whitelist_info.data.borrow())?;
+    if &flash_loan_whitelist.lending_market != lending_market_info.key {
+        msg!("Flash loan whitelist lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    Ok(flash_loan_whitelist.find_flash_loan_fee_wad(flash_loan_receiver_program_id))
+}
+
+fn process_add_flash_loan_whitelist_entry(
+    program_id: &Pubkey,
+    receiver_program_id: Pubkey,
+    flash_loan_fee_wad: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if flash_loan_fee_wad > WAD {
+        msg!("Flash loan fee must be in range [0, 1_000_000_000_000_000_000]");
+        return Err(LendingError::InvalidConfig.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let flash_loan_whitelist_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(next_account_info(account_info_iter)?)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let (flash_loan_whitelist_pubkey, bump_seed) =
+        find_flash_loan_whitelist_address(program_id, lending_market_info.key);
+    if &flash_loan_whitelist_pubkey != flash_loan_whitelist_info.key {
+        msg!(
+            "Flash loan whitelist provided does not match the derived flash loan whitelist address"
+        );
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let mut flash_loan_whitelist = if flash_loan_whitelist_info.data_is_empty() {
+        let flash_loan_whitelist_signer_seeds: &[&[_]] = &[
+            lending_market_info.key.as_ref(),
+            b"flash_loan_whitelist",
+            &[bump_seed],
+        ];
+        create_pda_account(CreatePdaAccountParams {
+            payer: lending_market_owner_info.clone(),
+            account: flash_loan_whitelist_info.clone(),
+            space: FlashLoanWhitelist::LEN,
+            owner: program_id,
+            rent,
+            signer_seeds: flash_loan_whitelist_signer_seeds,
+            system_program: system_program_info.clone(),
+        })?;
+        FlashLoanWhitelist::new(*lending_market_info.key)
+    } else {
+        if flash_loan_whitelist_info.owner != program_id {
+            msg!("Flash loan whitelist provided is not owned by the lending program");
+            return Err(LendingError::InvalidAccountOwner.into());
+        }
+        FlashLoanWhitelist::unpack(&flash_loan_whitelist_info.data.borrow())?
+    };
+
+    flash_loan_whitelist.add_entry(FlashLoanWhitelistEntry {
+        receiver_program_id,
+        flash_loan_fee_wad,
+    })?;
+    FlashLoanWhitelist::pack(
+        flash_loan_whitelist,
+        &mut flash_loan_whitelist_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+fn process_remove_flash_loan_whitelist_entry(
+    program_id: &Pubkey,
+    receiver_program_id: Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let flash_loan_whitelist_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let mut flash_loan_whitelist =
+        FlashLoanWhitelist::unpack(&flash_loan_whitelist_info.data.borrow())?;
+    if flash_loan_whitelist_info.owner != program_id {
+        msg!("Flash loan whitelist provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &flash_loan_whitelist.lending_market != lending_market_info.key {
+        msg!("Flash loan whitelist lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    flash_loan_whitelist.remove_entry(&receiver_program_id)?;
+    FlashLoanWhitelist::pack(
+        flash_loan_whitelist,
+        &mut flash_loan_whitelist_info.data.borrow_mut(),
+    )?;
+
+    Ok(())
+}
+
+fn process_init_referrer_fees_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let referrer_fees_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_mint_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let referrer_info = next_account_info(account_info_iter)?;
+    let funding_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(rent_info)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.mint_pubkey != reserve_liquidity_mint_info.key {
+        msg!("Reserve liquidity mint does not match the reserve liquidity mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let (referrer_fees_pubkey, bump_seed) =
+        find_referrer_fees_address(program_id, reserve_info.key, referrer_info.key);
+    if &referrer_fees_pubkey != referrer_fees_info.key {
+        msg!("Referrer fees account provided does not match the derived referrer fees address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let referrer_fees_signer_seeds: &[&[_]] = &[
+        reserve_info.key.as_ref(),
+        referrer_info.key.as_ref(),
+        b"referrer_fees",
+        &[bump_seed],
+    ];
+    create_pda_account(CreatePdaAccountParams {
+        payer: funding_info.clone(),
+        account: referrer_fees_info.clone(),
+        space: Account::LEN,
+        owner: token_program_id.key,
+        rent,
+        signer_seeds: referrer_fees_signer_seeds,
+        system_program: system_program_info.clone(),
+    })?;
+
+    spl_token_init_account(TokenInitializeAccountParams {
+        account: referrer_fees_info.clone(),
+        mint: reserve_liquidity_mint_info.clone(),
+        owner: lending_market_authority_info.clone(),
+        rent: rent_info.clone(),
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_claim_referral_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let referrer_fees_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let referrer_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    if !referrer_info.is_signer {
+        msg!("Referrer provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    let (referrer_fees_pubkey, _bump_seed) =
+        find_referrer_fees_address(program_id, reserve_info.key, referrer_info.key);
+    if &referrer_fees_pubkey != referrer_fees_info.key {
+        msg!("Referrer fees account provided does not match the derived referrer fees address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let claim_amount = Account::unpack(&referrer_fees_info.data.borrow())?.amount;
+    if claim_amount == 0 {
+        msg!("Referrer fees account has no fees to claim");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    spl_token_transfer(TokenTransferParams {
+        source: referrer_fees_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: claim_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_borrow_reserve_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if liquidity_amount == 0 {
+        msg!("Liquidity amount provided cannot be zero");
+        return Err(LendingError::InvalidAmount.into());
+    }
+
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let sysvar_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != source_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == destination_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if !reserve.is_active() {
+        msg!("Reserve is not active and only allows reducing positions");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+
+    let current_index = load_current_index_checked(sysvar_info)? as usize;
+    let current_instruction = load_instruction_at_checked(current_index, sysvar_info)?;
+    if &current_instruction.program_id != program_id {
+        msg!("Flash borrow must be a top-level instruction");
+        return Err(LendingError::FlashLoanCpi.into());
+    }
+
+    // the repay must be a later top-level instruction in this transaction for the same reserve
+    let mut found_repay = false;
+    let mut index = current_index + 1;
+    while let Ok(instruction) = load_instruction_at_checked(index, sysvar_info) {
+        index += 1;
+        if &instruction.program_id != program_id {
+            continue;
+        }
+        match LendingInstruction::unpack(&instruction.data)? {
+            LendingInstruction::FlashRepayReserveLiquidity {
+                liquidity_amount: repay_liquidity_amount,
+                borrow_instruction_index,
+            } => {
+                if found_repay {
+                    msg!("Multiple flash repays not allowed");
+                    return Err(LendingError::MultipleFlashBorrows.into());
+                }
+                if instruction.accounts.get(4).map(|account| account.pubkey)
+                    != Some(*reserve_info.key)
+                {
+                    msg!("Invalid reserve account on flash repay");
+                    return Err(LendingError::InvalidFlashRepay.into());
+                }
+                if repay_liquidity_amount != liquidity_amount {
+                    msg!("Liquidity amount for flash repay doesn't match borrow");
+                    return Err(LendingError::InvalidFlashRepay.into());
+                }
+                if borrow_instruction_index as usize != current_index {
+                    msg!("Borrow instruction index for flash repay doesn't match current index");
+                    return Err(LendingError::InvalidFlashRepay.into());
+                }
+                found_repay = true;
+            }
+            LendingInstruction::FlashBorrowReserveLiquidity { .. } => {
+                msg!("Multiple flash borrows not allowed");
+                return Err(LendingError::MultipleFlashBorrows.into());
+            }
+            _ => (),
+        }
+    }
+    if !found_repay {
+        msg!("No matching flash repay found");
+        return Err(LendingError::NoFlashRepayFound.into());
+    }
+
+    // flash borrows don't require a fresh reserve, so the last refreshed price is used
+    let outflow_value = reserve.market_value(Decimal::from(liquidity_amount))?;
+    reserve
+        .rate_limiter
+        .update(Clock::get()?.slot, outflow_value)?;
+    reserve.liquidity.borrow(Decimal::from(liquidity_amount))?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: liquidity_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_flash_repay_reserve_liquidity(
+    program_id: &Pubkey,
+    liquidity_amount: u64,
+    borrow_instruction_index: u8,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let host_fee_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let sysvar_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != destination_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the destination liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey == source_liquidity_info.key {
+        msg!("Reserve liquidity supply cannot be used as the source liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let current_index = load_current_index_checked(sysvar_info)? as usize;
+    let current_instruction = load_instruction_at_checked(current_index, sysvar_info)?;
+    if &current_instruction.program_id != program_id {
+        msg!("Flash repay must be a top-level instruction");
+        return Err(LendingError::FlashLoanCpi.into());
+    }
+
+    // the matching borrow has already validated this repay, so only the link back is checked
+    let borrow_instruction_index = borrow_instruction_index as usize;
+    if borrow_instruction_index >= current_index {
+        msg!("Flash repay must come after the flash borrow");
+        return Err(LendingError::InvalidFlashRepay.into());
+    }
+    let borrow_instruction = load_instruction_at_checked(borrow_instruction_index, sysvar_info)?;
+    if &borrow_instruction.program_id != program_id {
+        msg!("Flash repay borrow instruction index is not a lending program instruction");
+        return Err(LendingError::InvalidFlashRepay.into());
+    }
+    match LendingInstruction::unpack(&borrow_instruction.data)? {
+        LendingInstruction::FlashBorrowReserveLiquidity {
+            liquidity_amount: borrow_liquidity_amount,
+        } => {
+            if borrow_instruction
+                .accounts
+                .get(2)
+                .map(|account| account.pubkey)
+                != Some(*reserve_info.key)
+            {
+                msg!("Invalid reserve account on flash borrow");
+                return Err(LendingError::InvalidFlashRepay.into());
+            }
+            if borrow_liquidity_amount != liquidity_amount {
+                msg!("Liquidity amount for flash repay doesn't match borrow");
+                return Err(LendingError::InvalidFlashRepay.into());
+            }
+        }
+        _ => {
+            msg!("Flash repay borrow instruction index is not a flash borrow");
+            return Err(LendingError::InvalidFlashRepay.into());
+        }
+    }
+
+    let (origination_fee, host_fee) = reserve
+        .config
+        .fees
+        .calculate_flash_loan_fees(Decimal::from(liquidity_amount), FeeCalculation::Exclusive)?;
+
+    reserve
+        .liquidity
+        .repay(liquidity_amount, Decimal::from(liquidity_amount))?;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: source_liquidity_info.clone(),
+        destination: destination_liquidity_info.clone(),
+        amount: liquidity_amount,
+        authority: user_transfer_authority_info.clone(),
+        authority_signer_seeds: &[],
+        token_program: token_program_id.clone(),
+    })?;
+
+    let mut owner_fee = origination_fee;
+    if host_fee > 0 {
+        owner_fee = owner_fee
+            .checked_sub(host_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: host_fee_receiver_info.clone(),
+            amount: host_fee,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    if owner_fee > 0 {
+        spl_token_transfer(TokenTransferParams {
+            source: source_liquidity_info.clone(),
+            destination: reserve_liquidity_fee_receiver_info.clone(),
+            amount: owner_fee,
+            authority: user_transfer_authority_info.clone(),
+            authority_signer_seeds: &[],
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_withdraw_obligation_collateral_and_redeem_reserve_liquidity(
+    program_id: &Pubkey,
+    collateral_amount: u64,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_collateral_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let obligation_owner_info = next_account_info(account_info_iter)?;
+    let user_transfer_authority_info = next_account_info(account_info_iter)?;
+    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let liquidity_amount = _withdraw_obligation_collateral(
+        program_id,
+        collateral_amount,
+        reserve_collateral_info,
+        user_collateral_info,
+        reserve_info,
+        obligation_info,
+        lending_market_info,
+        lending_market_authority_info,
+        obligation_owner_info,
+        clock,
+        token_program_id,
+    )?;
+
+    _redeem_reserve_collateral(
+        program_id,
+        liquidity_amount,
+        user_collateral_info,
+        user_liquidity_info,
+        reserve_info,
+        reserve_collateral_mint_info,
+        reserve_liquidity_supply_info,
+        lending_market_info,
+        lending_market_authority_info,
+        user_transfer_authority_info,
+        clock,
+        token_program_id,
+        true,
+    )?;
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_update_reserve_config(
+    program_id: &Pubkey,
+    config: ReserveConfig,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    validate_reserve_config(config)?;
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let pyth_product_info = next_account_info(account_info_iter)?;
+    let pyth_price_info = next_account_info(account_info_iter)?;
+    let switchboard_feed_info = next_account_info(account_info_iter)?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!(
+            "Reserve provided is not owned by the lending program {} != {}",
+            &reserve_info.owner.to_string(),
+            &program_id.to_string(),
+        );
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!(
+            "Lending market provided is not owned by the lending program  {} != {}",
+            &lending_market_info.owner.to_string(),
+            &program_id.to_string(),
+        );
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    if *pyth_price_info.key != reserve.liquidity.pyth_oracle_pubkey {
+        validate_pyth_keys(&lending_market, pyth_product_info, pyth_price_info)?;
+        reserve.liquidity.pyth_oracle_pubkey = *pyth_price_info.key;
+    }
+
+    if *switchboard_feed_info.key != reserve.liquidity.switchboard_oracle_pubkey {
+        validate_switchboard_keys(&lending_market, switchboard_feed_info)?;
+        reserve.liquidity.switchboard_oracle_pubkey = *switchboard_feed_info.key;
+    }
+    if reserve.liquidity.switchboard_oracle_pubkey == solend_program::NULL_PUBKEY
+        && (*pyth_price_info.key == solend_program::NULL_PUBKEY
+            || *pyth_product_info.key == solend_program::NULL_PUBKEY)
+    {
+        msg!("At least one price oracle must have a non-null pubkey");
+        return Err(LendingError::InvalidOracleConfig.into());
+    }
+
+    reserve.config = config;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_redeem_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter().peekable();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_supply_liquidity_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!(
+            "Reserve provided is not owned by the lending program {} != {}",
+            &reserve_info.owner.to_string(),
+            &program_id.to_string(),
+        );
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_supply_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the reserve supply liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
 
-    let balance_before_flash_loan = Account::unpack(&source_liquidity_info.data.borrow())?.amount;
-    let expected_balance_after_flash_loan = balance_before_flash_loan
-        .checked_add(origination_fee)
-        .ok_or(LendingError::MathOverflow)?;
-    let returned_amount_required = flash_loan_amount
-        .checked_add(origination_fee)
-        .ok_or(LendingError::MathOverflow)?;
+    let withdraw_amount = reserve.calculate_redeem_fees()?;
+    if withdraw_amount == 0 {
+        return Err(LendingError::InsufficientProtocolFeesToRedeem.into());
+    }
 
-    let mut flash_loan_instruction_accounts = vec![
-        AccountMeta::new(*destination_liquidity_info.key, false),
-        AccountMeta::new(*source_liquidity_info.key, false),
-        AccountMeta::new_readonly(*token_program_id.key, false),
-    ];
-    let mut flash_loan_instruction_account_infos = vec![
-        destination_liquidity_info.clone(),
-        flash_loan_receiver_program_id.clone(),
-        source_liquidity_info.clone(),
-        token_program_id.clone(),
+    reserve.liquidity.redeem_fees(withdraw_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_supply_liquidity_info.clone(),
+        destination: reserve_liquidity_fee_receiver_info.clone(),
+        amount: withdraw_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_collect_protocol_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Reserve collateral mint does not match the reserve collateral mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
     ];
-    for account_info in account_info_iter {
-        flash_loan_instruction_accounts.push(AccountMeta {
-            pubkey: *account_info.key,
-            is_signer: account_info.is_signer,
-            is_writable: account_info.is_writable,
-        });
-        flash_loan_instruction_account_infos.push(account_info.clone());
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    reserve.liquidity.borrow(flash_loan_amount_decimal)?;
+    let collateral_amount = reserve.collect_protocol_fees()?;
+    if collateral_amount == 0 {
+        return Err(LendingError::InsufficientProtocolFeesToRedeem.into());
+    }
+    reserve.last_update.mark_stale();
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
 
-    spl_token_transfer(TokenTransferParams {
-        source: source_liquidity_info.clone(),
-        destination: destination_liquidity_info.clone(),
-        amount: flash_loan_amount,
+    spl_token_mint_to(TokenMintToParams {
+        mint: reserve_collateral_mint_info.clone(),
+        destination: destination_collateral_info.clone(),
+        amount: collateral_amount,
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_init_insurance_fund_account(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let insurance_fund_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_mint_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let funding_info = next_account_info(account_info_iter)?;
+    let rent_info = next_account_info(account_info_iter)?;
+    let rent = &Rent::from_account_info(rent_info)?;
+    let system_program_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.mint_pubkey != reserve_liquidity_mint_info.key {
+        msg!("Reserve liquidity mint does not match the reserve liquidity mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let (insurance_fund_pubkey, bump_seed) = find_insurance_fund_address(
+        program_id,
+        lending_market_info.key,
+        reserve_liquidity_mint_info.key,
+    );
+    if &insurance_fund_pubkey != insurance_fund_info.key {
+        msg!("Insurance fund account provided does not match the derived insurance fund address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let insurance_fund_signer_seeds: &[&[_]] = &[
+        lending_market_info.key.as_ref(),
+        reserve_liquidity_mint_info.key.as_ref(),
+        b"insurance_fund",
+        &[bump_seed],
+    ];
+    create_pda_account(CreatePdaAccountParams {
+        payer: funding_info.clone(),
+        account: insurance_fund_info.clone(),
+        space: Account::LEN,
+        owner: token_program_id.key,
+        rent,
+        signer_seeds: insurance_fund_signer_seeds,
+        system_program: system_program_info.clone(),
+    })?;
+
+    spl_token_init_account(TokenInitializeAccountParams {
+        account: insurance_fund_info.clone(),
+        mint: reserve_liquidity_mint_info.clone(),
+        owner: lending_market_authority_info.clone(),
+        rent: rent_info.clone(),
+        token_program: token_program_id.clone(),
+    })?;
+
+    Ok(())
+}
+
+fn process_redeem_insurance_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let insurance_fund_info = next_account_info(account_info_iter)?;
+    let reserve_supply_liquidity_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_supply_liquidity_info.key {
+        msg!("Reserve liquidity supply must be used as the reserve supply liquidity provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.last_update.is_stale(clock.slot)? {
+        msg!("Reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
+
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        program_id,
+        lending_market_info.key,
+        &reserve.liquidity.mint_pubkey,
+    );
+    if &insurance_fund_pubkey != insurance_fund_info.key {
+        msg!("Insurance fund account provided does not match the derived insurance fund address");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let withdraw_amount = reserve.calculate_redeem_insurance_fees()?;
+    if withdraw_amount == 0 {
+        return Err(LendingError::InsufficientProtocolFeesToRedeem.into());
+    }
+
+    reserve.liquidity.redeem_insurance_fees(withdraw_amount)?;
+    reserve.last_update.mark_stale();
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    spl_token_transfer(TokenTransferParams {
+        source: reserve_supply_liquidity_info.clone(),
+        destination: insurance_fund_info.clone(),
+        amount: withdraw_amount,
         authority: lending_market_authority_info.clone(),
         authority_signer_seeds,
         token_program: token_program_id.clone(),
     })?;
 
-    const RECEIVE_FLASH_LOAN_INSTRUCTION_DATA_SIZE: usize = 9;
-    // @FIXME: don't use 0 to indicate a flash loan receiver instruction https://git.io/JGzz9
-    const RECEIVE_FLASH_LOAN_INSTRUCTION_TAG: u8 = 0u8;
+    Ok(())
+}
 
-    let mut data = Vec::with_capacity(RECEIVE_FLASH_LOAN_INSTRUCTION_DATA_SIZE);
-    data.push(RECEIVE_FLASH_LOAN_INSTRUCTION_TAG);
-    data.extend_from_slice(&returned_amount_required.to_le_bytes());
+#[inline(never)] // avoid stack frame limit
+fn process_write_off_bad_debt(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let obligation_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let insurance_fund_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+    let clock = &Clock::get()?;
 
-    invoke(
-        &Instruction {
-            program_id: *flash_loan_receiver_program_id.key,
-            accounts: flash_loan_instruction_accounts,
-            data,
-        },
-        &flash_loan_instruction_account_infos[..],
-    )?;
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
 
-    reserve = Reserve::unpack(&reserve_info.data.borrow())?;
-    reserve
-        .liquidity
-        .repay(flash_loan_amount, flash_loan_amount_decimal)?;
-    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    let mut borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+    if borrow_reserve_info.owner != program_id {
+        msg!("Borrow reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &borrow_reserve.lending_market != lending_market_info.key {
+        msg!("Borrow reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &borrow_reserve.liquidity.supply_pubkey != borrow_reserve_liquidity_supply_info.key {
+        msg!("Borrow reserve liquidity supply does not match the borrow reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if borrow_reserve.last_update.is_stale(clock.slot)? {
+        msg!("Borrow reserve is stale and must be refreshed in the current slot");
+        return Err(LendingError::ReserveStale.into());
+    }
 
-    let actual_balance_after_flash_loan =
-        Account::unpack(&source_liquidity_info.data.borrow())?.amount;
-    if actual_balance_after_flash_loan < expected_balance_after_flash_loan {
-        msg!("Insufficient reserve liquidity after flash loan");
-        return Err(LendingError::NotEnoughLiquidityAfterFlashLoan.into());
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        program_id,
+        lending_market_info.key,
+        &borrow_reserve.liquidity.mint_pubkey,
+    );
+    if &insurance_fund_pubkey != insurance_fund_info.key {
+        msg!("Insurance fund account provided does not match the derived insurance fund address");
+        return Err(LendingError::InvalidAccountInput.into());
     }
 
-    let mut owner_fee = origination_fee;
-    if host_fee > 0 {
-        owner_fee = owner_fee
-            .checked_sub(host_fee)
-            .ok_or(LendingError::MathOverflow)?;
-        spl_token_transfer(TokenTransferParams {
-            source: source_liquidity_info.clone(),
-            destination: host_fee_receiver_info.clone(),
-            amount: host_fee,
-            authority: lending_market_authority_info.clone(),
-            authority_signer_seeds,
-            token_program: token_program_id.clone(),
-        })?;
+    let mut obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    if obligation_info.owner != program_id {
+        msg!("Obligation provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &obligation.lending_market != lending_market_info.key {
+        msg!("Obligation lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if obligation.last_update.is_stale(clock.slot)? {
+        msg!("Obligation is stale and must be refreshed in the current slot");
+        return Err(LendingError::ObligationStale.into());
+    }
+    if !obligation.deposits.is_empty() {
+        msg!("Obligation collateral must be liquidated before its debt can be written off");
+        return Err(LendingError::ObligationHasCollateral.into());
     }
 
-    if owner_fee > 0 {
+    let (liquidity, liquidity_index) =
+        obligation.find_liquidity_in_borrows(*borrow_reserve_info.key)?;
+    let bad_debt = liquidity.borrowed_amount_wads;
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    let insurance_fund_amount = if insurance_fund_info.data_is_empty() {
+        0
+    } else {
+        Account::unpack(&insurance_fund_info.data.borrow())?.amount
+    };
+    let insurance_repay_amount =
+        borrow_reserve.write_off_bad_debt(bad_debt, insurance_fund_amount)?;
+    borrow_reserve.last_update.mark_stale();
+    Reserve::pack(borrow_reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+
+    obligation.repay(bad_debt, liquidity_index)?;
+    obligation.last_update.mark_stale();
+    Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+    if insurance_repay_amount > 0 {
         spl_token_transfer(TokenTransferParams {
-            source: source_liquidity_info.clone(),
-            destination: reserve_liquidity_fee_receiver_info.clone(),
-            amount: owner_fee,
+            source: insurance_fund_info.clone(),
+            destination: borrow_reserve_liquidity_supply_info.clone(),
+            amount: insurance_repay_amount,
             authority: lending_market_authority_info.clone(),
             authority_signer_seeds,
             token_program: token_program_id.clone(),
@@ -2066,81 +4819,217 @@ fn process_flash_loan(
     Ok(())
 }
 
-#[inline(never)] // avoid stack frame limit
-fn process_withdraw_obligation_collateral_and_redeem_reserve_liquidity(
+fn process_set_reserve_status(
     program_id: &Pubkey,
-    collateral_amount: u64,
+    status: ReserveStatus,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
-    let reserve_collateral_info = next_account_info(account_info_iter)?;
-    let user_collateral_info = next_account_info(account_info_iter)?;
-    let reserve_info = next_account_info(account_info_iter)?;
-    let obligation_info = next_account_info(account_info_iter)?;
-    let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
-    let user_liquidity_info = next_account_info(account_info_iter)?;
-    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
-    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
-    let obligation_owner_info = next_account_info(account_info_iter)?;
-    let user_transfer_authority_info = next_account_info(account_info_iter)?;
-    let clock = &Clock::from_account_info(next_account_info(account_info_iter)?)?;
-    let token_program_id = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+
+    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    reserve.status = status;
+    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+#[inline(never)] // avoid stack frame limit
+fn process_close_reserve(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let destination_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let lending_market_owner_info = next_account_info(account_info_iter)?;
+    let token_program_id = next_account_info(account_info_iter)?;
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    if reserve_info.owner != program_id {
+        msg!("Reserve provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &reserve.lending_market != lending_market_info.key {
+        msg!("Reserve lending market does not match the lending market provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.liquidity.supply_pubkey != reserve_liquidity_supply_info.key {
+        msg!("Reserve liquidity supply does not match the reserve liquidity supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.mint_pubkey != reserve_collateral_mint_info.key {
+        msg!("Reserve collateral mint does not match the reserve collateral mint provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &reserve.collateral.supply_pubkey != reserve_collateral_supply_info.key {
+        msg!("Reserve collateral supply does not match the reserve collateral supply provided");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if reserve.status != ReserveStatus::Deprecated {
+        msg!("Reserve must be deprecated before it can be closed");
+        return Err(LendingError::ReserveNotActive.into());
+    }
+    if !reserve.is_empty()? {
+        msg!("Reserve cannot be closed while it has liquidity, collateral, borrows or fees");
+        return Err(LendingError::ReserveNotEmpty.into());
+    }
+
+    let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
+    if lending_market_info.owner != program_id {
+        msg!("Lending market provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    if &lending_market.token_program_id != token_program_id.key {
+        msg!("Lending market token program does not match the token program provided");
+        return Err(LendingError::InvalidTokenProgram.into());
+    }
+    if &lending_market.owner != lending_market_owner_info.key {
+        msg!("Lending market owner does not match the lending market owner provided");
+        return Err(LendingError::InvalidMarketOwner.into());
+    }
+    if !lending_market_owner_info.is_signer {
+        msg!("Lending market owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+
+    let authority_signer_seeds = &[
+        lending_market_info.key.as_ref(),
+        &[lending_market.bump_seed],
+    ];
+    let lending_market_authority_pubkey =
+        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
+    if &lending_market_authority_pubkey != lending_market_authority_info.key {
+        msg!(
+            "Derived lending market authority does not match the lending market authority provided"
+        );
+        return Err(LendingError::InvalidMarketAuthority.into());
+    }
+
+    // sweep unaccounted tokens and unclaimed fees before closing the token accounts
+    for token_account_info in [
+        reserve_liquidity_supply_info,
+        reserve_liquidity_fee_receiver_info,
+    ] {
+        let amount = Account::unpack(&token_account_info.data.borrow())?.amount;
+        if amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: token_account_info.clone(),
+                destination: destination_liquidity_info.clone(),
+                amount,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
+
+    for token_account_info in [
+        reserve_liquidity_supply_info,
+        reserve_liquidity_fee_receiver_info,
+        reserve_collateral_supply_info,
+    ] {
+        spl_token_close_account(TokenCloseAccountParams {
+            account: token_account_info.clone(),
+            destination: destination_info.clone(),
+            authority: lending_market_authority_info.clone(),
+            authority_signer_seeds,
+            token_program: token_program_id.clone(),
+        })?;
+    }
+
+    spl_token_revoke_mint_authority(TokenRevokeMintAuthorityParams {
+        mint: reserve_collateral_mint_info.clone(),
+        authority: lending_market_authority_info.clone(),
+        authority_signer_seeds,
+        token_program: token_program_id.clone(),
+    })?;
+
+    close_account(reserve_info, destination_info)
+}
+
+fn process_migrate_account(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+    let account_info = next_account_info(account_info_iter)?;
 
-    let liquidity_amount = _withdraw_obligation_collateral(
-        program_id,
-        collateral_amount,
-        reserve_collateral_info,
-        user_collateral_info,
-        reserve_info,
-        obligation_info,
-        lending_market_info,
-        lending_market_authority_info,
-        obligation_owner_info,
-        clock,
-        token_program_id,
-    )?;
+    if account_info.owner != program_id {
+        msg!("Account provided is not owned by the lending program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
 
-    _redeem_reserve_collateral(
-        program_id,
-        liquidity_amount,
-        user_collateral_info,
-        user_liquidity_info,
-        reserve_info,
-        reserve_collateral_mint_info,
-        reserve_liquidity_supply_info,
-        lending_market_info,
-        lending_market_authority_info,
-        user_transfer_authority_info,
-        clock,
-        token_program_id,
-    )?;
-    Ok(())
+    // account lengths are unique to each account type
+    let data_len = account_info.data_len();
+    if data_len == LendingMarket::LEN {
+        migrate_account::<LendingMarket>(account_info)
+    } else if data_len == Reserve::LEN {
+        migrate_account::<Reserve>(account_info)
+    } else if data_len == FlashLoanWhitelist::LEN {
+        migrate_account::<FlashLoanWhitelist>(account_info)
+    } else if Obligation::max_reserves(data_len).is_ok() {
+        migrate_account::<Obligation>(account_info)
+    } else {
+        msg!("Account provided is not a lending program account");
+        Err(LendingError::InvalidAccountInput.into())
+    }
+}
+
+fn migrate_account<T: Migrate>(account_info: &AccountInfo) -> ProgramResult {
+    let mut state = T::unpack(&account_info.data.borrow())?;
+    if !state.migrate()? {
+        msg!("Account provided is already at the current program version");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    T::pack(state, &mut account_info.data.borrow_mut())
 }
 
 #[inline(never)] // avoid stack frame limit
-fn process_update_reserve_config(
+fn process_set_reserve_rate_limiter(
     program_id: &Pubkey,
-    config: ReserveConfig,
+    config: RateLimiterConfig,
     accounts: &[AccountInfo],
 ) -> ProgramResult {
-    validate_reserve_config(config)?;
     let account_info_iter = &mut accounts.iter();
     let reserve_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
-    let lending_market_authority_info = next_account_info(account_info_iter)?;
     let lending_market_owner_info = next_account_info(account_info_iter)?;
-    let pyth_product_info = next_account_info(account_info_iter)?;
-    let pyth_price_info = next_account_info(account_info_iter)?;
-    let switchboard_feed_info = next_account_info(account_info_iter)?;
 
     let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
     if reserve_info.owner != program_id {
-        msg!(
-            "Reserve provided is not owned by the lending program {} != {}",
-            &reserve_info.owner.to_string(),
-            &program_id.to_string(),
-        );
+        msg!("Reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
     if &reserve.lending_market != lending_market_info.key {
@@ -2150,11 +5039,7 @@ fn process_update_reserve_config(
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
     if lending_market_info.owner != program_id {
-        msg!(
-            "Lending market provided is not owned by the lending program  {} != {}",
-            &lending_market_info.owner.to_string(),
-            &program_id.to_string(),
-        );
+        msg!("Lending market provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
     if &lending_market.owner != lending_market_owner_info.key {
@@ -2166,77 +5051,33 @@ fn process_update_reserve_config(
         return Err(LendingError::InvalidSigner.into());
     }
 
-    let authority_signer_seeds = &[
-        lending_market_info.key.as_ref(),
-        &[lending_market.bump_seed],
-    ];
-    let lending_market_authority_pubkey =
-        Pubkey::create_program_address(authority_signer_seeds, program_id)?;
-    if &lending_market_authority_pubkey != lending_market_authority_info.key {
-        msg!(
-            "Derived lending market authority does not match the lending market authority provided"
-        );
-        return Err(LendingError::InvalidMarketAuthority.into());
-    }
-
-    if *pyth_price_info.key != reserve.liquidity.pyth_oracle_pubkey {
-        validate_pyth_keys(&lending_market, pyth_product_info, pyth_price_info)?;
-        reserve.liquidity.pyth_oracle_pubkey = *pyth_price_info.key;
-    }
-
-    if *switchboard_feed_info.key != reserve.liquidity.switchboard_oracle_pubkey {
-        validate_switchboard_keys(&lending_market, switchboard_feed_info)?;
-        reserve.liquidity.switchboard_oracle_pubkey = *switchboard_feed_info.key;
-    }
-    if reserve.liquidity.switchboard_oracle_pubkey == solend_program::NULL_PUBKEY
-        && (*pyth_price_info.key == solend_program::NULL_PUBKEY
-            || *pyth_product_info.key == solend_program::NULL_PUBKEY)
-    {
-        msg!("At least one price oracle must have a non-null pubkey");
-        return Err(LendingError::InvalidOracleConfig.into());
-    }
-
-    reserve.config = config;
+    reserve.rate_limiter = RateLimiter::new(config);
     Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+
     Ok(())
 }
 
-#[inline(never)] // avoid stack frame limit
-fn process_redeem_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
-    let account_info_iter = &mut accounts.iter().peekable();
+fn process_distribute_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
     let reserve_info = next_account_info(account_info_iter)?;
     let reserve_liquidity_fee_receiver_info = next_account_info(account_info_iter)?;
-    let reserve_supply_liquidity_info = next_account_info(account_info_iter)?;
+    let owner_liquidity_info = next_account_info(account_info_iter)?;
     let lending_market_info = next_account_info(account_info_iter)?;
     let lending_market_authority_info = next_account_info(account_info_iter)?;
     let token_program_id = next_account_info(account_info_iter)?;
-    let clock = &Clock::get()?;
 
-    let mut reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
     if reserve_info.owner != program_id {
-        msg!(
-            "Reserve provided is not owned by the lending program {} != {}",
-            &reserve_info.owner.to_string(),
-            &program_id.to_string(),
-        );
+        msg!("Reserve provided is not owned by the lending program");
         return Err(LendingError::InvalidAccountOwner.into());
     }
-
-    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
-        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
-    if &reserve.liquidity.supply_pubkey != reserve_supply_liquidity_info.key {
-        msg!("Reserve liquidity supply must be used as the reserve supply liquidity provided");
-        return Err(LendingError::InvalidAccountInput.into());
-    }
     if &reserve.lending_market != lending_market_info.key {
         msg!("Reserve lending market does not match the lending market provided");
         return Err(LendingError::InvalidAccountInput.into());
     }
-    if reserve.last_update.is_stale(clock.slot)? {
-        msg!("reserve is stale and must be refreshed in the current slot");
-        return Err(LendingError::ReserveStale.into());
+    if &reserve.config.fee_receiver != reserve_liquidity_fee_receiver_info.key {
+        msg!("Reserve liquidity fee receiver does not match the reserve liquidity fee receiver provided");
+        return Err(LendingError::InvalidAccountInput.into());
     }
 
     let lending_market = LendingMarket::unpack(&lending_market_info.data.borrow())?;
@@ -2248,6 +5089,7 @@ fn process_redeem_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> Program
         msg!("Lending market token program does not match the token program provided");
         return Err(LendingError::InvalidTokenProgram.into());
     }
+
     let authority_signer_seeds = &[
         lending_market_info.key.as_ref(),
         &[lending_market.bump_seed],
@@ -2261,24 +5103,107 @@ fn process_redeem_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> Program
         return Err(LendingError::InvalidMarketAuthority.into());
     }
 
-    let withdraw_amount = reserve.calculate_redeem_fees()?;
-    if withdraw_amount == 0 {
-        return Err(LendingError::InsufficientProtocolFeesToRedeem.into());
+    assert_fee_distribution_token_account(
+        owner_liquidity_info,
+        &lending_market.owner,
+        &reserve.liquidity.mint_pubkey,
+        token_program_id.key,
+    )?;
+    let fee_receiver_infos = lending_market
+        .fee_distribution
+        .iter()
+        .map(|receiver| {
+            let receiver_info = next_account_info(account_info_iter)?;
+            assert_fee_distribution_token_account(
+                receiver_info,
+                &receiver.owner,
+                &reserve.liquidity.mint_pubkey,
+                token_program_id.key,
+            )?;
+            Ok(receiver_info)
+        })
+        .collect::<Result<Vec<_>, ProgramError>>()?;
+
+    let distribute_amount =
+        Account::unpack(&reserve_liquidity_fee_receiver_info.data.borrow())?.amount;
+    if distribute_amount == 0 {
+        msg!("Reserve liquidity fee receiver has no fees to distribute");
+        return Err(LendingError::InvalidAmount.into());
     }
 
-    reserve.liquidity.redeem_fees(withdraw_amount)?;
-    reserve.last_update.mark_stale();
-    Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+    // the remainder goes to the lending market owner so no fees are split twice
+    let (receiver_fees, owner_fee) =
+        lending_market.calculate_fee_distribution(distribute_amount)?;
+    for (destination_info, amount) in fee_receiver_infos
+        .into_iter()
+        .zip(receiver_fees)
+        .chain(std::iter::once((owner_liquidity_info, owner_fee)))
+    {
+        if amount > 0 {
+            spl_token_transfer(TokenTransferParams {
+                source: reserve_liquidity_fee_receiver_info.clone(),
+                destination: destination_info.clone(),
+                amount,
+                authority: lending_market_authority_info.clone(),
+                authority_signer_seeds,
+                token_program: token_program_id.clone(),
+            })?;
+        }
+    }
 
-    spl_token_transfer(TokenTransferParams {
-        source: reserve_supply_liquidity_info.clone(),
-        destination: reserve_liquidity_fee_receiver_info.clone(),
-        amount: withdraw_amount,
-        authority: lending_market_authority_info.clone(),
-        authority_signer_seeds,
-        token_program: token_program_id.clone(),
-    })?;
+    Ok(())
+}
+
+/// Host fees of an obligation with a referrer accrue in the referrer fees account for the borrow
+/// reserve, or go to the reserve fee receiver until that account is initialized
+fn obligation_host_fee_receiver<'a, 'b>(
+    program_id: &Pubkey,
+    obligation: &Obligation,
+    borrow_reserve_info: &AccountInfo,
+    host_fee_receiver_info: Option<&'a AccountInfo<'b>>,
+) -> Result<Option<&'a AccountInfo<'b>>, ProgramError> {
+    if obligation.referrer == Pubkey::default() {
+        return Ok(host_fee_receiver_info);
+    }
+
+    let (referrer_fees_pubkey, _bump_seed) =
+        find_referrer_fees_address(program_id, borrow_reserve_info.key, &obligation.referrer);
+    match host_fee_receiver_info {
+        Some(host_fee_receiver_info) if host_fee_receiver_info.key == &referrer_fees_pubkey => {
+            if host_fee_receiver_info.data_is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(host_fee_receiver_info))
+            }
+        }
+        _ => {
+            msg!("Host fee receiver must be the referrer fees account of the obligation referrer");
+            Err(LendingError::InvalidAccountInput.into())
+        }
+    }
+}
 
+/// Checks that a fee distribution destination is a token account of the reserve liquidity mint
+/// owned by the expected owner
+fn assert_fee_distribution_token_account(
+    token_account_info: &AccountInfo,
+    owner: &Pubkey,
+    liquidity_mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> ProgramResult {
+    if token_account_info.owner != token_program_id {
+        msg!("Fee distribution token account provided is not owned by the token program");
+        return Err(LendingError::InvalidAccountOwner.into());
+    }
+    let token_account = Account::unpack(&token_account_info.data.borrow())?;
+    if &token_account.owner != owner {
+        msg!("Fee distribution token account owner does not match the expected owner");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+    if &token_account.mint != liquidity_mint {
+        msg!("Fee distribution token account mint does not match the reserve liquidity mint");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
     Ok(())
 }
 
@@ -2306,6 +5231,100 @@ fn assert_uninitialized<T: Pack + IsInitialized>(
     }
 }
 
+/// Checks that the obligation owner, or a delegate granted the given permissions, signed.
+/// Tokens paid out on behalf of a delegate must go to an account owned by the obligation owner.
+fn assert_obligation_authority(
+    obligation: &Obligation,
+    authority_info: &AccountInfo,
+    permissions: u8,
+    destination_info: Option<&AccountInfo>,
+) -> ProgramResult {
+    if &obligation.owner != authority_info.key {
+        if !obligation.has_delegate_permission(authority_info.key, permissions) {
+            msg!("Obligation owner or delegate with the required permissions does not match the obligation owner provided");
+            return Err(LendingError::InvalidObligationOwner.into());
+        }
+        if let Some(destination_info) = destination_info {
+            let destination = Account::unpack(&destination_info.data.borrow())?;
+            if destination.owner != obligation.owner {
+                msg!("Obligation delegate can only send tokens to accounts owned by the obligation owner");
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+        }
+    }
+    if !authority_info.is_signer {
+        msg!("Obligation owner provided must be a signer");
+        return Err(LendingError::InvalidSigner.into());
+    }
+    Ok(())
+}
+
+/// Zeroes the account data and moves all of its lamports to the destination.
+fn close_account(account_info: &AccountInfo, destination_info: &AccountInfo) -> ProgramResult {
+    if account_info.key == destination_info.key {
+        msg!("Destination provided cannot be the account being closed");
+        return Err(LendingError::InvalidAccountInput.into());
+    }
+
+    account_info.data.borrow_mut().fill(0);
+    let lamports = account_info.lamports();
+    **destination_info.lamports.borrow_mut() = destination_info
+        .lamports()
+        .checked_add(lamports)
+        .ok_or(LendingError::MathOverflow)?;
+    **account_info.lamports.borrow_mut() = 0;
+
+    Ok(())
+}
+
+/// Creates a program derived account. Anyone can transfer lamports to the address before it
+/// is created, which makes `create_account` fail, so an already funded account is topped up to
+/// rent exemption, allocated and assigned instead.
+fn create_pda_account(params: CreatePdaAccountParams<'_, '_>) -> ProgramResult {
+    let CreatePdaAccountParams {
+        payer,
+        account,
+        space,
+        owner,
+        rent,
+        signer_seeds,
+        system_program,
+    } = params;
+    let required_lamports = rent.minimum_balance(space);
+
+    if account.lamports() == 0 {
+        return invoke_signed(
+            &system_instruction::create_account(
+                payer.key,
+                account.key,
+                required_lamports,
+                space as u64,
+                owner,
+            ),
+            &[payer, account, system_program],
+            &[signer_seeds],
+        );
+    }
+
+    let top_up_lamports = required_lamports.saturating_sub(account.lamports());
+    if top_up_lamports > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, top_up_lamports),
+            &[payer, account.clone(), system_program.clone()],
+        )?;
+    }
+    invoke_signed(
+        &system_instruction::allocate(account.key, space as u64),
+        &[account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )?;
+    invoke_signed(
+        &system_instruction::assign(account.key, owner),
+        &[account, system_program],
+        &[signer_seeds],
+    )
+}
+
 /// Unpacks a spl_token `Mint`.
 fn unpack_mint(data: &[u8]) -> Result<Mint, LendingError> {
     Mint::unpack(data).map_err(|_| LendingError::InvalidTokenMint)
@@ -2649,6 +5668,54 @@ fn spl_token_burn(params: TokenBurnParams<'_, '_>) -> ProgramResult {
     result.map_err(|_| LendingError::TokenBurnFailed.into())
 }
 
+/// Issue a spl_token `CloseAccount` instruction.
+fn spl_token_close_account(params: TokenCloseAccountParams<'_, '_>) -> ProgramResult {
+    let TokenCloseAccountParams {
+        account,
+        destination,
+        authority,
+        token_program,
+        authority_signer_seeds,
+    } = params;
+    let result = invoke_optionally_signed(
+        &spl_token::instruction::close_account(
+            token_program.key,
+            account.key,
+            destination.key,
+            authority.key,
+            &[],
+        )?,
+        &[account, destination, authority, token_program],
+        authority_signer_seeds,
+    );
+    result.map_err(|_| LendingError::TokenCloseAccountFailed.into())
+}
+
+/// Issue a spl_token `SetAuthority` instruction which removes the mint authority.
+fn spl_token_revoke_mint_authority(
+    params: TokenRevokeMintAuthorityParams<'_, '_>,
+) -> ProgramResult {
+    let TokenRevokeMintAuthorityParams {
+        mint,
+        authority,
+        token_program,
+        authority_signer_seeds,
+    } = params;
+    let result = invoke_optionally_signed(
+        &spl_token::instruction::set_authority(
+            token_program.key,
+            mint.key,
+            None,
+            spl_token::instruction::AuthorityType::MintTokens,
+            authority.key,
+            &[],
+        )?,
+        &[mint, authority, token_program],
+        authority_signer_seeds,
+    );
+    result.map_err(|_| LendingError::TokenSetAuthorityFailed.into())
+}
+
 /// validates reserve configs
 #[inline(always)]
 fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
@@ -2682,6 +5749,14 @@ fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
         msg!("Borrow fee must be in range [0, 1_000_000_000_000_000_000)");
         return Err(LendingError::InvalidConfig.into());
     }
+    if config.fees.deposit_fee_wad >= WAD {
+        msg!("Deposit fee must be in range [0, 1_000_000_000_000_000_000)");
+        return Err(LendingError::InvalidConfig.into());
+    }
+    if config.fees.redeem_fee_wad >= WAD {
+        msg!("Redeem fee must be in range [0, 1_000_000_000_000_000_000)");
+        return Err(LendingError::InvalidConfig.into());
+    }
     if config.fees.host_fee_percentage > 100 {
         msg!("Host fee percentage must be in range [0, 100]");
         return Err(LendingError::InvalidConfig.into());
@@ -2694,6 +5769,10 @@ fn validate_reserve_config(config: ReserveConfig) -> ProgramResult {
         msg!("Protocol take rate must be in range [0, 100]");
         return Err(LendingError::InvalidConfig.into());
     }
+    if config.max_obligation_deposit_percentage > 100 {
+        msg!("Max obligation deposit percentage must be in range [0, 100]");
+        return Err(LendingError::InvalidConfig.into());
+    }
     Ok(())
 }
 
@@ -2768,6 +5847,16 @@ fn validate_switchboard_keys(
     Ok(())
 }
 
+struct CreatePdaAccountParams<'a: 'b, 'b> {
+    payer: AccountInfo<'a>,
+    account: AccountInfo<'a>,
+    space: usize,
+    owner: &'b Pubkey,
+    rent: &'b Rent,
+    signer_seeds: &'b [&'b [u8]],
+    system_program: AccountInfo<'a>,
+}
+
 struct TokenInitializeMintParams<'a: 'b, 'b> {
     mint: AccountInfo<'a>,
     rent: AccountInfo<'a>,
@@ -2811,6 +5900,21 @@ struct TokenBurnParams<'a: 'b, 'b> {
     token_program: AccountInfo<'a>,
 }
 
+struct TokenCloseAccountParams<'a: 'b, 'b> {
+    account: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    authority_signer_seeds: &'b [&'b [u8]],
+    token_program: AccountInfo<'a>,
+}
+
+struct TokenRevokeMintAuthorityParams<'a: 'b, 'b> {
+    mint: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    authority_signer_seeds: &'b [&'b [u8]],
+    token_program: AccountInfo<'a>,
+}
+
 impl PrintProgramError for LendingError {
     fn print<E>(&self)
     where
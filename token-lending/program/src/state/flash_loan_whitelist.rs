@@ -0,0 +1,259 @@
+use super::*;
+use crate::error::LendingError;
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::{Pubkey, PUBKEY_BYTES},
+};
+use std::convert::TryFrom;
+
+/// Max number of receiver programs in a flash loan whitelist
+pub const MAX_FLASH_LOAN_WHITELIST_ENTRIES: usize = 32;
+
+/// Flash loan receiver programs which pay a reduced flash loan fee in a lending market
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FlashLoanWhitelist {
+    /// Version of the struct
+    pub version: u8,
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Whitelisted receiver programs
+    pub entries: Vec<FlashLoanWhitelistEntry>,
+}
+
+impl FlashLoanWhitelist {
+    /// Create a new, empty flash loan whitelist
+    pub fn new(lending_market: Pubkey) -> Self {
+        Self {
+            version: PROGRAM_VERSION,
+            lending_market,
+            entries: vec![],
+        }
+    }
+
+    /// Find the flash loan fee for a whitelisted receiver program
+    pub fn find_flash_loan_fee_wad(&self, receiver_program_id: &Pubkey) -> Option<u64> {
+        self.entries
+            .iter()
+            .find(|entry| &entry.receiver_program_id == receiver_program_id)
+            .map(|entry| entry.flash_loan_fee_wad)
+    }
+
+    /// Add a receiver program or update its flash loan fee
+    pub fn add_entry(&mut self, entry: FlashLoanWhitelistEntry) -> ProgramResult {
+        if let Some(existing) = self
+            .entries
+            .iter_mut()
+            .find(|existing| existing.receiver_program_id == entry.receiver_program_id)
+        {
+            existing.flash_loan_fee_wad = entry.flash_loan_fee_wad;
+            return Ok(());
+        }
+        if self.entries.len() >= MAX_FLASH_LOAN_WHITELIST_ENTRIES {
+            msg!(
+                "Flash loan whitelist cannot have more than {} entries",
+                MAX_FLASH_LOAN_WHITELIST_ENTRIES
+            );
+            return Err(LendingError::FlashLoanWhitelistFull.into());
+        }
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Remove a receiver program
+    pub fn remove_entry(&mut self, receiver_program_id: &Pubkey) -> ProgramResult {
+        let index = self
+            .entries
+            .iter()
+            .position(|entry| &entry.receiver_program_id == receiver_program_id)
+            .ok_or_else(|| {
+                msg!("Receiver program is not in the flash loan whitelist");
+                LendingError::InvalidAccountInput
+            })?;
+        self.entries.remove(index);
+        Ok(())
+    }
+}
+
+/// Whitelisted flash loan receiver program
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FlashLoanWhitelistEntry {
+    /// Flash loan receiver program id
+    pub receiver_program_id: Pubkey,
+    /// Flash loan fee, expressed as a Wad - capped at each reserve's flash loan fee
+    pub flash_loan_fee_wad: u64,
+}
+
+impl Sealed for FlashLoanWhitelist {}
+impl IsInitialized for FlashLoanWhitelist {
+    fn is_initialized(&self) -> bool {
+        self.version != UNINITIALIZED_VERSION
+    }
+}
+
+const FLASH_LOAN_WHITELIST_ENTRY_LEN: usize = 40; // 32 + 8
+const FLASH_LOAN_WHITELIST_LEN: usize = 1378; // 1 + 32 + 1 + (40 * 32) + 64
+impl Pack for FlashLoanWhitelist {
+    const LEN: usize = FLASH_LOAN_WHITELIST_LEN;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let output = array_mut_ref![dst, 0, FLASH_LOAN_WHITELIST_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, lending_market, entries_len, data_flat, _padding) = mut_array_refs![
+            output,
+            1,
+            PUBKEY_BYTES,
+            1,
+            FLASH_LOAN_WHITELIST_ENTRY_LEN * MAX_FLASH_LOAN_WHITELIST_ENTRIES,
+            64
+        ];
+
+        *version = self.version.to_le_bytes();
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        *entries_len = u8::try_from(self.entries.len()).unwrap().to_le_bytes();
+
+        let mut offset = 0;
+        for entry in &self.entries {
+            let entry_flat = array_mut_ref![data_flat, offset, FLASH_LOAN_WHITELIST_ENTRY_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (receiver_program_id, flash_loan_fee_wad) =
+                mut_array_refs![entry_flat, PUBKEY_BYTES, 8];
+            receiver_program_id.copy_from_slice(entry.receiver_program_id.as_ref());
+            *flash_loan_fee_wad = entry.flash_loan_fee_wad.to_le_bytes();
+            offset += FLASH_LOAN_WHITELIST_ENTRY_LEN;
+        }
+    }
+
+    /// Unpacks a byte buffer into a [FlashLoanWhitelist](struct.FlashLoanWhitelist.html).
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![src, 0, FLASH_LOAN_WHITELIST_LEN];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (version, lending_market, entries_len, data_flat, _padding) = array_refs![
+            input,
+            1,
+            PUBKEY_BYTES,
+            1,
+            FLASH_LOAN_WHITELIST_ENTRY_LEN * MAX_FLASH_LOAN_WHITELIST_ENTRIES,
+            64
+        ];
+
+        let version = u8::from_le_bytes(*version);
+        // older versions share the current layout and are upgraded by `Migrate`
+        if version > PROGRAM_VERSION {
+            msg!("Flash loan whitelist version does not match lending program version");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let entries_len = u8::from_le_bytes(*entries_len) as usize;
+        if entries_len > MAX_FLASH_LOAN_WHITELIST_ENTRIES {
+            msg!("Flash loan whitelist has too many entries");
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut entries = Vec::with_capacity(entries_len);
+        let mut offset = 0;
+        for _ in 0..entries_len {
+            let entry_flat = array_ref![data_flat, offset, FLASH_LOAN_WHITELIST_ENTRY_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (receiver_program_id, flash_loan_fee_wad) =
+                array_refs![entry_flat, PUBKEY_BYTES, 8];
+            entries.push(FlashLoanWhitelistEntry {
+                receiver_program_id: Pubkey::new_from_array(*receiver_program_id),
+                flash_loan_fee_wad: u64::from_le_bytes(*flash_loan_fee_wad),
+            });
+            offset += FLASH_LOAN_WHITELIST_ENTRY_LEN;
+        }
+
+        Ok(Self {
+            version,
+            lending_market: Pubkey::new_from_array(*lending_market),
+            entries,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flash_loan_whitelist_pack() {
+        let mut whitelist = FlashLoanWhitelist::new(Pubkey::new_unique());
+        for _ in 0..MAX_FLASH_LOAN_WHITELIST_ENTRIES {
+            whitelist
+                .add_entry(FlashLoanWhitelistEntry {
+                    receiver_program_id: Pubkey::new_unique(),
+                    flash_loan_fee_wad: 1,
+                })
+                .unwrap();
+        }
+
+        let mut packed = [0u8; FlashLoanWhitelist::LEN];
+        FlashLoanWhitelist::pack(whitelist.clone(), &mut packed).unwrap();
+        let unpacked = FlashLoanWhitelist::unpack(&packed).unwrap();
+        assert_eq!(whitelist, unpacked);
+    }
+
+    #[test]
+    fn flash_loan_whitelist_add_remove() {
+        let receiver_program_id = Pubkey::new_unique();
+        let mut whitelist = FlashLoanWhitelist::new(Pubkey::new_unique());
+        assert_eq!(
+            whitelist.find_flash_loan_fee_wad(&receiver_program_id),
+            None
+        );
+
+        whitelist
+            .add_entry(FlashLoanWhitelistEntry {
+                receiver_program_id,
+                flash_loan_fee_wad: 100,
+            })
+            .unwrap();
+        whitelist
+            .add_entry(FlashLoanWhitelistEntry {
+                receiver_program_id,
+                flash_loan_fee_wad: 0,
+            })
+            .unwrap();
+        assert_eq!(whitelist.entries.len(), 1);
+        assert_eq!(
+            whitelist.find_flash_loan_fee_wad(&receiver_program_id),
+            Some(0)
+        );
+
+        whitelist.remove_entry(&receiver_program_id).unwrap();
+        assert_eq!(
+            whitelist.find_flash_loan_fee_wad(&receiver_program_id),
+            None
+        );
+        assert_eq!(
+            whitelist.remove_entry(&receiver_program_id).unwrap_err(),
+            LendingError::InvalidAccountInput.into()
+        );
+    }
+
+    #[test]
+    fn flash_loan_whitelist_full() {
+        let mut whitelist = FlashLoanWhitelist::new(Pubkey::new_unique());
+        for _ in 0..MAX_FLASH_LOAN_WHITELIST_ENTRIES {
+            whitelist
+                .add_entry(FlashLoanWhitelistEntry {
+                    receiver_program_id: Pubkey::new_unique(),
+                    flash_loan_fee_wad: 0,
+                })
+                .unwrap();
+        }
+        assert_eq!(
+            whitelist
+                .add_entry(FlashLoanWhitelistEntry {
+                    receiver_program_id: Pubkey::new_unique(),
+                    flash_loan_fee_wad: 0,
+                })
+                .unwrap_err(),
+            LendingError::FlashLoanWhitelistFull.into()
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::error::LendingError;
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::{
     msg,
@@ -6,6 +7,10 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::{Pubkey, PUBKEY_BYTES},
 };
+use std::convert::TryFrom;
+
+/// Max number of fee distribution receivers in a lending market
+pub const MAX_FEE_DISTRIBUTION_RECEIVERS: usize = 3;
 
 /// Lending market state
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -25,6 +30,11 @@ pub struct LendingMarket {
     pub oracle_program_id: Pubkey,
     /// Oracle (Switchboard) program id
     pub switchboard_oracle_program_id: Pubkey,
+    /// Receivers of a share of the fees distributed from the reserve fee receivers
+    pub fee_distribution: Vec<FeeDistributionReceiver>,
+    /// Percentage of reserve protocol fees set aside for the insurance fund, copied to reserves
+    /// when they are initialized or passed to SetLendingMarketInsuranceFundFee
+    pub insurance_fund_fee_percentage: u8,
 }
 
 impl LendingMarket {
@@ -45,6 +55,34 @@ impl LendingMarket {
         self.oracle_program_id = params.oracle_program_id;
         self.switchboard_oracle_program_id = params.switchboard_oracle_program_id;
     }
+
+    /// Split a fee between the fee distribution receivers, returning the amount for each
+    /// receiver and the remaining amount for the lending market owner
+    pub fn calculate_fee_distribution(&self, fee: u64) -> Result<(Vec<u64>, u64), ProgramError> {
+        let mut remaining_fee = fee;
+        let mut receiver_fees = Vec::with_capacity(self.fee_distribution.len());
+        for receiver in &self.fee_distribution {
+            let receiver_fee = (fee as u128)
+                .checked_mul(receiver.fee_percentage as u128)
+                .map(|fee| fee / 100)
+                .and_then(|fee| u64::try_from(fee).ok())
+                .ok_or(LendingError::MathOverflow)?;
+            remaining_fee = remaining_fee
+                .checked_sub(receiver_fee)
+                .ok_or(LendingError::MathOverflow)?;
+            receiver_fees.push(receiver_fee);
+        }
+        Ok((receiver_fees, remaining_fee))
+    }
+}
+
+/// Receiver of a share of the fees paid to a lending market's reserves
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeDistributionReceiver {
+    /// Owner of the receiver token accounts
+    pub owner: Pubkey,
+    /// Percentage of the distributed reserve fees sent to the receiver
+    pub fee_percentage: u8,
 }
 
 /// Initialize a lending market
@@ -71,7 +109,8 @@ impl IsInitialized for LendingMarket {
     }
 }
 
-const LENDING_MARKET_LEN: usize = 290; // 1 + 1 + 32 + 32 + 32 + 32 + 32 + 128
+const FEE_DISTRIBUTION_RECEIVER_LEN: usize = 33; // 32 + 1
+const LENDING_MARKET_LEN: usize = 290; // 1 + 1 + 32 + 32 + 32 + 32 + 32 + 1 + (33 * 3) + 1 + 27
 impl Pack for LendingMarket {
     const LEN: usize = LENDING_MARKET_LEN;
 
@@ -86,6 +125,9 @@ impl Pack for LendingMarket {
             token_program_id,
             oracle_program_id,
             switchboard_oracle_program_id,
+            fee_distribution_len,
+            fee_distribution_flat,
+            insurance_fund_fee_percentage,
             _padding,
         ) = mut_array_refs![
             output,
@@ -96,7 +138,10 @@ impl Pack for LendingMarket {
             PUBKEY_BYTES,
             PUBKEY_BYTES,
             PUBKEY_BYTES,
-            128
+            1,
+            FEE_DISTRIBUTION_RECEIVER_LEN * MAX_FEE_DISTRIBUTION_RECEIVERS,
+            1,
+            27
         ];
 
         *version = self.version.to_le_bytes();
@@ -106,6 +151,21 @@ impl Pack for LendingMarket {
         token_program_id.copy_from_slice(self.token_program_id.as_ref());
         oracle_program_id.copy_from_slice(self.oracle_program_id.as_ref());
         switchboard_oracle_program_id.copy_from_slice(self.switchboard_oracle_program_id.as_ref());
+        *fee_distribution_len = u8::try_from(self.fee_distribution.len())
+            .unwrap()
+            .to_le_bytes();
+
+        let mut offset = 0;
+        for receiver in &self.fee_distribution {
+            let receiver_flat =
+                array_mut_ref![fee_distribution_flat, offset, FEE_DISTRIBUTION_RECEIVER_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (receiver_owner, fee_percentage) = mut_array_refs![receiver_flat, PUBKEY_BYTES, 1];
+            receiver_owner.copy_from_slice(receiver.owner.as_ref());
+            *fee_percentage = receiver.fee_percentage.to_le_bytes();
+            offset += FEE_DISTRIBUTION_RECEIVER_LEN;
+        }
+        *insurance_fund_fee_percentage = self.insurance_fund_fee_percentage.to_le_bytes();
     }
 
     /// Unpacks a byte buffer into a [LendingMarketInfo](struct.LendingMarketInfo.html)
@@ -120,6 +180,9 @@ impl Pack for LendingMarket {
             token_program_id,
             oracle_program_id,
             switchboard_oracle_program_id,
+            fee_distribution_len,
+            fee_distribution_flat,
+            insurance_fund_fee_percentage,
             _padding,
         ) = array_refs![
             input,
@@ -130,15 +193,38 @@ impl Pack for LendingMarket {
             PUBKEY_BYTES,
             PUBKEY_BYTES,
             PUBKEY_BYTES,
-            128
+            1,
+            FEE_DISTRIBUTION_RECEIVER_LEN * MAX_FEE_DISTRIBUTION_RECEIVERS,
+            1,
+            27
         ];
 
         let version = u8::from_le_bytes(*version);
+        // older versions share the current layout and are upgraded by `Migrate`
         if version > PROGRAM_VERSION {
             msg!("Lending market version does not match lending program version");
             return Err(ProgramError::InvalidAccountData);
         }
 
+        let fee_distribution_len = u8::from_le_bytes(*fee_distribution_len) as usize;
+        if fee_distribution_len > MAX_FEE_DISTRIBUTION_RECEIVERS {
+            msg!("Lending market fee distribution exceeds the account capacity");
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut fee_distribution = Vec::with_capacity(fee_distribution_len);
+        let mut offset = 0;
+        for _ in 0..fee_distribution_len {
+            let receiver_flat =
+                array_ref![fee_distribution_flat, offset, FEE_DISTRIBUTION_RECEIVER_LEN];
+            #[allow(clippy::ptr_offset_with_cast)]
+            let (receiver_owner, fee_percentage) = array_refs![receiver_flat, PUBKEY_BYTES, 1];
+            fee_distribution.push(FeeDistributionReceiver {
+                owner: Pubkey::new_from_array(*receiver_owner),
+                fee_percentage: u8::from_le_bytes(*fee_percentage),
+            });
+            offset += FEE_DISTRIBUTION_RECEIVER_LEN;
+        }
+
         Ok(Self {
             version,
             bump_seed: u8::from_le_bytes(*bump_seed),
@@ -147,6 +233,66 @@ impl Pack for LendingMarket {
             token_program_id: Pubkey::new_from_array(*token_program_id),
             oracle_program_id: Pubkey::new_from_array(*oracle_program_id),
             switchboard_oracle_program_id: Pubkey::new_from_array(*switchboard_oracle_program_id),
+            fee_distribution,
+            insurance_fund_fee_percentage: u8::from_le_bytes(*insurance_fund_fee_percentage),
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lending_market_pack_fee_distribution() {
+        let lending_market = LendingMarket {
+            version: PROGRAM_VERSION,
+            owner: Pubkey::new_unique(),
+            fee_distribution: vec![
+                FeeDistributionReceiver {
+                    owner: Pubkey::new_unique(),
+                    fee_percentage: 20,
+                },
+                FeeDistributionReceiver {
+                    owner: Pubkey::new_unique(),
+                    fee_percentage: 30,
+                },
+            ],
+            insurance_fund_fee_percentage: 10,
+            ..LendingMarket::default()
+        };
+
+        let mut data = [0u8; LendingMarket::LEN];
+        LendingMarket::pack(lending_market.clone(), &mut data).unwrap();
+        assert_eq!(LendingMarket::unpack(&data).unwrap(), lending_market);
+    }
+
+    #[test]
+    fn fee_distribution_calculation() {
+        let mut lending_market = LendingMarket::default();
+        assert_eq!(
+            lending_market.calculate_fee_distribution(1_001).unwrap(),
+            (vec![], 1_001)
+        );
+
+        lending_market.fee_distribution = vec![
+            FeeDistributionReceiver {
+                owner: Pubkey::new_unique(),
+                fee_percentage: 20,
+            },
+            FeeDistributionReceiver {
+                owner: Pubkey::new_unique(),
+                fee_percentage: 30,
+            },
+        ];
+        // rounding dust stays with the reserve fee receiver
+        assert_eq!(
+            lending_market.calculate_fee_distribution(1_001).unwrap(),
+            (vec![200, 300], 501)
+        );
+
+        let (receiver_fees, remaining_fee) =
+            lending_market.calculate_fee_distribution(u64::MAX).unwrap();
+        assert_eq!(receiver_fees.iter().sum::<u64>() + remaining_fee, u64::MAX);
+    }
+}
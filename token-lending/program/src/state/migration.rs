@@ -0,0 +1,237 @@
+use super::*;
+use crate::error::LendingError;
+use solana_program::{
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack},
+};
+
+/// Account state written by older versions of the program which can be upgraded in place.
+/// `Pack::unpack` decodes every earlier version of an account's layout, so migrating only
+/// rewrites the account state at the current version.
+///
+/// Version history:
+///
+/// 1. Initial layouts. Obligations may use the legacy length, which has no room for a referrer.
+/// 2. Obligations use the current length. Legacy length obligations are moved to an account
+///    with the current length by `UpgradeObligation`.
+/// 3. Reserves store their initial collateral ratio, which was previously fixed at 1 for every
+///    reserve. Older reserves unpack with a ratio of 1, which migrating writes to the account.
+pub trait Migrate: Pack + IsInitialized {
+    /// Version of the account state
+    fn version(&self) -> u8;
+
+    /// Set the version of the account state
+    fn set_version(&mut self, version: u8);
+
+    /// Upgrade the account state from `version` to `version + 1`
+    fn migrate_from(&mut self, version: u8) -> ProgramResult;
+
+    /// Upgrade the account state to the current program version, returning false if it is
+    /// already up to date
+    fn migrate(&mut self) -> Result<bool, ProgramError> {
+        let version = self.version();
+        if version == UNINITIALIZED_VERSION {
+            msg!("Uninitialized accounts cannot be migrated");
+            return Err(ProgramError::UninitializedAccount);
+        }
+        if version >= PROGRAM_VERSION {
+            return Ok(false);
+        }
+        for version in version..PROGRAM_VERSION {
+            self.migrate_from(version)?;
+        }
+        self.set_version(PROGRAM_VERSION);
+        Ok(true)
+    }
+}
+
+fn unknown_version(version: u8) -> ProgramError {
+    msg!("Account version {} has no migration", version);
+    LendingError::InvalidAccountInput.into()
+}
+
+impl Migrate for LendingMarket {
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+
+    fn migrate_from(&mut self, version: u8) -> ProgramResult {
+        match version {
+            1 | 2 => Ok(()),
+            _ => Err(unknown_version(version)),
+        }
+    }
+}
+
+impl Migrate for Reserve {
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+
+    fn migrate_from(&mut self, version: u8) -> ProgramResult {
+        match version {
+            1 | 2 => Ok(()),
+            _ => Err(unknown_version(version)),
+        }
+    }
+}
+
+impl Migrate for Obligation {
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+
+    fn migrate_from(&mut self, version: u8) -> ProgramResult {
+        match version {
+            1 | 2 => Ok(()),
+            _ => Err(unknown_version(version)),
+        }
+    }
+}
+
+impl Migrate for FlashLoanWhitelist {
+    fn version(&self) -> u8 {
+        self.version
+    }
+
+    fn set_version(&mut self, version: u8) {
+        self.version = version;
+    }
+
+    fn migrate_from(&mut self, version: u8) -> ProgramResult {
+        match version {
+            1 | 2 => Ok(()),
+            _ => Err(unknown_version(version)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::math::Rate;
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn migrate_reserve() {
+        let mut reserve = Reserve {
+            version: 1,
+            last_update: LastUpdate::new(42),
+            ..Reserve::default()
+        };
+        reserve.last_update.update_slot(42);
+
+        // version 1 reserves have zeroed padding in place of the initial collateral ratio
+        let mut data = [0u8; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut data).unwrap();
+        let mut migrated = Reserve::unpack(&data).unwrap();
+        reserve.collateral.initial_collateral_ratio = 1;
+        assert_eq!(migrated, reserve);
+
+        // an empty reserve prices its collateral at the fixed ratio before it is migrated
+        assert_eq!(
+            Rate::from(migrated.collateral_exchange_rate().unwrap()),
+            Rate::one()
+        );
+
+        assert!(migrated.migrate().unwrap());
+        assert_eq!(migrated.version, PROGRAM_VERSION);
+        assert_eq!(
+            Reserve {
+                version: PROGRAM_VERSION,
+                ..reserve
+            },
+            migrated
+        );
+
+        // the ratio is written to the account once it is migrated
+        Reserve::pack(migrated.clone(), &mut data).unwrap();
+        assert_eq!(Reserve::unpack(&data).unwrap(), migrated);
+
+        // already up to date
+        assert!(!migrated.migrate().unwrap());
+    }
+
+    #[test]
+    fn migrate_lending_market() {
+        let mut lending_market = LendingMarket {
+            version: 1,
+            owner: Pubkey::new_unique(),
+            ..LendingMarket::default()
+        };
+        assert!(lending_market.migrate().unwrap());
+        assert_eq!(lending_market.version, PROGRAM_VERSION);
+        assert!(!lending_market.migrate().unwrap());
+    }
+
+    #[test]
+    fn migrate_legacy_obligation() {
+        let obligation = Obligation {
+            version: 1,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral::new(Pubkey::new_unique())],
+            ..Obligation::default()
+        };
+
+        let mut data = [0u8; LEGACY_OBLIGATION_LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        let mut migrated = Obligation::unpack(&data).unwrap();
+        assert_eq!(migrated, obligation);
+
+        // legacy length obligations are migrated in place at their length
+        assert!(migrated.migrate().unwrap());
+        Obligation::pack(migrated, &mut data).unwrap();
+        assert_eq!(
+            Obligation::unpack(&data).unwrap(),
+            Obligation {
+                version: PROGRAM_VERSION,
+                ..obligation
+            }
+        );
+    }
+
+    #[test]
+    fn migrate_unknown_version() {
+        let mut data = [0u8; LendingMarket::LEN];
+        LendingMarket::pack(
+            LendingMarket {
+                version: 1,
+                ..LendingMarket::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        data[0] = PROGRAM_VERSION + 1;
+        assert_eq!(
+            LendingMarket::unpack(&data).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+    }
+
+    #[test]
+    fn migrate_uninitialized() {
+        assert_eq!(
+            Obligation::default().migrate().unwrap_err(),
+            ProgramError::UninitializedAccount
+        );
+        assert_eq!(
+            Obligation::unpack(&[0u8; Obligation::LEN]).unwrap_err(),
+            ProgramError::UninitializedAccount
+        );
+    }
+}
@@ -1,25 +1,27 @@
 //! State types
 
+mod flash_loan_whitelist;
 mod last_update;
 mod lending_market;
+mod migration;
 mod obligation;
+mod rate_limiter;
 mod reserve;
 
+pub use flash_loan_whitelist::*;
 pub use last_update::*;
 pub use lending_market::*;
+pub use migration::*;
 pub use obligation::*;
+pub use rate_limiter::*;
 pub use reserve::*;
 
-use crate::math::{Decimal, WAD};
+use crate::math::Decimal;
 use solana_program::{msg, program_error::ProgramError};
 
-/// Collateral tokens are initially valued at a ratio of 5:1 (collateral:liquidity)
-// @FIXME: restore to 5
-pub const INITIAL_COLLATERAL_RATIO: u64 = 1;
-const INITIAL_COLLATERAL_RATE: u64 = INITIAL_COLLATERAL_RATIO * WAD;
-
-/// Current version of the program and all new accounts created
-pub const PROGRAM_VERSION: u8 = 1;
+/// Current version of the program and all new accounts created. Accounts written by older
+/// versions are upgraded with `MigrateAccount`, see [Migrate](trait.Migrate.html).
+pub const PROGRAM_VERSION: u8 = 3;
 
 /// Accounts are created with data zeroed out, so uninitialized state instances
 /// will have the version set to 0.
@@ -55,16 +57,3 @@ fn unpack_bool(src: &[u8; 1]) -> Result<bool, ProgramError> {
         }
     }
 }
-
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn initial_collateral_rate_sanity() {
-        assert_eq!(
-            INITIAL_COLLATERAL_RATIO.checked_mul(WAD).unwrap(),
-            INITIAL_COLLATERAL_RATE
-        );
-    }
-}
@@ -18,7 +18,20 @@ use std::{
 };
 
 /// Max number of collateral and liquidity reserve accounts combined for an obligation
-pub const MAX_OBLIGATION_RESERVES: usize = 10;
+pub const MAX_OBLIGATION_RESERVES: usize = 20;
+/// Max number of collateral and liquidity reserve accounts combined for a legacy obligation
+pub const LEGACY_MAX_OBLIGATION_RESERVES: usize = 10;
+
+/// Obligation delegate can deposit collateral
+pub const OBLIGATION_DELEGATE_DEPOSIT: u8 = 1 << 0;
+/// Obligation delegate can withdraw collateral to token accounts owned by the obligation owner
+pub const OBLIGATION_DELEGATE_WITHDRAW: u8 = 1 << 1;
+/// Obligation delegate can borrow up to the allowed borrow value, to token accounts owned by the
+/// obligation owner
+pub const OBLIGATION_DELEGATE_BORROW: u8 = 1 << 2;
+/// All obligation delegate permissions
+pub const OBLIGATION_DELEGATE_ALL: u8 =
+    OBLIGATION_DELEGATE_DEPOSIT | OBLIGATION_DELEGATE_WITHDRAW | OBLIGATION_DELEGATE_BORROW;
 
 /// Lending market obligation state
 #[derive(Clone, Debug, Default, PartialEq)]
@@ -43,6 +56,12 @@ pub struct Obligation {
     pub allowed_borrow_value: Decimal,
     /// The dangerous borrow value at the weighted average liquidation threshold
     pub unhealthy_borrow_value: Decimal,
+    /// Delegate authority which can act on the obligation for the owner
+    pub delegate: Pubkey,
+    /// Bitmask of `OBLIGATION_DELEGATE_*` permissions granted to the delegate
+    pub delegate_permissions: u8,
+    /// Referrer which accrues the host fees of the obligation's borrows
+    pub referrer: Pubkey,
 }
 
 impl Obligation {
@@ -61,6 +80,14 @@ impl Obligation {
         self.owner = params.owner;
         self.deposits = params.deposits;
         self.borrows = params.borrows;
+        self.referrer = params.referrer;
+    }
+
+    /// Check whether the delegate has been granted all of the given permissions
+    pub fn has_delegate_permission(&self, delegate: &Pubkey, permissions: u8) -> bool {
+        self.delegate != Pubkey::default()
+            && &self.delegate == delegate
+            && self.delegate_permissions & permissions == permissions
     }
 
     /// Calculate the current ratio of borrowed value to deposited value
@@ -111,15 +138,18 @@ impl Obligation {
         self.allowed_borrow_value.try_sub(self.borrowed_value)
     }
 
+    /// Calculate the maximum borrow value that can be liquidated across all borrows
+    pub fn max_liquidation_value(&self) -> Result<Decimal, ProgramError> {
+        self.borrowed_value
+            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))
+    }
+
     /// Calculate the maximum liquidation amount for a given liquidity
     pub fn max_liquidation_amount(
         &self,
         liquidity: &ObligationLiquidity,
     ) -> Result<Decimal, ProgramError> {
-        let max_liquidation_value = self
-            .borrowed_value
-            .try_mul(Rate::from_percent(LIQUIDATION_CLOSE_FACTOR))?
-            .min(liquidity.market_value);
+        let max_liquidation_value = self.max_liquidation_value()?.min(liquidity.market_value);
         let max_liquidation_pct = max_liquidation_value.try_div(liquidity.market_value)?;
         liquidity.borrowed_amount_wads.try_mul(max_liquidation_pct)
     }
@@ -235,6 +265,8 @@ pub struct InitObligationParams {
     pub deposits: Vec<ObligationCollateral>,
     /// Borrowed liquidity for the obligation, unique by borrow reserve address
     pub borrows: Vec<ObligationLiquidity>,
+    /// Referrer which accrues the host fees of the obligation's borrows
+    pub referrer: Pubkey,
 }
 
 impl Sealed for Obligation {}
@@ -346,13 +378,55 @@ impl ObligationLiquidity {
 
 const OBLIGATION_COLLATERAL_LEN: usize = 88; // 32 + 8 + 16 + 32
 const OBLIGATION_LIQUIDITY_LEN: usize = 112; // 32 + 16 + 16 + 16 + 32
-const OBLIGATION_LEN: usize = 1300; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 64 + 1 + 1 + (88 * 1) + (112 * 9)
+const OBLIGATION_HEADER_LEN: usize = 204; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 32 + 1 + 31 + 1 + 1
+const OBLIGATION_LEN: usize = 2452; // 204 + 32 + (88 * 1) + (112 * 19)
                                     // @TODO: break this up by obligation / collateral / liquidity https://git.io/JOCca
+/// Length of an obligation account created before the obligation layout was enlarged
+pub const LEGACY_OBLIGATION_LEN: usize = 1300; // 204 + (88 * 1) + (112 * 9)
+
+impl Obligation {
+    /// Max number of collateral and liquidity reserve accounts combined that fit in an
+    /// obligation account of the given length
+    pub fn max_reserves(data_len: usize) -> Result<usize, ProgramError> {
+        match data_len {
+            OBLIGATION_LEN => Ok(MAX_OBLIGATION_RESERVES),
+            LEGACY_OBLIGATION_LEN => Ok(LEGACY_MAX_OBLIGATION_RESERVES),
+            _ => {
+                msg!("Obligation account has an unexpected length");
+                Err(ProgramError::InvalidAccountData)
+            }
+        }
+    }
+}
+
 impl Pack for Obligation {
     const LEN: usize = OBLIGATION_LEN;
 
+    fn unpack_unchecked(input: &[u8]) -> Result<Self, ProgramError> {
+        Self::max_reserves(input.len())?;
+        Self::unpack_from_slice(input)
+    }
+
+    fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
+        if src.deposits.len() + src.borrows.len() > Self::max_reserves(dst.len())? {
+            msg!(
+                "Obligation account cannot hold more than {} deposits and borrows combined",
+                Self::max_reserves(dst.len())?
+            );
+            return Err(LendingError::ObligationReserveLimit.into());
+        }
+        if src.referrer != Pubkey::default() && dst.len() != OBLIGATION_LEN {
+            msg!("Legacy obligation accounts cannot record a referrer");
+            return Err(LendingError::InvalidAccountInput.into());
+        }
+        src.pack_into_slice(dst);
+        Ok(())
+    }
+
     fn pack_into_slice(&self, dst: &mut [u8]) {
-        let output = array_mut_ref![dst, 0, OBLIGATION_LEN];
+        let has_referrer = dst.len() == OBLIGATION_LEN;
+        let (output, data_flat) = dst.split_at_mut(OBLIGATION_HEADER_LEN);
+        let output = array_mut_ref![output, 0, OBLIGATION_HEADER_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -364,10 +438,11 @@ impl Pack for Obligation {
             borrowed_value,
             allowed_borrow_value,
             unhealthy_borrow_value,
+            delegate,
+            delegate_permissions,
             _padding,
             deposits_len,
             borrows_len,
-            data_flat,
         ) = mut_array_refs![
             output,
             1,
@@ -379,10 +454,11 @@ impl Pack for Obligation {
             16,
             16,
             16,
-            64,
+            PUBKEY_BYTES,
             1,
+            31,
             1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            1
         ];
 
         // obligation
@@ -395,9 +471,20 @@ impl Pack for Obligation {
         pack_decimal(self.borrowed_value, borrowed_value);
         pack_decimal(self.allowed_borrow_value, allowed_borrow_value);
         pack_decimal(self.unhealthy_borrow_value, unhealthy_borrow_value);
+        delegate.copy_from_slice(self.delegate.as_ref());
+        *delegate_permissions = self.delegate_permissions.to_le_bytes();
         *deposits_len = u8::try_from(self.deposits.len()).unwrap().to_le_bytes();
         *borrows_len = u8::try_from(self.borrows.len()).unwrap().to_le_bytes();
 
+        // referrer
+        let data_flat = if has_referrer {
+            let (referrer, data_flat) = data_flat.split_at_mut(PUBKEY_BYTES);
+            referrer.copy_from_slice(self.referrer.as_ref());
+            data_flat
+        } else {
+            data_flat
+        };
+
         let mut offset = 0;
 
         // deposits
@@ -436,7 +523,14 @@ impl Pack for Obligation {
 
     /// Unpacks a byte buffer into an [ObligationInfo](struct.ObligationInfo.html).
     fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
-        let input = array_ref![src, 0, OBLIGATION_LEN];
+        let (input, data_flat) = src.split_at(OBLIGATION_HEADER_LEN);
+        let (referrer, data_flat) = if src.len() == OBLIGATION_LEN {
+            let (referrer, data_flat) = data_flat.split_at(PUBKEY_BYTES);
+            (Pubkey::new(referrer), data_flat)
+        } else {
+            (Pubkey::default(), data_flat)
+        };
+        let input = array_ref![input, 0, OBLIGATION_HEADER_LEN];
         #[allow(clippy::ptr_offset_with_cast)]
         let (
             version,
@@ -448,10 +542,11 @@ impl Pack for Obligation {
             borrowed_value,
             allowed_borrow_value,
             unhealthy_borrow_value,
+            delegate,
+            delegate_permissions,
             _padding,
             deposits_len,
             borrows_len,
-            data_flat,
         ) = array_refs![
             input,
             1,
@@ -463,13 +558,15 @@ impl Pack for Obligation {
             16,
             16,
             16,
-            64,
+            PUBKEY_BYTES,
             1,
+            31,
             1,
-            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+            1
         ];
 
         let version = u8::from_le_bytes(*version);
+        // older versions share the current layout and are upgraded by `Migrate`
         if version > PROGRAM_VERSION {
             msg!("Obligation version does not match lending program version");
             return Err(ProgramError::InvalidAccountData);
@@ -477,6 +574,10 @@ impl Pack for Obligation {
 
         let deposits_len = u8::from_le_bytes(*deposits_len);
         let borrows_len = u8::from_le_bytes(*borrows_len);
+        if deposits_len as usize + borrows_len as usize > Self::max_reserves(src.len())? {
+            msg!("Obligation deposits and borrows exceed the account capacity");
+            return Err(ProgramError::InvalidAccountData);
+        }
         let mut deposits = Vec::with_capacity(deposits_len as usize + 1);
         let mut borrows = Vec::with_capacity(borrows_len as usize + 1);
 
@@ -526,6 +627,9 @@ impl Pack for Obligation {
             borrowed_value: unpack_decimal(borrowed_value),
             allowed_borrow_value: unpack_decimal(allowed_borrow_value),
             unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+            delegate: Pubkey::new_from_array(*delegate),
+            delegate_permissions: u8::from_le_bytes(*delegate_permissions),
+            referrer,
         })
     }
 }
@@ -533,7 +637,7 @@ impl Pack for Obligation {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::math::TryAdd;
+    use crate::math::{TryAdd, WAD};
     use proptest::prelude::*;
 
     const MAX_COMPOUNDED_INTEREST: u64 = 100; // 10,000%
@@ -569,6 +673,98 @@ mod test {
         );
     }
 
+    fn obligation_with_reserves(deposits_len: usize, borrows_len: usize) -> Obligation {
+        Obligation {
+            version: PROGRAM_VERSION,
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: (0..deposits_len)
+                .map(|_| ObligationCollateral::new(Pubkey::new_unique()))
+                .collect(),
+            borrows: (0..borrows_len)
+                .map(|_| ObligationLiquidity::new(Pubkey::new_unique(), Decimal::one()))
+                .collect(),
+            ..Obligation::default()
+        }
+    }
+
+    #[test]
+    fn obligation_pack_max_reserves() {
+        let obligation = obligation_with_reserves(1, MAX_OBLIGATION_RESERVES - 1);
+        let mut data = [0u8; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        assert_eq!(Obligation::unpack(&data).unwrap(), obligation);
+
+        let obligation = obligation_with_reserves(1, MAX_OBLIGATION_RESERVES);
+        assert_eq!(
+            Obligation::pack(obligation, &mut data),
+            Err(LendingError::ObligationReserveLimit.into())
+        );
+    }
+
+    #[test]
+    fn obligation_pack_legacy_len() {
+        let obligation = obligation_with_reserves(3, LEGACY_MAX_OBLIGATION_RESERVES - 3);
+        let mut data = [0u8; LEGACY_OBLIGATION_LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        assert_eq!(Obligation::unpack(&data).unwrap(), obligation);
+
+        let obligation = obligation_with_reserves(3, LEGACY_MAX_OBLIGATION_RESERVES - 2);
+        assert_eq!(
+            Obligation::pack(obligation, &mut data),
+            Err(LendingError::ObligationReserveLimit.into())
+        );
+
+        assert_eq!(
+            Obligation::unpack(&data[..LEGACY_OBLIGATION_LEN - 1]),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[test]
+    fn obligation_pack_referrer() {
+        let mut obligation = obligation_with_reserves(1, MAX_OBLIGATION_RESERVES - 1);
+        obligation.referrer = Pubkey::new_unique();
+        let mut data = [0u8; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        assert_eq!(Obligation::unpack(&data).unwrap(), obligation);
+
+        let mut obligation = obligation_with_reserves(1, LEGACY_MAX_OBLIGATION_RESERVES - 1);
+        obligation.referrer = Pubkey::new_unique();
+        let mut data = [0u8; LEGACY_OBLIGATION_LEN];
+        assert_eq!(
+            Obligation::pack(obligation.clone(), &mut data),
+            Err(LendingError::InvalidAccountInput.into())
+        );
+
+        obligation.referrer = Pubkey::default();
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        assert_eq!(Obligation::unpack(&data).unwrap(), obligation);
+    }
+
+    #[test]
+    fn obligation_delegate_permission() {
+        let delegate = Pubkey::new_unique();
+        let mut obligation = obligation_with_reserves(0, 0);
+        assert!(!obligation.has_delegate_permission(&Pubkey::default(), 0));
+        assert!(!obligation.has_delegate_permission(&delegate, OBLIGATION_DELEGATE_DEPOSIT));
+
+        obligation.delegate = delegate;
+        obligation.delegate_permissions =
+            OBLIGATION_DELEGATE_DEPOSIT | OBLIGATION_DELEGATE_WITHDRAW;
+        assert!(obligation.has_delegate_permission(&delegate, OBLIGATION_DELEGATE_DEPOSIT));
+        assert!(obligation.has_delegate_permission(&delegate, OBLIGATION_DELEGATE_WITHDRAW));
+        assert!(!obligation.has_delegate_permission(&delegate, OBLIGATION_DELEGATE_BORROW));
+        assert!(!obligation.has_delegate_permission(&delegate, OBLIGATION_DELEGATE_ALL));
+        assert!(
+            !obligation.has_delegate_permission(&Pubkey::new_unique(), OBLIGATION_DELEGATE_DEPOSIT)
+        );
+
+        let mut data = [0u8; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        assert_eq!(Obligation::unpack(&data).unwrap(), obligation);
+    }
+
     // Creates rates (r1, r2) where 0 < r1 <= r2 <= 100*r1
     prop_compose! {
         fn cumulative_rates()(rate in 1..=u128::MAX)(
@@ -0,0 +1,173 @@
+use crate::{
+    error::LendingError,
+    math::{Decimal, TryAdd, TryDiv, TryMul},
+};
+use solana_program::{clock::Slot, entrypoint::ProgramResult, msg, program_error::ProgramError};
+
+/// Rate limiter configuration
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RateLimiterConfig {
+    /// Length of the sliding window in slots, 0 disables the rate limiter
+    pub window_duration: u64,
+    /// Maximum value withdrawn and borrowed within the window, in the lending market's
+    /// quote currency
+    pub max_outflow: u64,
+}
+
+/// Sliding window limit on the value flowing out of a reserve
+///
+/// Outflows are bucketed into fixed windows. The outflow over the sliding window ending at the
+/// current slot is approximated by the current window's outflow plus the previous window's
+/// outflow, weighted by the share of the previous window still inside the sliding window.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RateLimiter {
+    /// Rate limiter configuration
+    pub config: RateLimiterConfig,
+    /// First slot of the current window
+    pub window_start: Slot,
+    /// Outflow during the previous window
+    pub previous_outflow: Decimal,
+    /// Outflow during the current window
+    pub current_outflow: Decimal,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter with an empty window
+    pub fn new(config: RateLimiterConfig) -> Self {
+        Self {
+            config,
+            ..Self::default()
+        }
+    }
+
+    /// Check whether the rate limiter is enabled
+    pub fn is_enabled(&self) -> bool {
+        self.config.window_duration != 0
+    }
+
+    /// Record an outflow, failing if it would exceed the maximum outflow within the window
+    pub fn update(&mut self, current_slot: Slot, outflow: Decimal) -> ProgramResult {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        self.advance_window(current_slot)?;
+
+        let window_outflow = self.window_outflow(current_slot)?.try_add(outflow)?;
+        if window_outflow > Decimal::from(self.config.max_outflow) {
+            msg!(
+                "Outflow of {} would exceed the maximum outflow of {} over {} slots",
+                window_outflow,
+                self.config.max_outflow,
+                self.config.window_duration
+            );
+            return Err(LendingError::OutflowRateLimitExceeded.into());
+        }
+
+        self.current_outflow = self.current_outflow.try_add(outflow)?;
+        Ok(())
+    }
+
+    /// Roll the current window forward to the window containing the current slot
+    fn advance_window(&mut self, current_slot: Slot) -> ProgramResult {
+        let slots_elapsed = current_slot
+            .checked_sub(self.window_start)
+            .ok_or(LendingError::MathOverflow)?;
+        let windows_elapsed = slots_elapsed / self.config.window_duration;
+        if windows_elapsed == 0 {
+            return Ok(());
+        }
+
+        self.previous_outflow = if windows_elapsed == 1 {
+            self.current_outflow
+        } else {
+            Decimal::zero()
+        };
+        self.current_outflow = Decimal::zero();
+        self.window_start = current_slot - slots_elapsed % self.config.window_duration;
+        Ok(())
+    }
+
+    /// Approximate outflow over the sliding window ending at the current slot
+    fn window_outflow(&self, current_slot: Slot) -> Result<Decimal, ProgramError> {
+        let slots_into_window = current_slot
+            .checked_sub(self.window_start)
+            .ok_or(LendingError::MathOverflow)?;
+        let previous_weight = Decimal::from(
+            self.config
+                .window_duration
+                .saturating_sub(slots_into_window),
+        )
+        .try_div(self.config.window_duration)?;
+        self.previous_outflow
+            .try_mul(previous_weight)?
+            .try_add(self.current_outflow)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rate_limiter(window_duration: u64, max_outflow: u64) -> RateLimiter {
+        RateLimiter::new(RateLimiterConfig {
+            window_duration,
+            max_outflow,
+        })
+    }
+
+    fn assert_exceeded(result: ProgramResult) {
+        assert_eq!(result, Err(LendingError::OutflowRateLimitExceeded.into()));
+    }
+
+    #[test]
+    fn disabled_rate_limiter() {
+        let mut rate_limiter = rate_limiter(0, 0);
+        rate_limiter.update(10, Decimal::from(u64::MAX)).unwrap();
+        assert_eq!(rate_limiter.current_outflow, Decimal::zero());
+    }
+
+    #[test]
+    fn outflow_within_window() {
+        let mut rate_limiter = rate_limiter(10, 100);
+        rate_limiter.update(100, Decimal::from(60u64)).unwrap();
+        rate_limiter.update(105, Decimal::from(40u64)).unwrap();
+        assert_exceeded(rate_limiter.update(109, Decimal::from(1u64)));
+        assert_eq!(rate_limiter.window_start, 100);
+        assert_eq!(rate_limiter.current_outflow, Decimal::from(100u64));
+    }
+
+    #[test]
+    fn previous_window_is_weighted() {
+        let mut rate_limiter = rate_limiter(10, 100);
+        rate_limiter.update(100, Decimal::from(100u64)).unwrap();
+
+        // 3 slots into the next window, 70% of the previous outflow still counts
+        assert_exceeded(rate_limiter.update(113, Decimal::from(31u64)));
+        rate_limiter.update(113, Decimal::from(30u64)).unwrap();
+        assert_eq!(rate_limiter.window_start, 110);
+        assert_eq!(rate_limiter.previous_outflow, Decimal::from(100u64));
+        assert_eq!(rate_limiter.current_outflow, Decimal::from(30u64));
+
+        // 8 slots into the next window, 20% of the previous outflow still counts
+        rate_limiter.update(118, Decimal::from(50u64)).unwrap();
+        assert_exceeded(rate_limiter.update(118, Decimal::from(1u64)));
+    }
+
+    #[test]
+    fn window_resets_after_full_window_elapsed() {
+        let mut rate_limiter = rate_limiter(10, 100);
+        rate_limiter.update(100, Decimal::from(100u64)).unwrap();
+        rate_limiter.update(125, Decimal::from(100u64)).unwrap();
+        assert_eq!(rate_limiter.window_start, 120);
+        assert_eq!(rate_limiter.previous_outflow, Decimal::zero());
+        assert_eq!(rate_limiter.current_outflow, Decimal::from(100u64));
+    }
+
+    #[test]
+    fn zero_max_outflow_blocks_outflows() {
+        let mut rate_limiter = rate_limiter(10, 0);
+        rate_limiter.update(100, Decimal::zero()).unwrap();
+        assert_exceeded(rate_limiter.update(100, Decimal::from(1u64)));
+    }
+}
@@ -38,6 +38,12 @@ pub struct Reserve {
     pub collateral: ReserveCollateral,
     /// Reserve configuration values
     pub config: ReserveConfig,
+    /// Percentage of protocol fees set aside for the insurance fund, copied from the lending market
+    pub insurance_fund_fee_percentage: u8,
+    /// Lifecycle status of the reserve
+    pub status: ReserveStatus,
+    /// Limit on the value withdrawn and borrowed from the reserve over a sliding window
+    pub rate_limiter: RateLimiter,
 }
 
 impl Reserve {
@@ -56,30 +62,74 @@ impl Reserve {
         self.liquidity = params.liquidity;
         self.collateral = params.collateral;
         self.config = params.config;
+        self.status = ReserveStatus::Active;
     }
 
-    /// Record deposited liquidity and return amount of collateral tokens to mint
+    /// Check whether the reserve accepts new deposits and borrows
+    pub fn is_active(&self) -> bool {
+        self.status == ReserveStatus::Active
+    }
+
+    /// Check whether the reserve can be closed: no liquidity, collateral, borrows or fees are left
+    pub fn is_empty(&self) -> Result<bool, ProgramError> {
+        Ok(self.liquidity.available_amount == 0
+            && self.liquidity.borrowed_amount_wads.try_floor_u64()? == 0
+            && self
+                .liquidity
+                .accumulated_protocol_fees_wads
+                .try_floor_u64()?
+                == 0
+            && self
+                .liquidity
+                .accumulated_insurance_fund_wads
+                .try_floor_u64()?
+                == 0
+            && self.collateral.mint_total_supply == 0)
+    }
+
+    /// Record deposited liquidity and return amount of collateral tokens to mint. The deposit fee
+    /// is kept in the reserve as protocol fees.
     pub fn deposit_liquidity(&mut self, liquidity_amount: u64) -> Result<u64, ProgramError> {
-        let collateral_amount = self
-            .collateral_exchange_rate()?
-            .liquidity_to_collateral(liquidity_amount)?;
+        let deposit_fee = self.config.fees.calculate_deposit_fee(liquidity_amount)?;
+        let collateral_amount = self.collateral_exchange_rate()?.liquidity_to_collateral(
+            liquidity_amount
+                .checked_sub(deposit_fee)
+                .ok_or(LendingError::MathOverflow)?,
+        )?;
 
         self.liquidity.deposit(liquidity_amount)?;
+        self.liquidity.add_protocol_fees(deposit_fee)?;
         self.collateral.mint(collateral_amount)?;
 
         Ok(collateral_amount)
     }
 
-    /// Record redeemed collateral and return amount of liquidity to withdraw
+    /// Record redeemed collateral and return amount of liquidity to withdraw. The redeem fee is
+    /// kept in the reserve as protocol fees.
     pub fn redeem_collateral(&mut self, collateral_amount: u64) -> Result<u64, ProgramError> {
-        let collateral_exchange_rate = self.collateral_exchange_rate()?;
-        let liquidity_amount =
-            collateral_exchange_rate.collateral_to_liquidity(collateral_amount)?;
+        let (withdraw_amount, redeem_fee) = self.calculate_redeem_collateral(collateral_amount)?;
 
         self.collateral.burn(collateral_amount)?;
-        self.liquidity.withdraw(liquidity_amount)?;
+        self.liquidity.withdraw(withdraw_amount)?;
+        self.liquidity.add_protocol_fees(redeem_fee)?;
+
+        Ok(withdraw_amount)
+    }
 
-        Ok(liquidity_amount)
+    /// Calculate the liquidity withdrawn for redeemed collateral and the redeem fee kept by the
+    /// reserve
+    pub fn calculate_redeem_collateral(
+        &self,
+        collateral_amount: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        let liquidity_amount = self
+            .collateral_exchange_rate()?
+            .collateral_to_liquidity(collateral_amount)?;
+        let redeem_fee = self.config.fees.calculate_redeem_fee(liquidity_amount)?;
+        let withdraw_amount = liquidity_amount
+            .checked_sub(redeem_fee)
+            .ok_or(LendingError::MathOverflow)?;
+        Ok((withdraw_amount, redeem_fee))
     }
 
     /// Calculate the current borrow rate
@@ -139,14 +189,110 @@ impl Reserve {
         self.collateral.exchange_rate(total_liquidity)
     }
 
+    /// Calculate the market value of an amount of liquidity
+    pub fn market_value(&self, liquidity_amount: Decimal) -> Result<Decimal, ProgramError> {
+        // @TODO: add lookup table https://git.io/JOCYq
+        let decimals = 10u64
+            .checked_pow(self.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        liquidity_amount
+            .try_mul(self.liquidity.market_price)?
+            .try_div(decimals)
+    }
+
+    /// Calculate the amount of liquidity worth a market value
+    pub fn liquidity_amount_for_value(
+        &self,
+        market_value: Decimal,
+    ) -> Result<Decimal, ProgramError> {
+        // @TODO: add lookup table https://git.io/JOCYq
+        let decimals = 10u64
+            .checked_pow(self.liquidity.mint_decimals as u32)
+            .ok_or(LendingError::MathOverflow)?;
+        market_value
+            .try_mul(decimals)?
+            .try_div(self.liquidity.market_price)
+    }
+
+    /// Liquidity that can still be borrowed before reaching the reserve borrow limits
+    pub fn remaining_borrow_capacity(&self) -> Result<Decimal, ProgramError> {
+        let remaining_borrow = Decimal::from(self.config.borrow_limit)
+            .try_sub(self.liquidity.borrowed_amount_wads)
+            .unwrap_or_else(|_| Decimal::zero());
+        if self.config.borrow_limit_value == 0 {
+            return Ok(remaining_borrow);
+        }
+
+        let remaining_borrow_value = Decimal::from(self.config.borrow_limit_value)
+            .try_sub(self.market_value(self.liquidity.borrowed_amount_wads)?)
+            .unwrap_or_else(|_| Decimal::zero());
+        Ok(remaining_borrow.min(self.liquidity_amount_for_value(remaining_borrow_value)?))
+    }
+
+    /// Check whether the reserve liquidity supply would exceed the deposit value limit after
+    /// depositing a liquidity amount
+    pub fn exceeds_deposit_value_limit(&self, liquidity_amount: u64) -> Result<bool, ProgramError> {
+        if self.config.deposit_limit_value == 0 {
+            return Ok(false);
+        }
+
+        let total_supply_value = self.market_value(
+            Decimal::from(liquidity_amount).try_add(self.liquidity.total_supply()?)?,
+        )?;
+        Ok(total_supply_value > Decimal::from(self.config.deposit_limit_value))
+    }
+
+    /// Liquidity an obligation can still borrow before reaching the per-obligation borrow limit
+    pub fn remaining_obligation_borrow_capacity(
+        &self,
+        obligation_borrowed_amount: Decimal,
+    ) -> Decimal {
+        if self.config.max_obligation_borrow == 0 {
+            return Decimal::from(u64::MAX);
+        }
+
+        Decimal::from(self.config.max_obligation_borrow)
+            .try_sub(obligation_borrowed_amount)
+            .unwrap_or_else(|_| Decimal::zero())
+    }
+
+    /// Check whether an obligation's deposited collateral exceeds its maximum share of the
+    /// reserve collateral
+    pub fn exceeds_obligation_deposit_limit(
+        &self,
+        obligation_deposited_amount: u64,
+    ) -> Result<bool, ProgramError> {
+        if self.config.max_obligation_deposit_percentage == 0 {
+            return Ok(false);
+        }
+
+        let max_deposited_amount = Decimal::from(self.collateral.mint_total_supply).try_mul(
+            Rate::from_percent(self.config.max_obligation_deposit_percentage),
+        )?;
+        Ok(Decimal::from(obligation_deposited_amount) > max_deposited_amount)
+    }
+
+    /// Calculate the market value of an amount of collateral
+    pub fn collateral_market_value(&self, collateral_amount: u64) -> Result<Decimal, ProgramError> {
+        let liquidity_amount = self
+            .collateral_exchange_rate()?
+            .decimal_collateral_to_liquidity(collateral_amount.into())?;
+        self.market_value(liquidity_amount)
+    }
+
     /// Update borrow rate and accrue interest
     pub fn accrue_interest(&mut self, current_slot: Slot) -> ProgramResult {
         let slots_elapsed = self.last_update.slots_elapsed(current_slot)?;
         if slots_elapsed > 0 {
             let current_borrow_rate = self.current_borrow_rate()?;
             let take_rate = Rate::from_percent(self.config.protocol_take_rate);
-            self.liquidity
-                .compound_interest(current_borrow_rate, slots_elapsed, take_rate)?;
+            let insurance_fund_rate = Rate::from_percent(self.insurance_fund_fee_percentage);
+            self.liquidity.compound_interest(
+                current_borrow_rate,
+                slots_elapsed,
+                take_rate,
+                insurance_fund_rate,
+            )?;
         }
         Ok(())
     }
@@ -228,6 +374,52 @@ impl Reserve {
         })
     }
 
+    /// Flash loan liquidity up to the available amount. A whitelisted receiver program's flash
+    /// loan fee is capped at the reserve flash loan fee.
+    pub fn calculate_flash_loan(
+        &self,
+        amount_to_flash_loan: u64,
+        whitelisted_fee_wad: Option<u64>,
+    ) -> Result<CalculateFlashLoanResult, ProgramError> {
+        let fees = ReserveFees {
+            flash_loan_fee_wad: whitelisted_fee_wad
+                .map_or(self.config.fees.flash_loan_fee_wad, |whitelisted_fee_wad| {
+                    whitelisted_fee_wad.min(self.config.fees.flash_loan_fee_wad)
+                }),
+            ..self.config.fees
+        };
+
+        if amount_to_flash_loan == u64::MAX {
+            // fees are inclusive as with ordinary borrows
+            let (origination_fee, host_fee) = fees.calculate_flash_loan_fees(
+                self.liquidity.available_amount.into(),
+                FeeCalculation::Inclusive,
+            )?;
+            let flash_loan_amount = self
+                .liquidity
+                .available_amount
+                .checked_sub(origination_fee)
+                .ok_or(LendingError::MathOverflow)?;
+
+            Ok(CalculateFlashLoanResult {
+                flash_loan_amount,
+                origination_fee,
+                host_fee,
+            })
+        } else {
+            let (origination_fee, host_fee) = fees.calculate_flash_loan_fees(
+                amount_to_flash_loan.into(),
+                FeeCalculation::Exclusive,
+            )?;
+
+            Ok(CalculateFlashLoanResult {
+                flash_loan_amount: amount_to_flash_loan,
+                origination_fee,
+                host_fee,
+            })
+        }
+    }
+
     /// Liquidate some or all of an unhealthy obligation
     pub fn calculate_liquidation(
         &self,
@@ -338,6 +530,56 @@ impl Reserve {
                 .try_floor_u64()?,
         ))
     }
+
+    /// Calculate insurance fund redemption accounting for available liquidity and accumulated fees
+    pub fn calculate_redeem_insurance_fees(&self) -> Result<u64, ProgramError> {
+        Ok(min(
+            self.liquidity.available_amount,
+            self.liquidity
+                .accumulated_insurance_fund_wads
+                .try_floor_u64()?,
+        ))
+    }
+
+    /// Write off the remaining debt of an obligation borrow, repaying what the insurance fund can
+    /// cover and socializing the rest across depositors through the collateral exchange rate
+    pub fn write_off_bad_debt(
+        &mut self,
+        bad_debt: Decimal,
+        insurance_fund_amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let insurance_repay_amount = min(bad_debt.try_ceil_u64()?, insurance_fund_amount);
+        let insurance_settle_amount = Decimal::from(insurance_repay_amount).min(bad_debt);
+        self.liquidity
+            .repay(insurance_repay_amount, insurance_settle_amount)?;
+        self.liquidity
+            .forgive(bad_debt.try_sub(insurance_settle_amount)?)?;
+
+        Ok(insurance_repay_amount)
+    }
+
+    /// Convert accumulated protocol fees into collateral as if they were deposited, and return
+    /// the amount of collateral to mint. The collateral exchange rate is unchanged.
+    pub fn collect_protocol_fees(&mut self) -> Result<u64, ProgramError> {
+        let fee_amount = self
+            .liquidity
+            .accumulated_protocol_fees_wads
+            .try_floor_u64()?;
+        let collateral_amount = self
+            .collateral_exchange_rate()?
+            .liquidity_to_collateral(fee_amount)?;
+        if collateral_amount == 0 {
+            return Ok(0);
+        }
+
+        self.liquidity.accumulated_protocol_fees_wads = self
+            .liquidity
+            .accumulated_protocol_fees_wads
+            .try_sub(Decimal::from(fee_amount))?;
+        self.collateral.mint(collateral_amount)?;
+
+        Ok(collateral_amount)
+    }
 }
 
 /// Initialize a reserve
@@ -376,6 +618,17 @@ pub struct CalculateRepayResult {
     pub repay_amount: u64,
 }
 
+/// Calculate flash loan result
+#[derive(Debug)]
+pub struct CalculateFlashLoanResult {
+    /// Amount of liquidity transferred to the flash loan receiver
+    pub flash_loan_amount: u64,
+    /// Total fee owed on top of the flash loan amount, including the host fee
+    pub origination_fee: u64,
+    /// Host fee portion of the origination fee
+    pub host_fee: u64,
+}
+
 /// Calculate liquidation result
 #[derive(Debug)]
 pub struct CalculateLiquidationResult {
@@ -388,6 +641,89 @@ pub struct CalculateLiquidationResult {
     pub withdraw_amount: u64,
 }
 
+/// Liquidation preview result
+#[derive(Debug, PartialEq)]
+pub struct LiquidationPreview {
+    /// Amount of liquidity that is settled from the obligation
+    pub settle_amount: Decimal,
+    /// Amount of liquidity the liquidator repays
+    pub repay_amount: u64,
+    /// Amount of collateral the liquidator receives
+    pub withdraw_amount: u64,
+    /// Amount of liquidity the withdrawn collateral redeems for, net of the redeem fee
+    pub withdraw_liquidity_amount: u64,
+    /// Protocol fee taken from the redeemed liquidity
+    pub protocol_fee: u64,
+}
+
+/// Preview a liquidation without executing it, using the same checks and calculations as the
+/// lending program. The obligation and reserves are expected to be refreshed.
+pub fn preview_liquidation(
+    obligation: &Obligation,
+    repay_reserve_pubkey: &Pubkey,
+    withdraw_reserve_pubkey: &Pubkey,
+    withdraw_reserve: &Reserve,
+    liquidity_amount: u64,
+) -> Result<LiquidationPreview, ProgramError> {
+    if obligation.deposited_value == Decimal::zero() {
+        msg!("Obligation deposited value is zero");
+        return Err(LendingError::ObligationDepositsZero.into());
+    }
+    if obligation.borrowed_value == Decimal::zero() {
+        msg!("Obligation borrowed value is zero");
+        return Err(LendingError::ObligationBorrowsZero.into());
+    }
+    if obligation.borrowed_value < obligation.unhealthy_borrow_value {
+        msg!("Obligation is healthy and cannot be liquidated");
+        return Err(LendingError::ObligationHealthy.into());
+    }
+
+    let (liquidity, _) = obligation.find_liquidity_in_borrows(*repay_reserve_pubkey)?;
+    if liquidity.market_value == Decimal::zero() {
+        msg!("Obligation borrow value is zero");
+        return Err(LendingError::ObligationLiquidityEmpty.into());
+    }
+
+    let (collateral, _) = obligation.find_collateral_in_deposits(*withdraw_reserve_pubkey)?;
+    if collateral.market_value == Decimal::zero() {
+        msg!("Obligation deposit value is zero");
+        return Err(LendingError::ObligationCollateralEmpty.into());
+    }
+
+    let CalculateLiquidationResult {
+        settle_amount,
+        repay_amount,
+        withdraw_amount,
+    } = withdraw_reserve.calculate_liquidation(
+        liquidity_amount,
+        obligation,
+        liquidity,
+        collateral,
+    )?;
+
+    if repay_amount == 0 {
+        msg!("Liquidation is too small to transfer liquidity");
+        return Err(LendingError::LiquidationTooSmall.into());
+    }
+    if withdraw_amount == 0 {
+        msg!("Liquidation is too small to receive collateral");
+        return Err(LendingError::LiquidationTooSmall.into());
+    }
+
+    let (withdraw_liquidity_amount, _redeem_fee) =
+        withdraw_reserve.calculate_redeem_collateral(withdraw_amount)?;
+    let protocol_fee =
+        withdraw_reserve.calculate_protocol_liquidation_fee(withdraw_liquidity_amount)?;
+
+    Ok(LiquidationPreview {
+        settle_amount,
+        repay_amount,
+        withdraw_amount,
+        withdraw_liquidity_amount,
+        protocol_fee,
+    })
+}
+
 /// Reserve liquidity
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ReserveLiquidity {
@@ -409,6 +745,8 @@ pub struct ReserveLiquidity {
     pub cumulative_borrow_rate_wads: Decimal,
     /// Reserve cumulative protocol fees
     pub accumulated_protocol_fees_wads: Decimal,
+    /// Reserve cumulative protocol fees set aside for the insurance fund
+    pub accumulated_insurance_fund_wads: Decimal,
     /// Reserve liquidity market price in quote currency
     pub market_price: Decimal,
 }
@@ -426,6 +764,7 @@ impl ReserveLiquidity {
             borrowed_amount_wads: Decimal::zero(),
             cumulative_borrow_rate_wads: Decimal::one(),
             accumulated_protocol_fees_wads: Decimal::zero(),
+            accumulated_insurance_fund_wads: Decimal::zero(),
             market_price: params.market_price,
         }
     }
@@ -434,7 +773,8 @@ impl ReserveLiquidity {
     pub fn total_supply(&self) -> Result<Decimal, ProgramError> {
         Decimal::from(self.available_amount)
             .try_add(self.borrowed_amount_wads)?
-            .try_sub(self.accumulated_protocol_fees_wads)
+            .try_sub(self.accumulated_protocol_fees_wads)?
+            .try_sub(self.accumulated_insurance_fund_wads)
     }
 
     /// Add liquidity to available amount
@@ -501,6 +841,36 @@ impl ReserveLiquidity {
         Ok(())
     }
 
+    /// Keep fee_amount of available liquidity as protocol fees
+    pub fn add_protocol_fees(&mut self, fee_amount: u64) -> ProgramResult {
+        self.accumulated_protocol_fees_wads = self
+            .accumulated_protocol_fees_wads
+            .try_add(Decimal::from(fee_amount))?;
+
+        Ok(())
+    }
+
+    /// Subtract withdraw_amount from accumulated_insurance_fund_wads and available liquidity
+    pub fn redeem_insurance_fees(&mut self, withdraw_amount: u64) -> ProgramResult {
+        self.available_amount = self
+            .available_amount
+            .checked_sub(withdraw_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        self.accumulated_insurance_fund_wads = self
+            .accumulated_insurance_fund_wads
+            .try_sub(Decimal::from(withdraw_amount))?;
+
+        Ok(())
+    }
+
+    /// Subtract unrecoverable debt from total borrows without repaying it
+    pub fn forgive(&mut self, settle_amount: Decimal) -> ProgramResult {
+        let safe_settle_amount = settle_amount.min(self.borrowed_amount_wads);
+        self.borrowed_amount_wads = self.borrowed_amount_wads.try_sub(safe_settle_amount)?;
+
+        Ok(())
+    }
+
     /// Calculate the liquidity utilization rate of the reserve
     pub fn utilization_rate(&self) -> Result<Rate, ProgramError> {
         let total_supply = self.total_supply()?;
@@ -516,6 +886,7 @@ impl ReserveLiquidity {
         current_borrow_rate: Rate,
         slots_elapsed: u64,
         take_rate: Rate,
+        insurance_fund_rate: Rate,
     ) -> ProgramResult {
         let slot_interest_rate = current_borrow_rate.try_div(SLOTS_PER_YEAR)?;
         let compounded_interest_rate = Rate::one()
@@ -530,9 +901,13 @@ impl ReserveLiquidity {
             .try_mul(compounded_interest_rate)?
             .try_sub(self.borrowed_amount_wads)?;
 
-        self.accumulated_protocol_fees_wads = net_new_debt
-            .try_mul(take_rate)?
+        let protocol_fees = net_new_debt.try_mul(take_rate)?;
+        let insurance_fund_fees = protocol_fees.try_mul(insurance_fund_rate)?;
+        self.accumulated_protocol_fees_wads = protocol_fees
+            .try_sub(insurance_fund_fees)?
             .try_add(self.accumulated_protocol_fees_wads)?;
+        self.accumulated_insurance_fund_wads =
+            insurance_fund_fees.try_add(self.accumulated_insurance_fund_wads)?;
 
         self.borrowed_amount_wads = self.borrowed_amount_wads.try_add(net_new_debt)?;
         Ok(())
@@ -564,6 +939,8 @@ pub struct ReserveCollateral {
     pub mint_total_supply: u64,
     /// Reserve collateral supply address
     pub supply_pubkey: Pubkey,
+    /// Collateral tokens minted per liquidity token while the reserve is empty
+    pub initial_collateral_ratio: u64,
 }
 
 impl ReserveCollateral {
@@ -573,6 +950,7 @@ impl ReserveCollateral {
             mint_pubkey: params.mint_pubkey,
             mint_total_supply: 0,
             supply_pubkey: params.supply_pubkey,
+            initial_collateral_ratio: params.initial_collateral_ratio,
         }
     }
 
@@ -600,7 +978,11 @@ impl ReserveCollateral {
         total_liquidity: Decimal,
     ) -> Result<CollateralExchangeRate, ProgramError> {
         let rate = if self.mint_total_supply == 0 || total_liquidity == Decimal::zero() {
-            Rate::from_scaled_val(INITIAL_COLLATERAL_RATE)
+            if self.initial_collateral_ratio == 0 {
+                msg!("Reserve initial collateral ratio is not set");
+                return Err(LendingError::InvalidAccountInput.into());
+            }
+            Rate::try_from(Decimal::from(self.initial_collateral_ratio))?
         } else {
             let mint_total_supply = Decimal::from(self.mint_total_supply);
             Rate::try_from(mint_total_supply.try_div(total_liquidity)?)?
@@ -616,6 +998,8 @@ pub struct NewReserveCollateralParams {
     pub mint_pubkey: Pubkey,
     /// Reserve collateral supply address
     pub supply_pubkey: Pubkey,
+    /// Collateral tokens minted per liquidity token while the reserve is empty
+    pub initial_collateral_ratio: u64,
 }
 
 /// Collateral exchange rate
@@ -688,6 +1072,17 @@ pub struct ReserveConfig {
     pub protocol_liquidation_fee: u8,
     /// Protocol take rate is the amount borrowed interest protocol recieves, as a percentage  
     pub protocol_take_rate: u8,
+    /// Maximum market value of deposited liquidity in the lending market's quote currency,
+    /// 0 for no limit
+    pub deposit_limit_value: u64,
+    /// Maximum market value of borrowed liquidity in the lending market's quote currency,
+    /// 0 for no limit
+    pub borrow_limit_value: u64,
+    /// Maximum share of the reserve collateral a single obligation can deposit, as a percentage,
+    /// 0 for no limit
+    pub max_obligation_deposit_percentage: u8,
+    /// Maximum amount of liquidity a single obligation can borrow, 0 for no limit
+    pub max_obligation_borrow: u64,
 }
 
 /// Additional fee information on a reserve
@@ -709,6 +1104,10 @@ pub struct ReserveFees {
     pub flash_loan_fee_wad: u64,
     /// Amount of fee going to host account, if provided in liquidate and repay
     pub host_fee_percentage: u8,
+    /// Fee assessed on deposited liquidity, expressed as a Wad.
+    pub deposit_fee_wad: u64,
+    /// Fee assessed on redeemed liquidity, expressed as a Wad.
+    pub redeem_fee_wad: u64,
 }
 
 impl ReserveFees {
@@ -725,12 +1124,25 @@ impl ReserveFees {
     pub fn calculate_flash_loan_fees(
         &self,
         flash_loan_amount: Decimal,
+        fee_calculation: FeeCalculation,
     ) -> Result<(u64, u64), ProgramError> {
-        self.calculate_fees(
-            flash_loan_amount,
-            self.flash_loan_fee_wad,
-            FeeCalculation::Exclusive,
-        )
+        self.calculate_fees(flash_loan_amount, self.flash_loan_fee_wad, fee_calculation)
+    }
+
+    /// Calculate the fee kept by the reserve on deposit
+    pub fn calculate_deposit_fee(&self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        Self::calculate_liquidity_fee(liquidity_amount, self.deposit_fee_wad)
+    }
+
+    /// Calculate the fee kept by the reserve on redeem
+    pub fn calculate_redeem_fee(&self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        Self::calculate_liquidity_fee(liquidity_amount, self.redeem_fee_wad)
+    }
+
+    fn calculate_liquidity_fee(liquidity_amount: u64, fee_wad: u64) -> Result<u64, ProgramError> {
+        Decimal::from(liquidity_amount)
+            .try_mul(Rate::from_scaled_val(fee_wad))?
+            .try_floor_u64()
     }
 
     fn calculate_fees(
@@ -783,6 +1195,41 @@ impl ReserveFees {
     }
 }
 
+/// Lifecycle status of a reserve
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReserveStatus {
+    /// All operations are allowed
+    Active,
+    /// Only repay, withdraw, redeem and liquidate are allowed
+    ReduceOnly,
+    /// Reduce only, and the collateral no longer counts towards borrowing power so that positions
+    /// are unwound before the reserve is closed
+    Deprecated,
+}
+
+#[allow(clippy::derivable_impls)] // `#[default]` variants need a newer toolchain
+impl Default for ReserveStatus {
+    fn default() -> Self {
+        Self::Active
+    }
+}
+
+impl TryFrom<u8> for ReserveStatus {
+    type Error = ProgramError;
+
+    fn try_from(status: u8) -> Result<Self, Self::Error> {
+        match status {
+            0 => Ok(Self::Active),
+            1 => Ok(Self::ReduceOnly),
+            2 => Ok(Self::Deprecated),
+            _ => {
+                msg!("Reserve status {} is invalid", status);
+                Err(LendingError::InvalidConfig.into())
+            }
+        }
+    }
+}
+
 /// Calculate fees exlusive or inclusive of an amount
 pub enum FeeCalculation {
     /// Fee added to amount: fee = rate * amount
@@ -798,7 +1245,7 @@ impl IsInitialized for Reserve {
     }
 }
 
-const RESERVE_LEN: usize = 619; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 1 + 1 + 16 + 230
+const RESERVE_LEN: usize = 619; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 1 + 1 + 16 + 16 + 1 + 8 + 8 + 1 + 8 + 8 + 8 + 8 + 8 + 8 + 16 + 16 + 1 + 8 + 107
 impl Pack for Reserve {
     const LEN: usize = RESERVE_LEN;
 
@@ -839,6 +1286,21 @@ impl Pack for Reserve {
             config_protocol_liquidation_fee,
             config_protocol_take_rate,
             liquidity_accumulated_protocol_fees_wads,
+            liquidity_accumulated_insurance_fund_wads,
+            insurance_fund_fee_percentage,
+            config_fees_deposit_fee_wad,
+            config_fees_redeem_fee_wad,
+            status,
+            collateral_initial_collateral_ratio,
+            config_deposit_limit_value,
+            config_borrow_limit_value,
+            rate_limiter_window_duration,
+            rate_limiter_max_outflow,
+            rate_limiter_window_start,
+            rate_limiter_previous_outflow,
+            rate_limiter_current_outflow,
+            config_max_obligation_deposit_percentage,
+            config_max_obligation_borrow,
             _padding,
         ) = mut_array_refs![
             output,
@@ -874,7 +1336,22 @@ impl Pack for Reserve {
             1,
             1,
             16,
-            230
+            16,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            8,
+            8,
+            8,
+            8,
+            16,
+            16,
+            1,
+            8,
+            107
         ];
 
         // reserve
@@ -903,6 +1380,10 @@ impl Pack for Reserve {
             self.liquidity.accumulated_protocol_fees_wads,
             liquidity_accumulated_protocol_fees_wads,
         );
+        pack_decimal(
+            self.liquidity.accumulated_insurance_fund_wads,
+            liquidity_accumulated_insurance_fund_wads,
+        );
         pack_decimal(self.liquidity.market_price, liquidity_market_price);
 
         // collateral
@@ -926,6 +1407,39 @@ impl Pack for Reserve {
         config_fee_receiver.copy_from_slice(self.config.fee_receiver.as_ref());
         *config_protocol_liquidation_fee = self.config.protocol_liquidation_fee.to_le_bytes();
         *config_protocol_take_rate = self.config.protocol_take_rate.to_le_bytes();
+
+        // insurance fund
+        *insurance_fund_fee_percentage = self.insurance_fund_fee_percentage.to_le_bytes();
+
+        // fees added after the original config layout
+        *config_fees_deposit_fee_wad = self.config.fees.deposit_fee_wad.to_le_bytes();
+        *config_fees_redeem_fee_wad = self.config.fees.redeem_fee_wad.to_le_bytes();
+
+        *status = (self.status as u8).to_le_bytes();
+        *collateral_initial_collateral_ratio =
+            self.collateral.initial_collateral_ratio.to_le_bytes();
+
+        // value limits added after the original config layout
+        *config_deposit_limit_value = self.config.deposit_limit_value.to_le_bytes();
+        *config_borrow_limit_value = self.config.borrow_limit_value.to_le_bytes();
+
+        // rate limiter
+        *rate_limiter_window_duration = self.rate_limiter.config.window_duration.to_le_bytes();
+        *rate_limiter_max_outflow = self.rate_limiter.config.max_outflow.to_le_bytes();
+        *rate_limiter_window_start = self.rate_limiter.window_start.to_le_bytes();
+        pack_decimal(
+            self.rate_limiter.previous_outflow,
+            rate_limiter_previous_outflow,
+        );
+        pack_decimal(
+            self.rate_limiter.current_outflow,
+            rate_limiter_current_outflow,
+        );
+
+        // per-obligation limits added after the original config layout
+        *config_max_obligation_deposit_percentage =
+            self.config.max_obligation_deposit_percentage.to_le_bytes();
+        *config_max_obligation_borrow = self.config.max_obligation_borrow.to_le_bytes();
     }
 
     /// Unpacks a byte buffer into a [ReserveInfo](struct.ReserveInfo.html).
@@ -965,6 +1479,21 @@ impl Pack for Reserve {
             config_protocol_liquidation_fee,
             config_protocol_take_rate,
             liquidity_accumulated_protocol_fees_wads,
+            liquidity_accumulated_insurance_fund_wads,
+            insurance_fund_fee_percentage,
+            config_fees_deposit_fee_wad,
+            config_fees_redeem_fee_wad,
+            status,
+            collateral_initial_collateral_ratio,
+            config_deposit_limit_value,
+            config_borrow_limit_value,
+            rate_limiter_window_duration,
+            rate_limiter_max_outflow,
+            rate_limiter_window_start,
+            rate_limiter_previous_outflow,
+            rate_limiter_current_outflow,
+            config_max_obligation_deposit_percentage,
+            config_max_obligation_borrow,
             _padding,
         ) = array_refs![
             input,
@@ -1000,15 +1529,38 @@ impl Pack for Reserve {
             1,
             1,
             16,
-            230
+            16,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            8,
+            8,
+            8,
+            8,
+            16,
+            16,
+            1,
+            8,
+            107
         ];
 
         let version = u8::from_le_bytes(*version);
+        // older versions share the current layout and are upgraded by `Migrate`
         if version > PROGRAM_VERSION {
             msg!("Reserve version does not match lending program version");
             return Err(ProgramError::InvalidAccountData);
         }
 
+        // reserves written before version 3 have no initial collateral ratio, which was fixed at 1
+        let initial_collateral_ratio = if version < 3 {
+            1
+        } else {
+            u64::from_le_bytes(*collateral_initial_collateral_ratio)
+        };
+
         Ok(Self {
             version,
             last_update: LastUpdate {
@@ -1030,12 +1582,16 @@ impl Pack for Reserve {
                 accumulated_protocol_fees_wads: unpack_decimal(
                     liquidity_accumulated_protocol_fees_wads,
                 ),
+                accumulated_insurance_fund_wads: unpack_decimal(
+                    liquidity_accumulated_insurance_fund_wads,
+                ),
                 market_price: unpack_decimal(liquidity_market_price),
             },
             collateral: ReserveCollateral {
                 mint_pubkey: Pubkey::new_from_array(*collateral_mint_pubkey),
                 mint_total_supply: u64::from_le_bytes(*collateral_mint_total_supply),
                 supply_pubkey: Pubkey::new_from_array(*collateral_supply_pubkey),
+                initial_collateral_ratio,
             },
             config: ReserveConfig {
                 optimal_utilization_rate: u8::from_le_bytes(*config_optimal_utilization_rate),
@@ -1049,12 +1605,32 @@ impl Pack for Reserve {
                     borrow_fee_wad: u64::from_le_bytes(*config_fees_borrow_fee_wad),
                     flash_loan_fee_wad: u64::from_le_bytes(*config_fees_flash_loan_fee_wad),
                     host_fee_percentage: u8::from_le_bytes(*config_fees_host_fee_percentage),
+                    deposit_fee_wad: u64::from_le_bytes(*config_fees_deposit_fee_wad),
+                    redeem_fee_wad: u64::from_le_bytes(*config_fees_redeem_fee_wad),
                 },
                 deposit_limit: u64::from_le_bytes(*config_deposit_limit),
                 borrow_limit: u64::from_le_bytes(*config_borrow_limit),
                 fee_receiver: Pubkey::new_from_array(*config_fee_receiver),
                 protocol_liquidation_fee: u8::from_le_bytes(*config_protocol_liquidation_fee),
                 protocol_take_rate: u8::from_le_bytes(*config_protocol_take_rate),
+                deposit_limit_value: u64::from_le_bytes(*config_deposit_limit_value),
+                borrow_limit_value: u64::from_le_bytes(*config_borrow_limit_value),
+                max_obligation_deposit_percentage: u8::from_le_bytes(
+                    *config_max_obligation_deposit_percentage,
+                ),
+                max_obligation_borrow: u64::from_le_bytes(*config_max_obligation_borrow),
+            },
+            insurance_fund_fee_percentage: u8::from_le_bytes(*insurance_fund_fee_percentage),
+            status: ReserveStatus::try_from(u8::from_le_bytes(*status))
+                .map_err(|_| ProgramError::InvalidAccountData)?,
+            rate_limiter: RateLimiter {
+                config: RateLimiterConfig {
+                    window_duration: u64::from_le_bytes(*rate_limiter_window_duration),
+                    max_outflow: u64::from_le_bytes(*rate_limiter_max_outflow),
+                },
+                window_start: u64::from_le_bytes(*rate_limiter_window_start),
+                previous_outflow: unpack_decimal(rate_limiter_previous_outflow),
+                current_outflow: unpack_decimal(rate_limiter_current_outflow),
             },
         })
     }
@@ -1188,6 +1764,7 @@ mod test {
             let mut reserve = Reserve {
                 collateral: ReserveCollateral {
                     mint_total_supply,
+                    initial_collateral_ratio: 1,
                     ..ReserveCollateral::default()
                 },
                 liquidity: ReserveLiquidity {
@@ -1223,17 +1800,25 @@ mod test {
             slots_elapsed in 0..=SLOTS_PER_YEAR,
             borrow_rate in 0..=u8::MAX,
             take_rate in 0..=100u8,
+            insurance_fund_rate in 0..=100u8,
         ) {
             let mut reserve = Reserve::default();
             let borrow_rate = Rate::from_percent(borrow_rate);
             let take_rate = Rate::from_percent(take_rate);
+            let insurance_fund_rate = Rate::from_percent(insurance_fund_rate);
 
             // Simulate running for max 1000 years, assuming that interest is
             // compounded at least once a year
             for _ in 0..1000 {
-                reserve.liquidity.compound_interest(borrow_rate, slots_elapsed, take_rate)?;
+                reserve.liquidity.compound_interest(
+                    borrow_rate,
+                    slots_elapsed,
+                    take_rate,
+                    insurance_fund_rate,
+                )?;
                 reserve.liquidity.cumulative_borrow_rate_wads.to_scaled_val()?;
                 reserve.liquidity.accumulated_protocol_fees_wads.to_scaled_val()?;
+                reserve.liquidity.accumulated_insurance_fund_wads.to_scaled_val()?;
             }
         }
 
@@ -1278,6 +1863,8 @@ mod test {
                 borrow_fee_wad,
                 flash_loan_fee_wad,
                 host_fee_percentage,
+               deposit_fee_wad: 0,
+               redeem_fee_wad: 0,
             };
             let (total_fee, host_fee) = fees.calculate_borrow_fees(Decimal::from(borrow_amount), FeeCalculation::Exclusive)?;
 
@@ -1321,8 +1908,10 @@ mod test {
                 borrow_fee_wad,
                 flash_loan_fee_wad,
                 host_fee_percentage,
+               deposit_fee_wad: 0,
+               redeem_fee_wad: 0,
             };
-            let (total_fee, host_fee) = fees.calculate_flash_loan_fees(Decimal::from(borrow_amount))?;
+            let (total_fee, host_fee) = fees.calculate_flash_loan_fees(Decimal::from(borrow_amount), FeeCalculation::Exclusive)?;
 
             // The total fee can't be greater than the amount borrowed, as long
             // as amount borrowed is greater than 2.
@@ -1358,6 +1947,8 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         };
 
         // only 2 tokens borrowed, get error
@@ -1386,6 +1977,8 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         };
 
         // only 2 tokens borrowed, ok
@@ -1415,6 +2008,8 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 20,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         };
 
         let (total_fee, host_fee) = fees
@@ -1431,6 +2026,8 @@ mod test {
             borrow_fee_wad: 10_000_000_000_000_000, // 1%
             flash_loan_fee_wad: 0,
             host_fee_percentage: 0,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         };
 
         let (total_fee, host_fee) = fees
@@ -1440,4 +2037,471 @@ mod test {
         assert_eq!(total_fee, 10); // 1% of 1000
         assert_eq!(host_fee, 0); // 0 host fee
     }
+
+    #[test]
+    fn flash_loan_fee_calculation_inclusive() {
+        let fees = ReserveFees {
+            borrow_fee_wad: 0,
+            flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+            host_fee_percentage: 20,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
+        };
+
+        let (total_fee, host_fee) = fees
+            .calculate_flash_loan_fees(Decimal::from(1_003_000u64), FeeCalculation::Inclusive)
+            .unwrap();
+
+        assert_eq!(total_fee, 3_000); // 0.3% of the 1_000_000 received
+        assert_eq!(host_fee, 600); // 20% of 3_000
+
+        // the fee must leave something to flash loan
+        let err = fees
+            .calculate_flash_loan_fees(Decimal::from(2u64), FeeCalculation::Inclusive)
+            .unwrap_err();
+        assert_eq!(err, LendingError::BorrowTooSmall.into());
+
+        // nothing available, no fee
+        let (total_fee, host_fee) = fees
+            .calculate_flash_loan_fees(Decimal::zero(), FeeCalculation::Inclusive)
+            .unwrap();
+        assert_eq!(total_fee, 0);
+        assert_eq!(host_fee, 0);
+    }
+
+    #[test]
+    fn flash_loan_calculation_max_amount() {
+        let reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 1_003_000,
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    borrow_fee_wad: 0,
+                    flash_loan_fee_wad: 3_000_000_000_000_000, // 0.3%
+                    host_fee_percentage: 20,
+                    deposit_fee_wad: 0,
+                    redeem_fee_wad: 0,
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        let result = reserve.calculate_flash_loan(u64::MAX, None).unwrap();
+        assert_eq!(result.flash_loan_amount, 1_000_000);
+        assert_eq!(result.origination_fee, 3_000);
+        assert_eq!(result.host_fee, 600);
+        // the receiver returns exactly the available amount
+        assert_eq!(
+            result.flash_loan_amount + result.origination_fee,
+            reserve.liquidity.available_amount
+        );
+
+        let result = reserve.calculate_flash_loan(1_000_000, None).unwrap();
+        assert_eq!(result.flash_loan_amount, 1_000_000);
+        assert_eq!(result.origination_fee, 3_000);
+
+        // whitelisted receiver programs pay at most the reserve flash loan fee
+        let result = reserve.calculate_flash_loan(1_000_000, Some(0)).unwrap();
+        assert_eq!(result.origination_fee, 0);
+        assert_eq!(result.host_fee, 0);
+        let result = reserve
+            .calculate_flash_loan(1_000_000, Some(1_000_000_000_000_000))
+            .unwrap();
+        assert_eq!(result.origination_fee, 1_000);
+        let result = reserve
+            .calculate_flash_loan(1_000_000, Some(u64::MAX))
+            .unwrap();
+        assert_eq!(result.origination_fee, 3_000);
+    }
+
+    fn liquidation_preview_obligation(
+        repay_reserve: Pubkey,
+        withdraw_reserve: Pubkey,
+    ) -> Obligation {
+        // 100 SOL at 20 USDC deposited, 1600 USDC borrowed at an 80% liquidation threshold
+        Obligation {
+            deposits: vec![ObligationCollateral {
+                deposit_reserve: withdraw_reserve,
+                deposited_amount: 100_000_000_000,
+                market_value: Decimal::from(2_000u64),
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: repay_reserve,
+                cumulative_borrow_rate_wads: Decimal::one(),
+                borrowed_amount_wads: Decimal::from(1_600_000_000u64),
+                market_value: Decimal::from(1_600u64),
+            }],
+            deposited_value: Decimal::from(2_000u64),
+            borrowed_value: Decimal::from(1_600u64),
+            allowed_borrow_value: Decimal::from(1_000u64),
+            unhealthy_borrow_value: Decimal::from(1_600u64),
+            ..Obligation::default()
+        }
+    }
+
+    #[test]
+    fn collect_protocol_fees() {
+        let mut reserve = Reserve {
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000,
+                ..ReserveCollateral::default()
+            },
+            liquidity: ReserveLiquidity {
+                available_amount: 100,
+                borrowed_amount_wads: Decimal::from(1_000u64),
+                accumulated_protocol_fees_wads: Decimal::from(100u64),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+        let exchange_rate = reserve.collateral_exchange_rate().unwrap();
+
+        // fees are no longer excluded from the total supply, so they can't be redeemed twice
+        assert_eq!(reserve.collect_protocol_fees().unwrap(), 100);
+        assert_eq!(reserve.collateral.mint_total_supply, 1_100);
+        assert_eq!(
+            reserve.liquidity.accumulated_protocol_fees_wads,
+            Decimal::zero()
+        );
+        assert_eq!(reserve.calculate_redeem_fees().unwrap(), 0);
+        assert_eq!(
+            reserve.collateral_exchange_rate().unwrap().0,
+            exchange_rate.0
+        );
+
+        assert_eq!(reserve.collect_protocol_fees().unwrap(), 0);
+        assert_eq!(reserve.collateral.mint_total_supply, 1_100);
+    }
+
+    #[test]
+    fn deposit_and_redeem_fees() {
+        let mut reserve = Reserve {
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000,
+                ..ReserveCollateral::default()
+            },
+            liquidity: ReserveLiquidity {
+                available_amount: 1_000,
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                fees: ReserveFees {
+                    deposit_fee_wad: 10_000_000_000_000_000, // 1%
+                    redeem_fee_wad: 20_000_000_000_000_000,  // 2%
+                    ..ReserveFees::default()
+                },
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+        let exchange_rate = reserve.collateral_exchange_rate().unwrap();
+
+        // the deposit fee is kept as protocol fees and doesn't move the exchange rate
+        assert_eq!(reserve.deposit_liquidity(1_000).unwrap(), 990);
+        assert_eq!(reserve.liquidity.available_amount, 2_000);
+        assert_eq!(
+            reserve.liquidity.accumulated_protocol_fees_wads,
+            Decimal::from(10u64)
+        );
+        assert_eq!(reserve.collateral.mint_total_supply, 1_990);
+        assert_eq!(
+            reserve.collateral_exchange_rate().unwrap().0,
+            exchange_rate.0
+        );
+
+        // the redeem fee is withheld from the liquidity returned to the user
+        assert_eq!(reserve.redeem_collateral(500).unwrap(), 490);
+        assert_eq!(reserve.liquidity.available_amount, 1_510);
+        assert_eq!(
+            reserve.liquidity.accumulated_protocol_fees_wads,
+            Decimal::from(20u64)
+        );
+        assert_eq!(reserve.collateral.mint_total_supply, 1_490);
+        assert_eq!(
+            reserve.collateral_exchange_rate().unwrap().0,
+            exchange_rate.0
+        );
+        assert_eq!(reserve.calculate_redeem_fees().unwrap(), 20);
+    }
+
+    #[test]
+    fn reserve_status() {
+        let mut reserve = Reserve {
+            version: PROGRAM_VERSION,
+            status: ReserveStatus::ReduceOnly,
+            ..Reserve::default()
+        };
+        assert!(!reserve.is_active());
+
+        let mut data = [0u8; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut data).unwrap();
+        assert_eq!(Reserve::unpack(&data).unwrap(), reserve);
+
+        // an unknown status can't be unpacked
+        data[Reserve::LEN - 197] = 3;
+        assert_eq!(
+            Reserve::unpack(&data).unwrap_err(),
+            ProgramError::InvalidAccountData
+        );
+
+        assert!(reserve.is_empty().unwrap());
+        reserve.collateral.mint_total_supply = 1;
+        assert!(!reserve.is_empty().unwrap());
+        reserve.collateral.mint_total_supply = 0;
+        reserve.liquidity.borrowed_amount_wads = Decimal::from(1u64);
+        assert!(!reserve.is_empty().unwrap());
+    }
+
+    #[test]
+    fn remaining_borrow_capacity() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                mint_decimals: 6,
+                borrowed_amount_wads: Decimal::from(4_000_000u64),
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            config: ReserveConfig {
+                borrow_limit: 10_000_000,
+                ..ReserveConfig::default()
+            },
+            ..Reserve::default()
+        };
+
+        // no value limit
+        assert_eq!(
+            reserve.remaining_borrow_capacity().unwrap(),
+            Decimal::from(6_000_000u64)
+        );
+
+        // $10 limit with $8 borrowed leaves $2, or 1 token
+        reserve.config.borrow_limit_value = 10;
+        assert_eq!(
+            reserve.remaining_borrow_capacity().unwrap(),
+            Decimal::from(1_000_000u64)
+        );
+
+        // the token limit still applies when it is lower
+        reserve.config.borrow_limit = 4_500_000;
+        assert_eq!(
+            reserve.remaining_borrow_capacity().unwrap(),
+            Decimal::from(500_000u64)
+        );
+
+        // borrows above the value limit leave no capacity
+        reserve.liquidity.market_price = Decimal::from(3u64);
+        assert_eq!(
+            reserve.remaining_borrow_capacity().unwrap(),
+            Decimal::zero()
+        );
+    }
+
+    #[test]
+    fn obligation_limits() {
+        let mut reserve = Reserve {
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000,
+                ..ReserveCollateral::default()
+            },
+            ..Reserve::default()
+        };
+
+        // no limits
+        assert_eq!(
+            reserve.remaining_obligation_borrow_capacity(Decimal::from(100u64)),
+            Decimal::from(u64::MAX)
+        );
+        assert!(!reserve.exceeds_obligation_deposit_limit(1_000).unwrap());
+
+        reserve.config.max_obligation_borrow = 150;
+        assert_eq!(
+            reserve.remaining_obligation_borrow_capacity(Decimal::from(100u64)),
+            Decimal::from(50u64)
+        );
+        assert_eq!(
+            reserve.remaining_obligation_borrow_capacity(Decimal::from(200u64)),
+            Decimal::zero()
+        );
+
+        reserve.config.max_obligation_deposit_percentage = 25;
+        assert!(!reserve.exceeds_obligation_deposit_limit(250).unwrap());
+        assert!(reserve.exceeds_obligation_deposit_limit(251).unwrap());
+    }
+
+    #[test]
+    fn deposit_value_limit() {
+        let mut reserve = Reserve {
+            liquidity: ReserveLiquidity {
+                available_amount: 100,
+                market_price: Decimal::from(2u64),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+        assert!(!reserve.exceeds_deposit_value_limit(u64::MAX / 4).unwrap());
+
+        reserve.config.deposit_limit_value = 300;
+        assert!(!reserve.exceeds_deposit_value_limit(50).unwrap());
+        assert!(reserve.exceeds_deposit_value_limit(51).unwrap());
+    }
+
+    #[test]
+    fn write_off_bad_debt() {
+        let mut reserve = Reserve {
+            collateral: ReserveCollateral {
+                mint_total_supply: 1_000,
+                ..ReserveCollateral::default()
+            },
+            liquidity: ReserveLiquidity {
+                available_amount: 100,
+                borrowed_amount_wads: Decimal::from(900u64),
+                ..ReserveLiquidity::default()
+            },
+            ..Reserve::default()
+        };
+
+        // the insurance fund covers the bad debt in full
+        assert_eq!(
+            reserve
+                .write_off_bad_debt(Decimal::from(50u64), 1_000)
+                .unwrap(),
+            50
+        );
+        assert_eq!(reserve.liquidity.available_amount, 150);
+        assert_eq!(
+            reserve.liquidity.borrowed_amount_wads,
+            Decimal::from(850u64)
+        );
+        assert_eq!(reserve.collateral_exchange_rate().unwrap().0, Rate::one());
+
+        // the remainder is socialized across depositors
+        assert_eq!(
+            reserve
+                .write_off_bad_debt(Decimal::from(300u64), 100)
+                .unwrap(),
+            100
+        );
+        assert_eq!(reserve.liquidity.available_amount, 250);
+        assert_eq!(
+            reserve.liquidity.borrowed_amount_wads,
+            Decimal::from(550u64)
+        );
+        assert_eq!(
+            reserve.liquidity.total_supply().unwrap(),
+            Decimal::from(800u64)
+        );
+    }
+
+    #[test]
+    fn liquidation_preview() {
+        let repay_reserve_pubkey = Pubkey::new_unique();
+        let withdraw_reserve_pubkey = Pubkey::new_unique();
+        let obligation =
+            liquidation_preview_obligation(repay_reserve_pubkey, withdraw_reserve_pubkey);
+
+        let mut withdraw_reserve = Reserve::default();
+        withdraw_reserve.config.liquidation_bonus = 10;
+        withdraw_reserve.collateral.initial_collateral_ratio = 5;
+
+        let preview = preview_liquidation(
+            &obligation,
+            &repay_reserve_pubkey,
+            &withdraw_reserve_pubkey,
+            &withdraw_reserve,
+            u64::MAX,
+        )
+        .unwrap();
+
+        // 1600 USDC * 20% close factor -> 320 USDC / 20 USDC per SOL -> 16 SOL + 10% bonus
+        assert_eq!(
+            preview,
+            LiquidationPreview {
+                settle_amount: Decimal::from(320_000_000u64),
+                repay_amount: 320_000_000,
+                withdraw_amount: 17_600_000_000,
+                withdraw_liquidity_amount: 17_600_000_000 / 5,
+                protocol_fee: withdraw_reserve
+                    .calculate_protocol_liquidation_fee(17_600_000_000 / 5)
+                    .unwrap(),
+            }
+        );
+
+        let (liquidity, _) = obligation
+            .find_liquidity_in_borrows(repay_reserve_pubkey)
+            .unwrap();
+        let (collateral, _) = obligation
+            .find_collateral_in_deposits(withdraw_reserve_pubkey)
+            .unwrap();
+        let result = withdraw_reserve
+            .calculate_liquidation(u64::MAX, &obligation, liquidity, collateral)
+            .unwrap();
+        assert_eq!(preview.settle_amount, result.settle_amount);
+        assert_eq!(preview.repay_amount, result.repay_amount);
+        assert_eq!(preview.withdraw_amount, result.withdraw_amount);
+    }
+
+    #[test]
+    fn liquidation_preview_redeem_fee() {
+        let repay_reserve_pubkey = Pubkey::new_unique();
+        let withdraw_reserve_pubkey = Pubkey::new_unique();
+        let obligation =
+            liquidation_preview_obligation(repay_reserve_pubkey, withdraw_reserve_pubkey);
+
+        let mut withdraw_reserve = Reserve::default();
+        withdraw_reserve.config.liquidation_bonus = 10;
+        withdraw_reserve.config.fees.redeem_fee_wad = WAD / 100; // 1%
+        withdraw_reserve.collateral.mint_total_supply = 50_000_000_000;
+        withdraw_reserve.liquidity.available_amount = 10_000_000_000;
+
+        let preview = preview_liquidation(
+            &obligation,
+            &repay_reserve_pubkey,
+            &withdraw_reserve_pubkey,
+            &withdraw_reserve,
+            u64::MAX,
+        )
+        .unwrap();
+
+        // 17.6 SOL of collateral at 5 collateral per SOL -> 3.52 SOL - 1% redeem fee
+        assert_eq!(preview.withdraw_amount, 17_600_000_000);
+        assert_eq!(preview.withdraw_liquidity_amount, 3_484_800_000);
+        assert_eq!(
+            preview.protocol_fee,
+            withdraw_reserve
+                .calculate_protocol_liquidation_fee(3_484_800_000)
+                .unwrap()
+        );
+
+        // the preview matches the liquidity redeemed when the liquidation is executed
+        assert_eq!(
+            withdraw_reserve
+                .clone()
+                .redeem_collateral(preview.withdraw_amount)
+                .unwrap(),
+            preview.withdraw_liquidity_amount
+        );
+    }
+
+    #[test]
+    fn liquidation_preview_healthy() {
+        let repay_reserve_pubkey = Pubkey::new_unique();
+        let withdraw_reserve_pubkey = Pubkey::new_unique();
+        let mut obligation =
+            liquidation_preview_obligation(repay_reserve_pubkey, withdraw_reserve_pubkey);
+        obligation.unhealthy_borrow_value = Decimal::from(1_601u64);
+
+        assert_eq!(
+            preview_liquidation(
+                &obligation,
+                &repay_reserve_pubkey,
+                &withdraw_reserve_pubkey,
+                &Reserve::default(),
+                u64::MAX,
+            ),
+            Err(LendingError::ObligationHealthy.into())
+        );
+    }
 }
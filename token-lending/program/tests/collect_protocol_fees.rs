@@ -0,0 +1,333 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use std::str::FromStr;
+
+use helpers::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{collect_protocol_fees, refresh_reserve},
+    math::{Decimal, Rate},
+    processor::process_instruction,
+    state::Reserve,
+};
+use spl_token::state::Account as Token;
+
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 100_000 * FRACTIONAL_TO_USDC;
+const BORROW_AMOUNT: u64 = 50_000 * FRACTIONAL_TO_USDC;
+const SLOTS_ELAPSED: u64 = 69420;
+
+async fn setup() -> (
+    BanksClient,
+    Keypair,
+    solana_sdk::hash::Hash,
+    TestLendingMarket,
+    TestReserve,
+) {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // Configure reserve to a fixed borrow rate of 250%
+    const BORROW_RATE: u8 = 250;
+    let mut reserve_config = test_reserve_config();
+    reserve_config.min_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_utilization_rate = 100;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_oracle(
+        &mut test,
+        Pubkey::from_str(SRM_PYTH_PRODUCT).unwrap(),
+        Pubkey::from_str(SRM_PYTH_PRICE).unwrap(),
+        Pubkey::from_str(SRM_SWITCHBOARD_FEED).unwrap(),
+        // Set USDC price to $1
+        Decimal::from(1u64),
+        SLOTS_ELAPSED,
+    );
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: BORROW_AMOUNT,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: reserve_config,
+            slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(2 + SLOTS_ELAPSED).unwrap();
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_reserve(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+            usdc_oracle.pyth_price_pubkey,
+            usdc_oracle.switchboard_feed_pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    (
+        banks_client,
+        payer,
+        recent_blockhash,
+        lending_market,
+        usdc_test_reserve,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (mut banks_client, payer, recent_blockhash, lending_market, usdc_test_reserve) =
+        setup().await;
+
+    let destination_collateral_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_test_reserve.collateral_mint_pubkey,
+        &payer,
+        Some(Pubkey::new_unique()),
+        None,
+    )
+    .await;
+
+    let usdc_reserve_before = usdc_test_reserve.get_state(&mut banks_client).await;
+    let fee_amount = usdc_reserve_before
+        .liquidity
+        .accumulated_protocol_fees_wads
+        .try_floor_u64()
+        .unwrap();
+    assert!(fee_amount > 0);
+    let collateral_amount = usdc_reserve_before
+        .collateral_exchange_rate()
+        .unwrap()
+        .liquidity_to_collateral(fee_amount)
+        .unwrap();
+
+    let mut transaction = Transaction::new_with_payer(
+        &[collect_protocol_fees(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.collateral_mint_pubkey,
+            destination_collateral_pubkey,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        get_token_balance(&mut banks_client, destination_collateral_pubkey).await,
+        collateral_amount
+    );
+    assert_eq!(
+        usdc_reserve.collateral.mint_total_supply,
+        usdc_reserve_before.collateral.mint_total_supply + collateral_amount
+    );
+    assert!(usdc_reserve.liquidity.accumulated_protocol_fees_wads < Decimal::one());
+    assert_eq!(
+        Rate::from(usdc_reserve_before.collateral_exchange_rate().unwrap()),
+        Rate::from(usdc_reserve.collateral_exchange_rate().unwrap()),
+    );
+}
+
+#[tokio::test]
+async fn test_initialized_reserve() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let sol_oracle = add_sol_oracle(&mut test);
+
+    let mut test_context = test.start_with_context().await;
+    let payer = Keypair::from_bytes(&test_context.payer.to_bytes()).unwrap();
+
+    const RESERVE_AMOUNT: u64 = 100 * LAMPORTS_TO_SOL;
+    const PROTOCOL_FEES: u64 = LAMPORTS_TO_SOL;
+
+    let sol_user_liquidity_account = create_and_mint_to_token_account(
+        &mut test_context.banks_client,
+        spl_token::native_mint::id(),
+        None,
+        &payer,
+        user_accounts_owner.pubkey(),
+        RESERVE_AMOUNT,
+    )
+    .await;
+
+    let mut config = test_reserve_config();
+    let fee_receiver_keypair = Keypair::new();
+    config.fee_receiver = fee_receiver_keypair.pubkey();
+
+    let sol_reserve = TestReserve::init(
+        "sol".to_owned(),
+        &mut test_context.banks_client,
+        &lending_market,
+        &sol_oracle,
+        RESERVE_AMOUNT,
+        config,
+        INITIAL_COLLATERAL_RATIO,
+        spl_token::native_mint::id(),
+        sol_user_liquidity_account,
+        &fee_receiver_keypair,
+        &payer,
+        &user_accounts_owner,
+    )
+    .await
+    .unwrap();
+
+    // the reserve fee receiver created by InitReserve is owned by the lending market authority
+    let fee_receiver = Token::unpack(
+        &test_context
+            .banks_client
+            .get_account(sol_reserve.config.fee_receiver)
+            .await
+            .unwrap()
+            .unwrap()
+            .data[..],
+    )
+    .unwrap();
+    assert_eq!(fee_receiver.owner, lending_market.authority);
+
+    // accrue protocol fees without waiting for interest
+    let mut reserve_account = test_context
+        .banks_client
+        .get_account(sol_reserve.pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let mut reserve = Reserve::unpack(&reserve_account.data[..]).unwrap();
+    reserve.liquidity.accumulated_protocol_fees_wads = Decimal::from(PROTOCOL_FEES);
+    Reserve::pack(reserve.clone(), &mut reserve_account.data[..]).unwrap();
+    test_context.set_account(&sol_reserve.pubkey, &reserve_account.into());
+    let collateral_amount = reserve
+        .collateral_exchange_rate()
+        .unwrap()
+        .liquidity_to_collateral(PROTOCOL_FEES)
+        .unwrap();
+
+    let destination_collateral_pubkey = create_token_account(
+        &mut test_context.banks_client,
+        sol_reserve.collateral_mint_pubkey,
+        &payer,
+        Some(Pubkey::new_unique()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                solend_program::id(),
+                sol_reserve.pubkey,
+                sol_oracle.pyth_price_pubkey,
+                sol_oracle.switchboard_feed_pubkey,
+            ),
+            collect_protocol_fees(
+                solend_program::id(),
+                sol_reserve.pubkey,
+                sol_reserve.collateral_mint_pubkey,
+                destination_collateral_pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    let recent_blockhash = test_context
+        .banks_client
+        .get_recent_blockhash()
+        .await
+        .unwrap();
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(test_context
+        .banks_client
+        .process_transaction(transaction)
+        .await
+        .is_ok());
+
+    assert_eq!(
+        get_token_balance(
+            &mut test_context.banks_client,
+            destination_collateral_pubkey
+        )
+        .await,
+        collateral_amount
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_market_owner() {
+    let (mut banks_client, payer, recent_blockhash, lending_market, usdc_test_reserve) =
+        setup().await;
+
+    let destination_collateral_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_test_reserve.collateral_mint_pubkey,
+        &payer,
+        Some(Keypair::new().pubkey()),
+        None,
+    )
+    .await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[collect_protocol_fees(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.collateral_mint_pubkey,
+            destination_collateral_pubkey,
+            lending_market.pubkey,
+            invalid_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
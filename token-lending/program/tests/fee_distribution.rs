@@ -0,0 +1,563 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, distribute_fees,
+        liquidate_obligation_and_redeem_reserve_collateral, refresh_obligation,
+        set_lending_market_fee_distribution,
+    },
+    processor::process_instruction,
+    state::FeeDistributionReceiver,
+};
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(70_000);
+
+    const USDC_TOTAL_BORROW_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const FEE_AMOUNT: u64 = 100;
+    const HOST_FEE_AMOUNT: u64 = 20;
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = USDC_TOTAL_BORROW_FRACTIONAL - FEE_AMOUNT;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_TOTAL_BORROW_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let treasury = Pubkey::new_unique();
+    let insurance_fund = Pubkey::new_unique();
+    let treasury_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(treasury),
+        None,
+    )
+    .await;
+    let insurance_fund_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(insurance_fund),
+        None,
+    )
+    .await;
+    let owner_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(lending_market.owner.pubkey()),
+        None,
+    )
+    .await;
+    let fee_distribution = vec![
+        FeeDistributionReceiver {
+            owner: treasury,
+            fee_percentage: 50,
+        },
+        FeeDistributionReceiver {
+            owner: insurance_fund,
+            fee_percentage: 25,
+        },
+    ];
+
+    let initial_fee_receiver_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_fee_distribution(
+                solend_program::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                fee_distribution.clone(),
+            ),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                solend_program::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                Some(usdc_test_reserve.liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &lending_market.owner, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market_info = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(lending_market_info.fee_distribution, fee_distribution);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, HOST_FEE_AMOUNT);
+
+    let fee_receiver_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(
+        fee_receiver_balance,
+        initial_fee_receiver_balance + FEE_AMOUNT - HOST_FEE_AMOUNT
+    );
+
+    // distributing fees does not require the lending market owner
+    let mut transaction = Transaction::new_with_payer(
+        &[distribute_fees(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.config.fee_receiver,
+            owner_liquidity_pubkey,
+            lending_market.pubkey,
+            vec![treasury_liquidity_pubkey, insurance_fund_liquidity_pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let treasury_balance = get_token_balance(&mut banks_client, treasury_liquidity_pubkey).await;
+    assert_eq!(treasury_balance, fee_receiver_balance * 50 / 100);
+
+    let insurance_fund_balance =
+        get_token_balance(&mut banks_client, insurance_fund_liquidity_pubkey).await;
+    assert_eq!(insurance_fund_balance, fee_receiver_balance * 25 / 100);
+
+    let owner_balance = get_token_balance(&mut banks_client, owner_liquidity_pubkey).await;
+    assert_eq!(
+        owner_balance,
+        fee_receiver_balance - treasury_balance - insurance_fund_balance
+    );
+
+    let fee_receiver_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_receiver_balance, 0);
+}
+
+#[tokio::test]
+async fn test_liquidation_protocol_fee() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // 100 SOL collateral
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    // 100 SOL * 80% LTV -> 80 SOL * 20 USDC -> 1600 USDC borrow
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 1_600 * FRACTIONAL_TO_USDC;
+    // 1600 USDC * 20% -> 320 USDC liquidation
+    const USDC_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = USDC_BORROW_AMOUNT_FRACTIONAL / 5;
+    // 320 USDC / 20 USDC per SOL -> 16 SOL + 10% bonus -> 17.6 SOL (88/5)
+    const SOL_LIQUIDATION_AMOUNT_LAMPORTS: u64 =
+        LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO * 88 / 5;
+    // the protocol liquidation fee is currently the 1 lamport minimum
+    const PROTOCOL_FEE_LAMPORTS: u64 = 1;
+
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.liquidation_threshold = 80;
+    reserve_config.liquidation_bonus = 10;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS / INITIAL_COLLATERAL_RATIO,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let treasury = Pubkey::new_unique();
+    let treasury_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        &payer,
+        Some(treasury),
+        None,
+    )
+    .await;
+    let owner_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        spl_token::native_mint::id(),
+        &payer,
+        Some(lending_market.owner.pubkey()),
+        None,
+    )
+    .await;
+    let fee_distribution = vec![FeeDistributionReceiver {
+        owner: treasury,
+        fee_percentage: 100,
+    }];
+
+    let initial_user_withdraw_liquidity_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_liquidity_pubkey).await;
+    let initial_fee_receiver_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.config.fee_receiver).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_fee_distribution(
+                solend_program::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                fee_distribution,
+            ),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            liquidate_obligation_and_redeem_reserve_collateral(
+                solend_program::id(),
+                USDC_LIQUIDATION_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.user_liquidity_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.pubkey,
+                sol_test_reserve.collateral_mint_pubkey,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.liquidity_supply_pubkey,
+                sol_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+            distribute_fees(
+                solend_program::id(),
+                sol_test_reserve.pubkey,
+                sol_test_reserve.config.fee_receiver,
+                owner_liquidity_pubkey,
+                lending_market.pubkey,
+                vec![treasury_liquidity_pubkey],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &lending_market.owner, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let treasury_balance = get_token_balance(&mut banks_client, treasury_liquidity_pubkey).await;
+    assert_eq!(
+        treasury_balance,
+        initial_fee_receiver_balance + PROTOCOL_FEE_LAMPORTS
+    );
+
+    let owner_balance = get_token_balance(&mut banks_client, owner_liquidity_pubkey).await;
+    assert_eq!(owner_balance, 0);
+
+    let fee_receiver_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_receiver_balance, 0);
+
+    let user_withdraw_liquidity_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_liquidity_pubkey).await;
+    assert_eq!(
+        user_withdraw_liquidity_balance,
+        initial_user_withdraw_liquidity_balance + SOL_LIQUIDATION_AMOUNT_LAMPORTS
+            - PROTOCOL_FEE_LAMPORTS
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_fee_percentage() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_fee_distribution(
+            solend_program::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            vec![
+                FeeDistributionReceiver {
+                    owner: Pubkey::new_unique(),
+                    fee_percentage: 60,
+                },
+                FeeDistributionReceiver {
+                    owner: Pubkey::new_unique(),
+                    fee_percentage: 41,
+                },
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidConfig as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_fee_distribution(
+            solend_program::id(),
+            lending_market.pubkey,
+            invalid_owner.pubkey(),
+            vec![FeeDistributionReceiver {
+                owner: Pubkey::new_unique(),
+                fee_percentage: 50,
+            }],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_fee_distribution_receiver() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 1_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let owner_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(lending_market.owner.pubkey()),
+        None,
+    )
+    .await;
+    let invalid_liquidity_pubkey = create_token_account(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        Some(Pubkey::new_unique()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_lending_market_fee_distribution(
+                solend_program::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                vec![FeeDistributionReceiver {
+                    owner: Pubkey::new_unique(),
+                    fee_percentage: 50,
+                }],
+            ),
+            distribute_fees(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                owner_liquidity_pubkey,
+                lending_market.pubkey,
+                vec![invalid_liquidity_pubkey],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
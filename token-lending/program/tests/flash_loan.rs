@@ -12,6 +12,7 @@ use solana_sdk::{
 };
 use solend_program::{
     error::LendingError, instruction::flash_loan, processor::process_instruction,
+    state::FeeCalculation,
 };
 use spl_token::solana_program::instruction::InstructionError;
 
@@ -115,7 +116,7 @@ async fn test_success() {
     let (total_fee, host_fee) = usdc_reserve
         .config
         .fees
-        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into())
+        .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into(), FeeCalculation::Exclusive)
         .unwrap();
     assert_eq!(total_fee, FEE_AMOUNT);
     assert_eq!(host_fee, HOST_FEE_AMOUNT);
@@ -136,6 +137,114 @@ async fn test_success() {
     assert_eq!(host_fee_balance, HOST_FEE_AMOUNT);
 }
 
+#[tokio::test]
+async fn test_success_max_amount() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(50_000);
+
+    const RESERVE_LIQUIDITY_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const RECEIVER_BALANCE: u64 = 3_000_000;
+
+    let receiver_program_account = Keypair::new();
+    let receiver_program_id = receiver_program_account.pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id.clone(),
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: RESERVE_LIQUIDITY_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        RECEIVER_BALANCE,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            solend_program::id(),
+            u64::MAX,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.config.fee_receiver,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id.clone(),
+            vec![AccountMeta::new_readonly(
+                receiver_authority_pubkey.clone(),
+                false,
+            )],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // fees are included in the available amount, so the receiver pays them
+    // on top of the flash loaned amount and the reserve is made whole
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.available_amount,
+        RESERVE_LIQUIDITY_AMOUNT
+    );
+
+    let (total_fee, host_fee) = usdc_reserve
+        .config
+        .fees
+        .calculate_flash_loan_fees(RESERVE_LIQUIDITY_AMOUNT.into(), FeeCalculation::Inclusive)
+        .unwrap();
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, RESERVE_LIQUIDITY_AMOUNT);
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, RECEIVER_BALANCE - total_fee);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, total_fee - host_fee);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, host_fee);
+}
+
 #[tokio::test]
 async fn test_failure() {
     let mut test = ProgramTest::new(
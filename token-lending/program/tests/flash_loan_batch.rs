@@ -0,0 +1,259 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::instruction::AccountMeta;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{flash_loan_batch, BatchFlashLoan},
+    processor::process_instruction,
+    state::FeeCalculation,
+};
+
+const FLASH_LOAN_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const FEE_AMOUNT_A: u64 = 3_000_000;
+const FEE_AMOUNT_B: u64 = 5_000_000;
+
+struct TestFlashLoanBatch {
+    lending_market: TestLendingMarket,
+    reserve_a: TestReserve,
+    reserve_b: TestReserve,
+    receiver_program_id: Pubkey,
+    receiver_authority_pubkey: Pubkey,
+    receiver_account_a: Pubkey,
+    receiver_account_b: Pubkey,
+}
+
+fn setup(receiver_balance_b: u64) -> (ProgramTest, TestFlashLoanBatch) {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let receiver_program_id = Keypair::new().pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+
+    let mut reserve_config_a = test_reserve_config();
+    reserve_config_a.fees.flash_loan_fee_wad = 3_000_000_000_000_000;
+    let reserve_a = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config_a,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut reserve_config_b = test_reserve_config();
+    reserve_config_b.fees.flash_loan_fee_wad = 5_000_000_000_000_000;
+    let reserve_b = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config_b,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let receiver_account_a = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        FEE_AMOUNT_A,
+        &usdc_mint.pubkey,
+    );
+    let receiver_account_b = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        receiver_balance_b,
+        &usdc_mint.pubkey,
+    );
+
+    (
+        test,
+        TestFlashLoanBatch {
+            lending_market,
+            reserve_a,
+            reserve_b,
+            receiver_program_id,
+            receiver_authority_pubkey,
+            receiver_account_a,
+            receiver_account_b,
+        },
+    )
+}
+
+fn batch_flash_loan(reserve: &TestReserve, destination_liquidity_pubkey: Pubkey) -> BatchFlashLoan {
+    BatchFlashLoan {
+        liquidity_amount: FLASH_LOAN_AMOUNT,
+        source_liquidity_pubkey: reserve.liquidity_supply_pubkey,
+        destination_liquidity_pubkey,
+        reserve_pubkey: reserve.pubkey,
+        reserve_liquidity_fee_receiver_pubkey: reserve.config.fee_receiver,
+        host_fee_receiver_pubkey: reserve.liquidity_host_pubkey,
+    }
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (mut test, batch) = setup(FEE_AMOUNT_B);
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(100_000);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan_batch(
+            solend_program::id(),
+            &[
+                batch_flash_loan(&batch.reserve_a, batch.receiver_account_a),
+                batch_flash_loan(&batch.reserve_b, batch.receiver_account_b),
+            ],
+            batch.lending_market.pubkey,
+            batch.receiver_program_id,
+            vec![AccountMeta::new_readonly(
+                batch.receiver_authority_pubkey,
+                false,
+            )],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    for (reserve, receiver_account, fee_amount) in [
+        (&batch.reserve_a, batch.receiver_account_a, FEE_AMOUNT_A),
+        (&batch.reserve_b, batch.receiver_account_b, FEE_AMOUNT_B),
+    ] {
+        let reserve_state = reserve.get_state(&mut banks_client).await;
+        assert_eq!(reserve_state.liquidity.available_amount, FLASH_LOAN_AMOUNT);
+
+        let (total_fee, host_fee) = reserve_state
+            .config
+            .fees
+            .calculate_flash_loan_fees(FLASH_LOAN_AMOUNT.into(), FeeCalculation::Exclusive)
+            .unwrap();
+        assert_eq!(total_fee, fee_amount);
+
+        let liquidity_supply =
+            get_token_balance(&mut banks_client, reserve.liquidity_supply_pubkey).await;
+        assert_eq!(liquidity_supply, FLASH_LOAN_AMOUNT);
+
+        let receiver_balance = get_token_balance(&mut banks_client, receiver_account).await;
+        assert_eq!(receiver_balance, 0);
+
+        let fee_balance = get_token_balance(&mut banks_client, reserve.config.fee_receiver).await;
+        assert_eq!(fee_balance, total_fee - host_fee);
+
+        let host_fee_balance =
+            get_token_balance(&mut banks_client, reserve.liquidity_host_pubkey).await;
+        assert_eq!(host_fee_balance, host_fee);
+    }
+}
+
+#[tokio::test]
+async fn test_fee_not_repaid() {
+    let (test, batch) = setup(FEE_AMOUNT_B - 1);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan_batch(
+            solend_program::id(),
+            &[
+                batch_flash_loan(&batch.reserve_a, batch.receiver_account_a),
+                batch_flash_loan(&batch.reserve_b, batch.receiver_account_b),
+            ],
+            batch.lending_market.pubkey,
+            batch.receiver_program_id,
+            vec![AccountMeta::new_readonly(
+                batch.receiver_authority_pubkey,
+                false,
+            )],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::NotEnoughLiquidityAfterFlashLoan as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_duplicate_reserve() {
+    let (test, batch) = setup(FEE_AMOUNT_B);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan_batch(
+            solend_program::id(),
+            &[
+                batch_flash_loan(&batch.reserve_a, batch.receiver_account_a),
+                batch_flash_loan(&batch.reserve_a, batch.receiver_account_b),
+            ],
+            batch.lending_market.pubkey,
+            batch.receiver_program_id,
+            vec![AccountMeta::new_readonly(
+                batch.receiver_authority_pubkey,
+                false,
+            )],
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -0,0 +1,266 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::{instruction::AccountMeta, program_pack::Pack};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{
+        add_flash_loan_whitelist_entry, find_flash_loan_whitelist_address, flash_loan,
+        remove_flash_loan_whitelist_entry,
+    },
+    processor::process_instruction,
+    state::FlashLoanWhitelist,
+};
+
+const FLASH_LOAN_AMOUNT: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const FEE_AMOUNT: u64 = 3_000_000;
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(60_000);
+
+    let receiver_program_id = Keypair::new().pubkey();
+    test.prefer_bpf(false);
+    test.add_program(
+        "flash_loan_receiver",
+        receiver_program_id,
+        processor!(helpers::flash_loan_receiver::process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    test.add_account(
+        lending_market.owner.pubkey(),
+        Account::new(u32::MAX as u64, 0, &solana_program::system_program::id()),
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: FLASH_LOAN_AMOUNT,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (receiver_authority_pubkey, _) =
+        Pubkey::find_program_address(&[b"flashloan"], &receiver_program_id);
+    let program_owned_token_account = add_account_for_program(
+        &mut test,
+        &receiver_authority_pubkey,
+        FEE_AMOUNT,
+        &usdc_mint.pubkey,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[add_flash_loan_whitelist_entry(
+            solend_program::id(),
+            receiver_program_id,
+            0,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let (flash_loan_whitelist_pubkey, _) =
+        find_flash_loan_whitelist_address(&solend_program::id(), &lending_market.pubkey);
+    let flash_loan_whitelist_account = banks_client
+        .get_account(flash_loan_whitelist_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let flash_loan_whitelist =
+        FlashLoanWhitelist::unpack(&flash_loan_whitelist_account.data).unwrap();
+    assert_eq!(flash_loan_whitelist.lending_market, lending_market.pubkey);
+    assert_eq!(
+        flash_loan_whitelist.find_flash_loan_fee_wad(&receiver_program_id),
+        Some(0)
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            solend_program::id(),
+            FLASH_LOAN_AMOUNT,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.config.fee_receiver,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id,
+            vec![
+                AccountMeta::new_readonly(flash_loan_whitelist_pubkey, false),
+                AccountMeta::new_readonly(receiver_authority_pubkey, false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, FEE_AMOUNT);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, 0);
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(liquidity_supply, FLASH_LOAN_AMOUNT);
+
+    // once removed, the receiver program pays the reserve flash loan fee again
+    let mut transaction = Transaction::new_with_payer(
+        &[remove_flash_loan_whitelist_entry(
+            solend_program::id(),
+            receiver_program_id,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let mut transaction = Transaction::new_with_payer(
+        &[flash_loan(
+            solend_program::id(),
+            FLASH_LOAN_AMOUNT,
+            usdc_test_reserve.liquidity_supply_pubkey,
+            program_owned_token_account,
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.config.fee_receiver,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            receiver_program_id,
+            vec![
+                AccountMeta::new_readonly(flash_loan_whitelist_pubkey, false),
+                AccountMeta::new_readonly(receiver_authority_pubkey, false),
+            ],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let token_balance = get_token_balance(&mut banks_client, program_owned_token_account).await;
+    assert_eq!(token_balance, 0);
+}
+
+#[tokio::test]
+async fn test_prefunded_whitelist_address() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    test.add_account(
+        lending_market.owner.pubkey(),
+        Account::new(u32::MAX as u64, 0, &solana_program::system_program::id()),
+    );
+    let (flash_loan_whitelist_pubkey, _bump_seed) =
+        find_flash_loan_whitelist_address(&solend_program::id(), &lending_market.pubkey);
+    // anyone can send lamports to the whitelist address before it is created
+    test.add_account(
+        flash_loan_whitelist_pubkey,
+        Account::new(1, 0, &solana_program::system_program::id()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let receiver_program_id = Keypair::new().pubkey();
+    let mut transaction = Transaction::new_with_payer(
+        &[add_flash_loan_whitelist_entry(
+            solend_program::id(),
+            receiver_program_id,
+            0,
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let flash_loan_whitelist_account = banks_client
+        .get_account(flash_loan_whitelist_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(flash_loan_whitelist_account.owner, solend_program::id());
+    let flash_loan_whitelist =
+        FlashLoanWhitelist::unpack(&flash_loan_whitelist_account.data).unwrap();
+    assert_eq!(flash_loan_whitelist.entries.len(), 1);
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+    let invalid_owner = Keypair::new();
+    test.add_account(
+        invalid_owner.pubkey(),
+        Account::new(u32::MAX as u64, 0, &solana_program::system_program::id()),
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[add_flash_loan_whitelist_entry(
+            solend_program::id(),
+            Keypair::new().pubkey(),
+            0,
+            lending_market.pubkey,
+            invalid_owner.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidMarketOwner as u32)
+        )
+    );
+}
@@ -27,6 +27,22 @@ pub enum FlashLoanReceiverInstruction {
         /// The amount that is loaned
         amount: u64,
     },
+
+    /// Receive flash loans from several reserves and return each of them back.
+    ///
+    /// Accounts expected:
+    ///
+    ///   For each amount, in order:
+    ///
+    ///   0. `[writable]` Source liquidity (matching the destination from above).
+    ///   1. `[writable]` Destination liquidity (matching the source from above).
+    ///
+    ///   .. `[]` Token program id
+    ///   .. `[any]` Additional accounts provided to the lending program's `FlashLoanBatch` instruction above.
+    ReceiveBatchFlashLoan {
+        /// The amounts that are loaned
+        amounts: Vec<u64>,
+    },
 }
 
 entrypoint!(process_instruction);
@@ -52,6 +68,10 @@ impl Processor {
                 msg!("Instruction: Receive Flash Loan");
                 Self::process_receive_flash_loan(accounts, amount, program_id)
             }
+            FlashLoanReceiverInstruction::ReceiveBatchFlashLoan { amounts } => {
+                msg!("Instruction: Receive Batch Flash Loan");
+                Self::process_receive_batch_flash_loan(accounts, &amounts, program_id)
+            }
         }
     }
 
@@ -106,6 +126,58 @@ impl Processor {
 
         Ok(())
     }
+
+    fn process_receive_batch_flash_loan(
+        accounts: &[AccountInfo],
+        amounts: &[u64],
+        program_id: &Pubkey,
+    ) -> ProgramResult {
+        let (liquidity_accounts, rest) = accounts.split_at(amounts.len() * 2);
+        let account_info_iter = &mut rest.iter();
+        let token_program_id = next_account_info(account_info_iter)?;
+        let program_derived_account_info = next_account_info(account_info_iter)?;
+
+        let (expected_program_derived_account_pubkey, bump_seed) =
+            Pubkey::find_program_address(&[b"flashloan"], program_id);
+        if &expected_program_derived_account_pubkey != program_derived_account_info.key {
+            msg!("Supplied program derived account doesn't match with expectation.")
+        }
+
+        for (amount, liquidity_account_infos) in amounts.iter().zip(liquidity_accounts.chunks(2)) {
+            let source_liquidity_token_account_info = &liquidity_account_infos[0];
+            let destination_liquidity_token_account_info = &liquidity_account_infos[1];
+
+            let source_liquidity_token_account = Account::unpack_from_slice(
+                &source_liquidity_token_account_info.try_borrow_data()?,
+            )?;
+            if source_liquidity_token_account.owner != expected_program_derived_account_pubkey {
+                msg!("Source liquidity token account is not owned by the program");
+                return Err(ProgramError::IncorrectProgramId);
+            }
+
+            let transfer_ix = spl_token::instruction::transfer(
+                token_program_id.key,
+                source_liquidity_token_account_info.key,
+                destination_liquidity_token_account_info.key,
+                &expected_program_derived_account_pubkey,
+                &[],
+                min(source_liquidity_token_account.amount, *amount),
+            )?;
+
+            invoke_signed(
+                &transfer_ix,
+                &[
+                    source_liquidity_token_account_info.clone(),
+                    destination_liquidity_token_account_info.clone(),
+                    program_derived_account_info.clone(),
+                    token_program_id.clone(),
+                ],
+                &[&[&b"flashloan"[..], &[bump_seed]]],
+            )?;
+        }
+
+        Ok(())
+    }
 }
 
 impl FlashLoanReceiverInstruction {
@@ -116,6 +188,15 @@ impl FlashLoanReceiverInstruction {
             0 => Self::ReceiveFlashLoan {
                 amount: Self::unpack_amount(rest)?,
             },
+            1 => {
+                let (&count, mut rest) = rest.split_first().ok_or(InvalidInstruction)?;
+                let mut amounts = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    amounts.push(Self::unpack_amount(rest)?);
+                    rest = rest.get(8..).ok_or(InvalidInstruction)?;
+                }
+                Self::ReceiveBatchFlashLoan { amounts }
+            }
             _ => return Err(InvalidInstruction.into()),
         })
     }
@@ -26,7 +26,7 @@ use solend_program::{
         InitLendingMarketParams, InitObligationParams, InitReserveParams, LendingMarket,
         NewReserveCollateralParams, NewReserveLiquidityParams, Obligation, ObligationCollateral,
         ObligationLiquidity, Reserve, ReserveCollateral, ReserveConfig, ReserveFees,
-        ReserveLiquidity, INITIAL_COLLATERAL_RATIO, PROGRAM_VERSION,
+        ReserveLiquidity, PROGRAM_VERSION,
     },
 };
 use spl_token::{
@@ -42,6 +42,9 @@ pub const QUOTE_CURRENCY: [u8; 32] =
 pub const LAMPORTS_TO_SOL: u64 = 1_000_000_000;
 pub const FRACTIONAL_TO_USDC: u64 = 1_000_000;
 
+/// Collateral tokens minted per liquidity token by reserves created in tests
+pub const INITIAL_COLLATERAL_RATIO: u64 = 1;
+
 pub fn test_reserve_config() -> ReserveConfig {
     ReserveConfig {
         optimal_utilization_rate: 80,
@@ -55,12 +58,18 @@ pub fn test_reserve_config() -> ReserveConfig {
             borrow_fee_wad: 100_000_000_000,
             flash_loan_fee_wad: 3_000_000_000_000_000,
             host_fee_percentage: 20,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         },
         deposit_limit: 100_000_000_000,
         borrow_limit: u64::MAX,
         fee_receiver: Keypair::new().pubkey(),
         protocol_liquidation_fee: 30,
         protocol_take_rate: 10,
+        deposit_limit_value: 0,
+        borrow_limit_value: 0,
+        max_obligation_deposit_percentage: 0,
+        max_obligation_borrow: 0,
     }
 }
 
@@ -143,6 +152,7 @@ pub struct AddObligationArgs<'a> {
     pub borrows: &'a [(&'a TestReserve, u64)],
     pub mark_fresh: bool,
     pub slots_elapsed: u64,
+    pub referrer: Pubkey,
 }
 
 pub fn add_obligation(
@@ -156,6 +166,7 @@ pub fn add_obligation(
         borrows,
         mark_fresh,
         slots_elapsed,
+        referrer,
     } = args;
 
     let obligation_keypair = Keypair::new();
@@ -206,6 +217,7 @@ pub fn add_obligation(
         owner: user_accounts_owner.pubkey(),
         deposits: obligation_deposits,
         borrows: obligation_borrows,
+        referrer,
     });
 
     if mark_fresh {
@@ -333,7 +345,7 @@ pub fn add_reserve(
         amount,
         &Token {
             mint: liquidity_mint_pubkey,
-            owner: lending_market.owner.pubkey(),
+            owner: lending_market.authority,
             amount: 0,
             is_native,
             state: AccountState::Initialized,
@@ -372,6 +384,7 @@ pub fn add_reserve(
         collateral: ReserveCollateral::new(NewReserveCollateralParams {
             mint_pubkey: collateral_mint_pubkey,
             supply_pubkey: collateral_supply_pubkey,
+            initial_collateral_ratio: INITIAL_COLLATERAL_RATIO,
         }),
         config,
     });
@@ -433,6 +446,7 @@ pub fn add_reserve(
         pubkey: reserve_pubkey,
         lending_market_pubkey: lending_market.pubkey,
         config,
+        initial_collateral_ratio: INITIAL_COLLATERAL_RATIO,
         liquidity_mint_pubkey,
         liquidity_mint_decimals,
         liquidity_supply_pubkey,
@@ -830,6 +844,7 @@ pub struct TestReserve {
     pub pubkey: Pubkey,
     pub lending_market_pubkey: Pubkey,
     pub config: ReserveConfig,
+    pub initial_collateral_ratio: u64,
     pub liquidity_mint_pubkey: Pubkey,
     pub liquidity_mint_decimals: u8,
     pub liquidity_supply_pubkey: Pubkey,
@@ -852,6 +867,7 @@ impl TestReserve {
         oracle: &TestOracle,
         liquidity_amount: u64,
         config: ReserveConfig,
+        initial_collateral_ratio: u64,
         liquidity_mint_pubkey: Pubkey,
         user_liquidity_pubkey: Pubkey,
         liquidity_fee_receiver_keypair: &Keypair,
@@ -939,6 +955,7 @@ impl TestReserve {
                     solend_program::id(),
                     liquidity_amount,
                     config,
+                    initial_collateral_ratio,
                     user_liquidity_pubkey,
                     user_collateral_token_keypair.pubkey(),
                     reserve_pubkey,
@@ -981,6 +998,7 @@ impl TestReserve {
                 pubkey: reserve_pubkey,
                 lending_market_pubkey: lending_market.pubkey,
                 config: config,
+                initial_collateral_ratio,
                 liquidity_mint_pubkey: liquidity_mint_pubkey,
                 liquidity_mint_decimals: liquidity_mint.decimals,
                 liquidity_supply_pubkey: liquidity_supply_keypair.pubkey(),
@@ -1021,6 +1039,10 @@ impl TestReserve {
             reserve.collateral.supply_pubkey
         );
         assert_eq!(self.config, reserve.config);
+        assert_eq!(
+            self.initial_collateral_ratio,
+            reserve.collateral.initial_collateral_ratio
+        );
 
         assert_eq!(
             self.liquidity_pyth_oracle_pubkey,
@@ -1083,6 +1105,7 @@ impl TestObligation {
                     obligation.pubkey,
                     lending_market.pubkey,
                     user_accounts_owner.pubkey(),
+                    None,
                 ),
             ],
             Some(&payer.pubkey()),
@@ -0,0 +1,208 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::program_pack::Pack;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signer,
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{find_obligation_address, init_derived_obligation},
+    processor::process_instruction,
+    state::{Obligation, PROGRAM_VERSION},
+};
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(40_000);
+
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            init_derived_obligation(
+                solend_program::id(),
+                lending_market.pubkey,
+                payer.pubkey(),
+                0,
+                None,
+            ),
+            init_derived_obligation(
+                solend_program::id(),
+                lending_market.pubkey,
+                payer.pubkey(),
+                1,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    for index in 0..2 {
+        let (obligation_pubkey, _bump_seed) = find_obligation_address(
+            &solend_program::id(),
+            &lending_market.pubkey,
+            &payer.pubkey(),
+            index,
+        );
+        let obligation_account = banks_client
+            .get_account(obligation_pubkey)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(obligation_account.owner, solend_program::id());
+        assert_eq!(obligation_account.data.len(), Obligation::LEN);
+
+        let obligation = Obligation::unpack(&obligation_account.data[..]).unwrap();
+        assert_eq!(obligation.version, PROGRAM_VERSION);
+        assert_eq!(obligation.lending_market, lending_market.pubkey);
+        assert_eq!(obligation.owner, payer.pubkey());
+        assert!(obligation.deposits.is_empty());
+        assert!(obligation.borrows.is_empty());
+    }
+}
+
+#[tokio::test]
+async fn test_prefunded_obligation_address() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (obligation_pubkey, _bump_seed) = find_obligation_address(
+        &solend_program::id(),
+        &lending_market.pubkey,
+        &payer.pubkey(),
+        0,
+    );
+
+    // anyone can send lamports to the obligation address before it is created
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(&payer.pubkey(), &obligation_pubkey, 1),
+            init_derived_obligation(
+                solend_program::id(),
+                lending_market.pubkey,
+                payer.pubkey(),
+                0,
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let rent = banks_client.get_rent().await.unwrap();
+    let obligation_account = banks_client
+        .get_account(obligation_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(obligation_account.owner, solend_program::id());
+    assert_eq!(obligation_account.data.len(), Obligation::LEN);
+    assert_eq!(
+        obligation_account.lamports,
+        rent.minimum_balance(Obligation::LEN)
+    );
+
+    let obligation = Obligation::unpack(&obligation_account.data[..]).unwrap();
+    assert_eq!(obligation.owner, payer.pubkey());
+}
+
+#[tokio::test]
+async fn test_invalid_obligation_address() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut instruction = init_derived_obligation(
+        solend_program::id(),
+        lending_market.pubkey,
+        payer.pubkey(),
+        0,
+        None,
+    );
+    instruction.accounts[0].pubkey = Pubkey::new_unique();
+
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_owner_as_referrer() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[init_derived_obligation(
+            solend_program::id(),
+            lending_market.pubkey,
+            payer.pubkey(),
+            0,
+            Some(payer.pubkey()),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
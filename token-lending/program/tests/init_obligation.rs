@@ -3,15 +3,18 @@
 mod helpers;
 
 use helpers::*;
+use solana_program::program_pack::Pack;
 use solana_program_test::*;
 use solana_sdk::{
     instruction::InstructionError,
     pubkey::Pubkey,
     signature::{Keypair, Signer},
+    system_instruction::create_account,
     transaction::{Transaction, TransactionError},
 };
 use solend_program::{
     error::LendingError, instruction::init_obligation, processor::process_instruction,
+    state::Obligation,
 };
 
 #[tokio::test]
@@ -69,6 +72,7 @@ async fn test_already_initialized() {
             usdc_obligation.pubkey,
             lending_market.pubkey,
             user_accounts_owner.pubkey(),
+            None,
         )],
         Some(&payer.pubkey()),
     );
@@ -86,3 +90,55 @@ async fn test_already_initialized() {
         )
     );
 }
+
+#[tokio::test]
+async fn test_owner_as_referrer() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let obligation_keypair = Keypair::new();
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &obligation_keypair.pubkey(),
+                rent.minimum_balance(Obligation::LEN),
+                Obligation::LEN as u64,
+                &solend_program::id(),
+            ),
+            init_obligation(
+                solend_program::id(),
+                obligation_keypair.pubkey(),
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                Some(user_accounts_owner.pubkey()),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(
+        &[&payer, &obligation_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -15,7 +15,7 @@ use solend_program::{
     instruction::{init_reserve, update_reserve_config},
     math::Decimal,
     processor::process_instruction,
-    state::{ReserveConfig, ReserveFees, INITIAL_COLLATERAL_RATIO},
+    state::{ReserveConfig, ReserveFees},
 };
 
 #[tokio::test]
@@ -36,6 +36,7 @@ async fn test_success() {
     let (mut banks_client, payer, _recent_blockhash) = test.start().await;
 
     const RESERVE_AMOUNT: u64 = 42;
+    const COLLATERAL_RATIO: u64 = 5;
 
     let sol_user_liquidity_account = create_and_mint_to_token_account(
         &mut banks_client,
@@ -58,6 +59,7 @@ async fn test_success() {
         &sol_oracle,
         RESERVE_AMOUNT,
         config,
+        COLLATERAL_RATIO,
         spl_token::native_mint::id(),
         sol_user_liquidity_account,
         &fee_receiver_keypair,
@@ -79,7 +81,7 @@ async fn test_success() {
         get_token_balance(&mut banks_client, sol_reserve.user_collateral_pubkey).await;
     assert_eq!(
         user_sol_collateral_balance,
-        RESERVE_AMOUNT * INITIAL_COLLATERAL_RATIO
+        RESERVE_AMOUNT * COLLATERAL_RATIO
     );
 }
 
@@ -129,6 +131,7 @@ async fn test_init_reserve_null_oracles() {
             &all_null_oracles,
             RESERVE_AMOUNT,
             config,
+            INITIAL_COLLATERAL_RATIO,
             spl_token::native_mint::id(),
             sol_user_liquidity_account,
             &fee_receiver_keypair,
@@ -185,6 +188,7 @@ async fn test_null_switchboard() {
         &sol_oracle,
         RESERVE_AMOUNT,
         config,
+        INITIAL_COLLATERAL_RATIO,
         spl_token::native_mint::id(),
         sol_user_liquidity_account,
         &fee_receiver_keypair,
@@ -245,6 +249,7 @@ async fn test_already_initialized() {
             solend_program::id(),
             42,
             usdc_test_reserve.config,
+            usdc_test_reserve.initial_collateral_ratio,
             usdc_test_reserve.user_liquidity_pubkey,
             usdc_test_reserve.user_collateral_pubkey,
             usdc_test_reserve.pubkey,
@@ -311,6 +316,8 @@ async fn test_invalid_fees() {
             borrow_fee_wad: 1_000_000_000_000_000_001,
             flash_loan_fee_wad: 1_000_000_000_000_000_001,
             host_fee_percentage: 0,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         };
 
         let fee_receiver_keypair = Keypair::new();
@@ -324,6 +331,7 @@ async fn test_invalid_fees() {
                 &sol_oracle,
                 RESERVE_AMOUNT,
                 config,
+                INITIAL_COLLATERAL_RATIO,
                 spl_token::native_mint::id(),
                 sol_user_liquidity_account,
                 &fee_receiver_keypair,
@@ -346,6 +354,8 @@ async fn test_invalid_fees() {
             borrow_fee_wad: 10_000_000_000_000_000,
             flash_loan_fee_wad: 10_000_000_000_000_000,
             host_fee_percentage: 101,
+            deposit_fee_wad: 0,
+            redeem_fee_wad: 0,
         };
         let fee_receiver_keypair = Keypair::new();
         config.fee_receiver = fee_receiver_keypair.pubkey();
@@ -358,6 +368,68 @@ async fn test_invalid_fees() {
                 &sol_oracle,
                 RESERVE_AMOUNT,
                 config,
+                INITIAL_COLLATERAL_RATIO,
+                spl_token::native_mint::id(),
+                sol_user_liquidity_account,
+                &fee_receiver_keypair,
+                &payer,
+                &user_accounts_owner,
+            )
+            .await
+            .unwrap_err(),
+            TransactionError::InstructionError(
+                8,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    // deposit fee above 100%
+    {
+        let mut config = test_reserve_config();
+        config.fees.deposit_fee_wad = 1_000_000_000_000_000_000;
+        let fee_receiver_keypair = Keypair::new();
+        config.fee_receiver = fee_receiver_keypair.pubkey();
+
+        assert_eq!(
+            TestReserve::init(
+                "sol".to_owned(),
+                &mut banks_client,
+                &lending_market,
+                &sol_oracle,
+                RESERVE_AMOUNT,
+                config,
+                INITIAL_COLLATERAL_RATIO,
+                spl_token::native_mint::id(),
+                sol_user_liquidity_account,
+                &fee_receiver_keypair,
+                &payer,
+                &user_accounts_owner,
+            )
+            .await
+            .unwrap_err(),
+            TransactionError::InstructionError(
+                8,
+                InstructionError::Custom(LendingError::InvalidConfig as u32)
+            )
+        );
+    }
+
+    // initial collateral ratio of zero
+    {
+        let mut config = test_reserve_config();
+        let fee_receiver_keypair = Keypair::new();
+        config.fee_receiver = fee_receiver_keypair.pubkey();
+
+        assert_eq!(
+            TestReserve::init(
+                "sol".to_owned(),
+                &mut banks_client,
+                &lending_market,
+                &sol_oracle,
+                RESERVE_AMOUNT,
+                config,
+                0,
                 spl_token::native_mint::id(),
                 sol_user_liquidity_account,
                 &fee_receiver_keypair,
@@ -414,12 +486,18 @@ async fn test_update_reserve_config() {
             borrow_fee_wad: 200_000_000_000,
             flash_loan_fee_wad: 5_000_000_000_000_000,
             host_fee_percentage: 15,
+            deposit_fee_wad: 1_000_000_000_000_000,
+            redeem_fee_wad: 2_000_000_000_000_000,
         },
         deposit_limit: 1_000_000,
         borrow_limit: 300_000,
         fee_receiver: Keypair::new().pubkey(),
         protocol_liquidation_fee: 30,
         protocol_take_rate: 10,
+        deposit_limit_value: 5_000_000,
+        borrow_limit_value: 1_500_000,
+        max_obligation_deposit_percentage: 20,
+        max_obligation_borrow: 100_000,
     };
 
     let (mut banks_client, payer, recent_blockhash) = test.start().await;
@@ -443,6 +521,72 @@ async fn test_update_reserve_config() {
     assert_eq!(updated_reserve.config, new_config);
 }
 
+#[tokio::test]
+async fn test_update_reserve_config_original_layout() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mint = add_usdc_mint(&mut test);
+    let oracle = add_usdc_oracle(&mut test);
+    let mut config = test_reserve_config();
+    config.fees.deposit_fee_wad = 1_000_000_000_000_000;
+    config.deposit_limit_value = 5_000_000;
+    config.max_obligation_borrow = 100_000;
+    let test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 42,
+            liquidity_mint_decimals: mint.decimals,
+            liquidity_mint_pubkey: mint.pubkey,
+            config,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // tag, rates, borrow and flash loan fees, host fee, limits, fee receiver, protocol fees
+    const ORIGINAL_CONFIG_DATA_LEN: usize = 1 + 7 + 8 + 8 + 1 + 8 + 8 + 32 + 1 + 1;
+    let mut instruction = update_reserve_config(
+        solend_program::id(),
+        config,
+        test_reserve.pubkey,
+        lending_market.pubkey,
+        lending_market.owner.pubkey(),
+        oracle.pyth_product_pubkey,
+        oracle.pyth_price_pubkey,
+        oracle.switchboard_feed_pubkey,
+    );
+    instruction.data.truncate(ORIGINAL_CONFIG_DATA_LEN);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+    let mut transaction = Transaction::new_with_payer(&[instruction], Some(&payer.pubkey()));
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the fields appended to the config default to zero
+    let updated_reserve = test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        updated_reserve.config,
+        ReserveConfig {
+            fees: ReserveFees {
+                deposit_fee_wad: 0,
+                ..config.fees
+            },
+            deposit_limit_value: 0,
+            max_obligation_borrow: 0,
+            ..config
+        }
+    );
+}
+
 #[tokio::test]
 async fn test_update_invalid_oracle_config() {
     let mut test = ProgramTest::new(
@@ -0,0 +1,425 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use std::str::FromStr;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{
+        find_insurance_fund_address, init_insurance_fund_account, refresh_obligation,
+        refresh_reserve, set_lending_market_insurance_fund_fee, write_off_bad_debt,
+    },
+    math::{Decimal, TryMul, TrySub},
+    processor::process_instruction,
+};
+
+#[tokio::test]
+async fn test_set_insurance_fund_fee() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    const SLOTS_ELAPSED: u64 = 69420;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    // Configure reserves to a fixed borrow rate of 250%
+    const BORROW_RATE: u8 = 250;
+    let mut reserve_config = test_reserve_config();
+    reserve_config.min_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_borrow_rate = BORROW_RATE;
+    reserve_config.optimal_utilization_rate = 100;
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_oracle(
+        &mut test,
+        Pubkey::from_str(SRM_PYTH_PRODUCT).unwrap(),
+        Pubkey::from_str(SRM_PYTH_PRICE).unwrap(),
+        Pubkey::from_str(SRM_SWITCHBOARD_FEED).unwrap(),
+        // Set USDC price to $1
+        Decimal::from(1u64),
+        SLOTS_ELAPSED,
+    );
+    let add_usdc_reserve = |test: &mut ProgramTest| {
+        add_reserve(
+            test,
+            &lending_market,
+            &usdc_oracle,
+            &user_accounts_owner,
+            AddReserveArgs {
+                borrow_amount: 500 * FRACTIONAL_TO_USDC,
+                liquidity_amount: 1_000 * FRACTIONAL_TO_USDC,
+                liquidity_mint_pubkey: usdc_mint.pubkey,
+                liquidity_mint_decimals: usdc_mint.decimals,
+                config: reserve_config,
+                slots_elapsed: 1, // elapsed from 1; clock.slot = 2
+                ..AddReserveArgs::default()
+            },
+        )
+    };
+    let usdc_test_reserves = [add_usdc_reserve(&mut test), add_usdc_reserve(&mut test)];
+
+    let mut test_context = test.start_with_context().await;
+    test_context.warp_to_slot(2 + SLOTS_ELAPSED).unwrap();
+
+    let ProgramTestContext {
+        mut banks_client,
+        payer,
+        last_blockhash: recent_blockhash,
+        ..
+    } = test_context;
+
+    let [usdc_test_reserve, other_usdc_test_reserve] = &usdc_test_reserves;
+
+    // reserves passed to the instruction must be refreshed
+    let mut transaction = Transaction::new_with_payer(
+        &[set_lending_market_insurance_fund_fee(
+            solend_program::id(),
+            lending_market.pubkey,
+            lending_market.owner.pubkey(),
+            20,
+            vec![usdc_test_reserve.pubkey],
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::ReserveStale as u32)
+        )
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_reserve(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_oracle.pyth_price_pubkey,
+                usdc_oracle.switchboard_feed_pubkey,
+            ),
+            set_lending_market_insurance_fund_fee(
+                solend_program::id(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+                20,
+                vec![usdc_test_reserve.pubkey],
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let lending_market_info = lending_market.get_state(&mut banks_client).await;
+    assert_eq!(lending_market_info.insurance_fund_fee_percentage, 20);
+
+    // interest accrued before the update was split with the previous percentage
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(usdc_reserve.insurance_fund_fee_percentage, 20);
+    assert!(usdc_reserve.liquidity.accumulated_protocol_fees_wads > Decimal::zero());
+    assert_eq!(
+        usdc_reserve.liquidity.accumulated_insurance_fund_wads,
+        Decimal::zero()
+    );
+
+    // reserves which were not passed keep their percentage
+    let other_usdc_reserve = other_usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(other_usdc_reserve.insurance_fund_fee_percentage, 0);
+}
+
+#[tokio::test]
+async fn test_prefunded_insurance_fund_account() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        &solend_program::id(),
+        &lending_market.pubkey,
+        &usdc_mint.pubkey,
+    );
+
+    // anyone can send lamports to the insurance fund address before it is created
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(&payer.pubkey(), &insurance_fund_pubkey, 1),
+            init_insurance_fund_account(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_mint.pubkey,
+                lending_market.pubkey,
+                payer.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let insurance_fund_account = banks_client
+        .get_account(insurance_fund_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(insurance_fund_account.owner, spl_token::id());
+    assert_eq!(
+        get_token_balance(&mut banks_client, insurance_fund_pubkey).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_write_off_bad_debt() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(60_000);
+
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+    const USDC_BAD_DEBT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const INSURANCE_FUND_FRACTIONAL: u64 = 40 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: USDC_BAD_DEBT_FRACTIONAL,
+            collateral_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    // all of the collateral has been liquidated, leaving an insolvent borrow
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            borrows: &[(&usdc_test_reserve, USDC_BAD_DEBT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[init_insurance_fund_account(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+            usdc_mint.pubkey,
+            lending_market.pubkey,
+            payer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let (insurance_fund_pubkey, _bump_seed) = find_insurance_fund_address(
+        &solend_program::id(),
+        &lending_market.pubkey,
+        &usdc_mint.pubkey,
+    );
+    mint_to(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        insurance_fund_pubkey,
+        &usdc_mint.authority,
+        INSURANCE_FUND_FRACTIONAL,
+    )
+    .await;
+
+    let initial_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    let initial_liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![usdc_test_reserve.pubkey],
+            ),
+            write_off_bad_debt(
+                solend_program::id(),
+                test_obligation.pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_mint.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert!(obligation.borrows.is_empty());
+
+    let insurance_fund_balance = get_token_balance(&mut banks_client, insurance_fund_pubkey).await;
+    assert_eq!(insurance_fund_balance, 0);
+
+    let liquidity_supply =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_supply_pubkey).await;
+    assert_eq!(
+        liquidity_supply,
+        initial_liquidity_supply + INSURANCE_FUND_FRACTIONAL
+    );
+
+    // the insurance fund absorbs the bad debt first and depositors cover the rest
+    let usdc_reserve = usdc_test_reserve.get_state(&mut banks_client).await;
+    assert_eq!(
+        usdc_reserve.liquidity.available_amount,
+        initial_reserve.liquidity.available_amount + INSURANCE_FUND_FRACTIONAL
+    );
+    assert_eq!(
+        usdc_reserve.liquidity.borrowed_amount_wads,
+        initial_reserve
+            .liquidity
+            .borrowed_amount_wads
+            .try_sub(Decimal::from(USDC_BAD_DEBT_FRACTIONAL))
+            .unwrap()
+    );
+    assert!(
+        usdc_reserve.collateral_exchange_rate().unwrap().0
+            > initial_reserve.collateral_exchange_rate().unwrap().0
+    );
+}
+
+#[tokio::test]
+async fn test_obligation_has_collateral() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 1_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            borrow_amount: 100 * FRACTIONAL_TO_USDC,
+            collateral_amount: 1_000 * FRACTIONAL_TO_USDC,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&usdc_test_reserve, 1_000 * FRACTIONAL_TO_USDC)],
+            borrows: &[(&usdc_test_reserve, 100 * FRACTIONAL_TO_USDC)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![usdc_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            write_off_bad_debt(
+                solend_program::id(),
+                test_obligation.pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_mint.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ObligationHasCollateral as u32)
+        )
+    );
+}
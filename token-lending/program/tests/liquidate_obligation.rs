@@ -12,7 +12,6 @@ use solana_sdk::{
 use solend_program::{
     instruction::{liquidate_obligation, refresh_obligation},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
 };
 use spl_token::instruction::approve;
 
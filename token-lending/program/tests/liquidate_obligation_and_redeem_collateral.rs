@@ -12,7 +12,6 @@ use solana_sdk::{
 use solend_program::{
     instruction::{liquidate_obligation_and_redeem_reserve_collateral, refresh_obligation},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
 };
 use std::cmp::max;
 
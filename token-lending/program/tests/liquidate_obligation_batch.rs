@@ -0,0 +1,361 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{liquidate_obligation_batch, refresh_obligation, BatchLiquidation},
+    processor::process_instruction,
+};
+use spl_token::instruction::approve;
+
+// 100 SOL collateral, split across two reserves
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 50 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+// 100 SOL * 80% LTV -> 80 SOL * 20 USDC -> 1600 USDC borrow, split across two reserves
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 800 * FRACTIONAL_TO_USDC;
+
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+
+struct BatchLiquidationTest {
+    test: ProgramTest,
+    user_accounts_owner: Keypair,
+    lending_market: TestLendingMarket,
+    sol_test_reserves: [TestReserve; 2],
+    usdc_test_reserves: [TestReserve; 2],
+    test_obligation: TestObligation,
+}
+
+fn setup() -> BatchLiquidationTest {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config.liquidation_threshold = 80;
+    reserve_config.liquidation_bonus = 10;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let add_sol_reserve = |test: &mut ProgramTest| {
+        let mut reserve_config = reserve_config;
+        reserve_config.fee_receiver = Keypair::new().pubkey();
+        add_reserve(
+            test,
+            &lending_market,
+            &sol_oracle,
+            &user_accounts_owner,
+            AddReserveArgs {
+                collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+                liquidity_mint_pubkey: spl_token::native_mint::id(),
+                liquidity_mint_decimals: 9,
+                config: reserve_config,
+                mark_fresh: true,
+                ..AddReserveArgs::default()
+            },
+        )
+    };
+    let sol_test_reserves = [add_sol_reserve(&mut test), add_sol_reserve(&mut test)];
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let add_usdc_reserve = |test: &mut ProgramTest| {
+        let mut reserve_config = reserve_config;
+        reserve_config.fee_receiver = Keypair::new().pubkey();
+        add_reserve(
+            test,
+            &lending_market,
+            &usdc_oracle,
+            &user_accounts_owner,
+            AddReserveArgs {
+                borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+                user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+                liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+                liquidity_mint_pubkey: usdc_mint.pubkey,
+                liquidity_mint_decimals: usdc_mint.decimals,
+                config: reserve_config,
+                mark_fresh: true,
+                ..AddReserveArgs::default()
+            },
+        )
+    };
+    let usdc_test_reserves = [add_usdc_reserve(&mut test), add_usdc_reserve(&mut test)];
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[
+                (&sol_test_reserves[0], SOL_DEPOSIT_AMOUNT_LAMPORTS),
+                (&sol_test_reserves[1], SOL_DEPOSIT_AMOUNT_LAMPORTS),
+            ],
+            borrows: &[
+                (&usdc_test_reserves[0], USDC_BORROW_AMOUNT_FRACTIONAL),
+                (&usdc_test_reserves[1], USDC_BORROW_AMOUNT_FRACTIONAL),
+            ],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    BatchLiquidationTest {
+        test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserves,
+        usdc_test_reserves,
+        test_obligation,
+    }
+}
+
+fn batch_liquidation(
+    liquidity_amount: u64,
+    repay_reserve: &TestReserve,
+    withdraw_reserve: &TestReserve,
+) -> BatchLiquidation {
+    BatchLiquidation {
+        liquidity_amount,
+        source_liquidity_pubkey: repay_reserve.user_liquidity_pubkey,
+        destination_collateral_pubkey: withdraw_reserve.user_collateral_pubkey,
+        repay_reserve_pubkey: repay_reserve.pubkey,
+        repay_reserve_liquidity_supply_pubkey: repay_reserve.liquidity_supply_pubkey,
+        withdraw_reserve_pubkey: withdraw_reserve.pubkey,
+        withdraw_reserve_collateral_supply_pubkey: withdraw_reserve.collateral_supply_pubkey,
+    }
+}
+
+#[tokio::test]
+async fn test_success() {
+    let BatchLiquidationTest {
+        test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserves,
+        usdc_test_reserves,
+        test_obligation,
+    } = setup();
+
+    // 1600 USDC * 20% -> 320 USDC may be liquidated across the whole batch
+    const USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = 200 * FRACTIONAL_TO_USDC;
+    // only 120 USDC of the requested 200 USDC remains under the close factor
+    const USDC_SECOND_LIQUIDATION_AMOUNT_FRACTIONAL: u64 = 120 * FRACTIONAL_TO_USDC;
+    // 200 USDC / 20 USDC per SOL -> 10 SOL + 10% bonus -> 11 SOL
+    const SOL_FIRST_LIQUIDATION_AMOUNT_LAMPORTS: u64 =
+        LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO * 11;
+    // 120 USDC / 20 USDC per SOL -> 6 SOL + 10% bonus -> 6.6 SOL (33/5)
+    const SOL_SECOND_LIQUIDATION_AMOUNT_LAMPORTS: u64 =
+        LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO * 33 / 5;
+
+    let user_transfer_authority = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut initial_user_collateral_balances = [0; 2];
+    let mut initial_collateral_supply_balances = [0; 2];
+    for (i, sol_test_reserve) in sol_test_reserves.iter().enumerate() {
+        initial_user_collateral_balances[i] =
+            get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await;
+        initial_collateral_supply_balances[i] =
+            get_token_balance(&mut banks_client, sol_test_reserve.collateral_supply_pubkey).await;
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserves[0].user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserves[1].user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![
+                    sol_test_reserves[0].pubkey,
+                    sol_test_reserves[1].pubkey,
+                    usdc_test_reserves[0].pubkey,
+                    usdc_test_reserves[1].pubkey,
+                ],
+            ),
+            liquidate_obligation_batch(
+                solend_program::id(),
+                &[
+                    batch_liquidation(
+                        USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL,
+                        &usdc_test_reserves[0],
+                        &sol_test_reserves[0],
+                    ),
+                    batch_liquidation(
+                        USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL,
+                        &usdc_test_reserves[1],
+                        &sol_test_reserves[1],
+                    ),
+                ],
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let user_liquidity_balances = [
+        get_token_balance(
+            &mut banks_client,
+            usdc_test_reserves[0].user_liquidity_pubkey,
+        )
+        .await,
+        get_token_balance(
+            &mut banks_client,
+            usdc_test_reserves[1].user_liquidity_pubkey,
+        )
+        .await,
+    ];
+    assert_eq!(
+        user_liquidity_balances,
+        [
+            USDC_BORROW_AMOUNT_FRACTIONAL - USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL,
+            USDC_BORROW_AMOUNT_FRACTIONAL - USDC_SECOND_LIQUIDATION_AMOUNT_FRACTIONAL,
+        ]
+    );
+
+    let sol_liquidation_amounts = [
+        SOL_FIRST_LIQUIDATION_AMOUNT_LAMPORTS,
+        SOL_SECOND_LIQUIDATION_AMOUNT_LAMPORTS,
+    ];
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    for (i, sol_test_reserve) in sol_test_reserves.iter().enumerate() {
+        let user_collateral_balance =
+            get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await;
+        assert_eq!(
+            user_collateral_balance,
+            initial_user_collateral_balances[i] + sol_liquidation_amounts[i]
+        );
+
+        let collateral_supply_balance =
+            get_token_balance(&mut banks_client, sol_test_reserve.collateral_supply_pubkey).await;
+        assert_eq!(
+            collateral_supply_balance,
+            initial_collateral_supply_balances[i] - sol_liquidation_amounts[i]
+        );
+
+        assert_eq!(
+            obligation.deposits[i].deposited_amount,
+            SOL_DEPOSIT_AMOUNT_LAMPORTS - sol_liquidation_amounts[i]
+        );
+    }
+    assert_eq!(
+        obligation.borrows[0].borrowed_amount_wads,
+        (USDC_BORROW_AMOUNT_FRACTIONAL - USDC_FIRST_LIQUIDATION_AMOUNT_FRACTIONAL).into()
+    );
+    assert_eq!(
+        obligation.borrows[1].borrowed_amount_wads,
+        (USDC_BORROW_AMOUNT_FRACTIONAL - USDC_SECOND_LIQUIDATION_AMOUNT_FRACTIONAL).into()
+    );
+}
+
+#[tokio::test]
+async fn test_fail_close_factor_reached() {
+    let BatchLiquidationTest {
+        test,
+        user_accounts_owner,
+        lending_market,
+        sol_test_reserves,
+        usdc_test_reserves,
+        test_obligation,
+    } = setup();
+
+    let user_transfer_authority = Keypair::new();
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserves[0].user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            approve(
+                &spl_token::id(),
+                &usdc_test_reserves[1].user_liquidity_pubkey,
+                &user_transfer_authority.pubkey(),
+                &user_accounts_owner.pubkey(),
+                &[],
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+            )
+            .unwrap(),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![
+                    sol_test_reserves[0].pubkey,
+                    sol_test_reserves[1].pubkey,
+                    usdc_test_reserves[0].pubkey,
+                    usdc_test_reserves[1].pubkey,
+                ],
+            ),
+            liquidate_obligation_batch(
+                solend_program::id(),
+                &[
+                    batch_liquidation(u64::MAX, &usdc_test_reserves[0], &sol_test_reserves[0]),
+                    batch_liquidation(u64::MAX, &usdc_test_reserves[1], &sol_test_reserves[1]),
+                ],
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_transfer_authority.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &user_accounts_owner, &user_transfer_authority],
+        recent_blockhash,
+    );
+
+    // the first liquidation uses up the whole close factor
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            3,
+            InstructionError::Custom(LendingError::LiquidationTooSmall as u32)
+        )
+    );
+}
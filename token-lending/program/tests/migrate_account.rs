@@ -0,0 +1,215 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::migrate_account,
+    processor::process_instruction,
+    state::{FlashLoanWhitelist, LendingMarket, Obligation, Reserve, PROGRAM_VERSION},
+};
+use std::fs;
+
+const LENDING_MARKET: &str = "lending_market";
+const RESERVE: &str = "reserve";
+const OBLIGATION: &str = "obligation";
+const FLASH_LOAN_WHITELIST: &str = "flash_loan_whitelist";
+
+/// Fixture account type, version and filename suffix for every historical layout
+const FIXTURES: &[(&str, u8, &str)] = &[
+    (LENDING_MARKET, 1, ""),
+    (RESERVE, 1, ""),
+    (OBLIGATION, 1, ""),
+    (OBLIGATION, 1, "_legacy"),
+    (FLASH_LOAN_WHITELIST, 1, ""),
+    (LENDING_MARKET, 2, ""),
+    (RESERVE, 2, ""),
+    (OBLIGATION, 2, ""),
+    (OBLIGATION, 2, "_legacy"),
+    (FLASH_LOAN_WHITELIST, 2, ""),
+];
+
+fn fixture_filename(account_type: &str, version: u8, suffix: &str) -> String {
+    format!("{}_v{}{}.bin", account_type, version, suffix)
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(120_000);
+
+    let fixtures: Vec<(Pubkey, &str, u8, String)> = FIXTURES
+        .iter()
+        .map(|(account_type, version, suffix)| {
+            (
+                Pubkey::new_unique(),
+                *account_type,
+                *version,
+                fixture_filename(account_type, *version, suffix),
+            )
+        })
+        .collect();
+    for (pubkey, _, _, filename) in &fixtures {
+        test.add_account_with_file_data(*pubkey, u32::MAX as u64, solend_program::id(), filename);
+    }
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &fixtures
+            .iter()
+            .map(|(pubkey, _, _, _)| migrate_account(solend_program::id(), *pubkey))
+            .collect::<Vec<_>>(),
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    for (pubkey, account_type, version, filename) in &fixtures {
+        let fixture_data = fs::read(format!("tests/fixtures/{}", filename)).unwrap();
+        let data = banks_client
+            .get_account(*pubkey)
+            .await
+            .unwrap()
+            .unwrap()
+            .data;
+        match *account_type {
+            LENDING_MARKET => {
+                assert_eq!(data.len(), fixture_data.len());
+                let expected = LendingMarket::unpack(&fixture_data).unwrap();
+                let migrated = LendingMarket::unpack(&data).unwrap();
+                assert_eq!(expected.version, *version);
+                assert_eq!(
+                    migrated,
+                    LendingMarket {
+                        version: PROGRAM_VERSION,
+                        ..expected
+                    }
+                );
+            }
+            RESERVE => {
+                assert_eq!(data.len(), fixture_data.len());
+                let expected = Reserve::unpack(&fixture_data).unwrap();
+                let migrated = Reserve::unpack(&data).unwrap();
+                assert_eq!(expected.version, *version);
+                assert_eq!(
+                    migrated,
+                    Reserve {
+                        version: PROGRAM_VERSION,
+                        ..expected
+                    }
+                );
+            }
+            OBLIGATION => {
+                // legacy obligations keep their length
+                assert_eq!(data.len(), fixture_data.len());
+                let expected = Obligation::unpack(&fixture_data).unwrap();
+                let migrated = Obligation::unpack(&data).unwrap();
+                assert_eq!(expected.version, *version);
+                assert_eq!(
+                    migrated,
+                    Obligation {
+                        version: PROGRAM_VERSION,
+                        ..expected
+                    }
+                );
+            }
+            FLASH_LOAN_WHITELIST => {
+                assert_eq!(data.len(), fixture_data.len());
+                let expected = FlashLoanWhitelist::unpack(&fixture_data).unwrap();
+                let migrated = FlashLoanWhitelist::unpack(&data).unwrap();
+                assert_eq!(expected.version, *version);
+                assert_eq!(
+                    migrated,
+                    FlashLoanWhitelist {
+                        version: PROGRAM_VERSION,
+                        ..expected
+                    }
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+#[test]
+fn test_unmigrated_reserve() {
+    for version in [1, 2] {
+        let data = fs::read(format!(
+            "tests/fixtures/{}",
+            fixture_filename(RESERVE, version, "")
+        ))
+        .unwrap();
+        let reserve = Reserve::unpack(&data).unwrap();
+        assert_eq!(reserve.version, version);
+        // the initial collateral ratio was fixed at 1 before it was stored in the reserve
+        assert_eq!(reserve.collateral.initial_collateral_ratio, 1);
+        assert!(reserve.collateral_exchange_rate().is_ok());
+    }
+}
+
+#[tokio::test]
+async fn test_already_migrated() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 1_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[migrate_account(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
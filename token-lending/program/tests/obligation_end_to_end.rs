@@ -19,7 +19,7 @@ use solend_program::{
     },
     math::Decimal,
     processor::process_instruction,
-    state::{Obligation, INITIAL_COLLATERAL_RATIO},
+    state::Obligation,
 };
 use spl_token::{instruction::approve, solana_program::program_pack::Pack};
 
@@ -121,6 +121,7 @@ async fn test_success() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_accounts_owner_pubkey,
+                None,
             ),
             // 2
             approve(
@@ -380,6 +381,7 @@ async fn test_success2() {
                 obligation_pubkey,
                 lending_market.pubkey,
                 user_accounts_owner_pubkey,
+                None,
             ),
             // 2
             approve(
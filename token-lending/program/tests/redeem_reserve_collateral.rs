@@ -9,10 +9,7 @@ use solana_sdk::{
     signature::{Keypair, Signer},
     transaction::Transaction,
 };
-use solend_program::{
-    instruction::redeem_reserve_collateral, processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
-};
+use solend_program::{instruction::redeem_reserve_collateral, processor::process_instruction};
 use spl_token::instruction::approve;
 
 #[tokio::test]
@@ -0,0 +1,354 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    system_instruction,
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{
+        borrow_obligation_liquidity, claim_referral_fees, find_referrer_fees_address,
+        init_referrer_fees_account, refresh_obligation,
+    },
+    processor::process_instruction,
+};
+
+const USDC_TOTAL_BORROW_FRACTIONAL: u64 = 1_000 * FRACTIONAL_TO_USDC;
+const FEE_AMOUNT: u64 = 100;
+const HOST_FEE_AMOUNT: u64 = 20;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = USDC_TOTAL_BORROW_FRACTIONAL - FEE_AMOUNT;
+const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_TOTAL_BORROW_FRACTIONAL;
+
+struct TestSetup {
+    lending_market: TestLendingMarket,
+    usdc_mint: TestMint,
+    sol_test_reserve: TestReserve,
+    usdc_test_reserve: TestReserve,
+    test_obligation: TestObligation,
+}
+
+fn setup(test: &mut ProgramTest, user_accounts_owner: &Keypair, referrer: &Keypair) -> TestSetup {
+    let lending_market = add_lending_market(test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_oracle(test);
+    let sol_test_reserve = add_reserve(
+        test,
+        &lending_market,
+        &sol_oracle,
+        user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(test);
+    let usdc_oracle = add_usdc_oracle(test);
+    let usdc_test_reserve = add_reserve(
+        test,
+        &lending_market,
+        &usdc_oracle,
+        user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        test,
+        &lending_market,
+        user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            referrer: referrer.pubkey(),
+            ..AddObligationArgs::default()
+        },
+    );
+
+    TestSetup {
+        lending_market,
+        usdc_mint,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    }
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(60_000);
+
+    let user_accounts_owner = Keypair::new();
+    let referrer = Keypair::new();
+    let TestSetup {
+        lending_market,
+        usdc_mint,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+    } = setup(&mut test, &user_accounts_owner, &referrer);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (referrer_fees_pubkey, _) = find_referrer_fees_address(
+        &solend_program::id(),
+        &usdc_test_reserve.pubkey,
+        &referrer.pubkey(),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            init_referrer_fees_account(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_mint.pubkey,
+                lending_market.pubkey,
+                referrer.pubkey(),
+                payer.pubkey(),
+            ),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                solend_program::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                Some(referrer_fees_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let referrer_fees_balance = get_token_balance(&mut banks_client, referrer_fees_pubkey).await;
+    assert_eq!(referrer_fees_balance, HOST_FEE_AMOUNT);
+
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, FEE_AMOUNT - HOST_FEE_AMOUNT);
+
+    let mut transaction = Transaction::new_with_payer(
+        &[claim_referral_fees(
+            solend_program::id(),
+            usdc_test_reserve.pubkey,
+            usdc_test_reserve.liquidity_host_pubkey,
+            lending_market.pubkey,
+            referrer.pubkey(),
+        )],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &referrer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let referrer_fees_balance = get_token_balance(&mut banks_client, referrer_fees_pubkey).await;
+    assert_eq!(referrer_fees_balance, 0);
+
+    let host_fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(host_fee_balance, HOST_FEE_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_prefunded_referrer_fees_account() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let referrer = Keypair::new();
+    let TestSetup {
+        lending_market,
+        usdc_mint,
+        usdc_test_reserve,
+        ..
+    } = setup(&mut test, &user_accounts_owner, &referrer);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (referrer_fees_pubkey, _) = find_referrer_fees_address(
+        &solend_program::id(),
+        &usdc_test_reserve.pubkey,
+        &referrer.pubkey(),
+    );
+
+    // anyone can send lamports to the referrer fees address before it is created
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            system_instruction::transfer(&payer.pubkey(), &referrer_fees_pubkey, 1),
+            init_referrer_fees_account(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_mint.pubkey,
+                lending_market.pubkey,
+                referrer.pubkey(),
+                payer.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let referrer_fees_account = banks_client
+        .get_account(referrer_fees_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(referrer_fees_account.owner, spl_token::id());
+    assert_eq!(
+        get_token_balance(&mut banks_client, referrer_fees_pubkey).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_uninitialized_referrer_fees_account() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let referrer = Keypair::new();
+    let TestSetup {
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+        ..
+    } = setup(&mut test, &user_accounts_owner, &referrer);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let (referrer_fees_pubkey, _) = find_referrer_fees_address(
+        &solend_program::id(),
+        &usdc_test_reserve.pubkey,
+        &referrer.pubkey(),
+    );
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                solend_program::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                Some(referrer_fees_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // the host fee goes to the reserve fee receiver until the referrer fees account exists
+    let fee_balance =
+        get_token_balance(&mut banks_client, usdc_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, FEE_AMOUNT);
+}
+
+#[tokio::test]
+async fn test_invalid_host_fee_receiver() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let referrer = Keypair::new();
+    let TestSetup {
+        lending_market,
+        sol_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+        ..
+    } = setup(&mut test, &user_accounts_owner, &referrer);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            borrow_obligation_liquidity(
+                solend_program::id(),
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                test_obligation.owner,
+                Some(usdc_test_reserve.liquidity_host_pubkey),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -15,7 +15,7 @@ use solend_program::{
     instruction::{refresh_obligation, refresh_reserve},
     math::{Decimal, TryDiv},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
+    state::MAX_OBLIGATION_RESERVES,
 };
 
 #[tokio::test]
@@ -182,3 +182,124 @@ async fn test_success() {
     assert_eq!(sol_reserve.liquidity.market_price, collateral_price,);
     assert_eq!(usdc_reserve.liquidity.market_price, liquidity_price,);
 }
+
+#[tokio::test]
+async fn test_success_max_reserves() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // default per-instruction compute budget
+    test.set_bpf_compute_max_units(200_000);
+
+    const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+    const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 100 * FRACTIONAL_TO_USDC;
+    const SOL_RESERVE_COLLATERAL_LAMPORTS: u64 = 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS;
+    const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2 * USDC_BORROW_AMOUNT_FRACTIONAL;
+    const RESERVES_PER_SIDE: usize = MAX_OBLIGATION_RESERVES / 2;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserves: Vec<TestReserve> = (0..RESERVES_PER_SIDE)
+        .map(|_| {
+            add_reserve(
+                &mut test,
+                &lending_market,
+                &sol_oracle,
+                &user_accounts_owner,
+                AddReserveArgs {
+                    collateral_amount: SOL_RESERVE_COLLATERAL_LAMPORTS,
+                    liquidity_mint_decimals: 9,
+                    liquidity_mint_pubkey: spl_token::native_mint::id(),
+                    config: reserve_config,
+                    mark_fresh: true,
+                    ..AddReserveArgs::default()
+                },
+            )
+        })
+        .collect();
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserves: Vec<TestReserve> = (0..RESERVES_PER_SIDE)
+        .map(|_| {
+            add_reserve(
+                &mut test,
+                &lending_market,
+                &usdc_oracle,
+                &user_accounts_owner,
+                AddReserveArgs {
+                    borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+                    liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+                    liquidity_mint_decimals: usdc_mint.decimals,
+                    liquidity_mint_pubkey: usdc_mint.pubkey,
+                    config: reserve_config,
+                    mark_fresh: true,
+                    ..AddReserveArgs::default()
+                },
+            )
+        })
+        .collect();
+
+    let deposits: Vec<(&TestReserve, u64)> = sol_test_reserves
+        .iter()
+        .map(|reserve| (reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS))
+        .collect();
+    let borrows: Vec<(&TestReserve, u64)> = usdc_test_reserves
+        .iter()
+        .map(|reserve| (reserve, USDC_BORROW_AMOUNT_FRACTIONAL))
+        .collect();
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &deposits,
+            borrows: &borrows,
+            ..AddObligationArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[refresh_obligation(
+            solend_program::id(),
+            test_obligation.pubkey,
+            sol_test_reserves
+                .iter()
+                .chain(usdc_test_reserves.iter())
+                .map(|reserve| reserve.pubkey)
+                .collect(),
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(
+        obligation.deposits.len() + obligation.borrows.len(),
+        MAX_OBLIGATION_RESERVES
+    );
+    assert!(!obligation.last_update.stale);
+
+    let deposited_value = obligation.deposits[0]
+        .market_value
+        .try_mul(RESERVES_PER_SIDE as u64)
+        .unwrap();
+    let borrowed_value = obligation.borrows[0]
+        .market_value
+        .try_mul(RESERVES_PER_SIDE as u64)
+        .unwrap();
+    assert_eq!(obligation.deposited_value, deposited_value);
+    assert_eq!(obligation.borrowed_value, borrowed_value);
+}
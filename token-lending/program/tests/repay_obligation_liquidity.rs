@@ -13,7 +13,6 @@ use solend_program::{
     instruction::repay_obligation_liquidity,
     math::{Decimal, Rate, TryAdd, TryMul, TrySub},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
 };
 use spl_token::instruction::approve;
 
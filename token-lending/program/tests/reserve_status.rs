@@ -0,0 +1,287 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::{program_option::COption, program_pack::Pack};
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{close_reserve, deposit_reserve_liquidity, set_reserve_status},
+    processor::process_instruction,
+    state::ReserveStatus,
+};
+use spl_token::{
+    instruction::{set_authority, AuthorityType},
+    state::Mint,
+};
+
+#[tokio::test]
+async fn test_reduce_only() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            user_liquidity_amount: 100 * FRACTIONAL_TO_USDC,
+            liquidity_amount: 10_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_reserve_status(
+                solend_program::id(),
+                ReserveStatus::ReduceOnly,
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+            deposit_reserve_liquidity(
+                solend_program::id(),
+                100 * FRACTIONAL_TO_USDC,
+                usdc_test_reserve.user_liquidity_pubkey,
+                usdc_test_reserve.user_collateral_pubkey,
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.collateral_mint_pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &lending_market.owner, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ReserveNotActive as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_close_reserve() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    const UNCLAIMED_FEES_FRACTIONAL: u64 = 10 * FRACTIONAL_TO_USDC;
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    mint_to(
+        &mut banks_client,
+        usdc_mint.pubkey,
+        &payer,
+        usdc_test_reserve.config.fee_receiver,
+        &usdc_mint.authority,
+        UNCLAIMED_FEES_FRACTIONAL,
+    )
+    .await;
+
+    let destination_pubkey = Pubkey::new_unique();
+    let mut closed_lamports = 0;
+    for pubkey in [
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.config.fee_receiver,
+        usdc_test_reserve.collateral_supply_pubkey,
+    ] {
+        closed_lamports += banks_client
+            .get_account(pubkey)
+            .await
+            .unwrap()
+            .unwrap()
+            .lamports;
+    }
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            // InitReserve hands the fee receiver to the lending market authority
+            set_authority(
+                &spl_token::id(),
+                &usdc_test_reserve.config.fee_receiver,
+                Some(&lending_market.authority),
+                AuthorityType::AccountOwner,
+                &lending_market.owner.pubkey(),
+                &[],
+            )
+            .unwrap(),
+            set_reserve_status(
+                solend_program::id(),
+                ReserveStatus::Deprecated,
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+            close_reserve(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.collateral_supply_pubkey,
+                usdc_test_reserve.liquidity_host_pubkey,
+                destination_pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    for pubkey in [
+        usdc_test_reserve.pubkey,
+        usdc_test_reserve.liquidity_supply_pubkey,
+        usdc_test_reserve.config.fee_receiver,
+        usdc_test_reserve.collateral_supply_pubkey,
+    ] {
+        assert!(banks_client.get_account(pubkey).await.unwrap().is_none());
+    }
+
+    let destination_account = banks_client
+        .get_account(destination_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination_account.lamports, closed_lamports);
+
+    let swept_fees =
+        get_token_balance(&mut banks_client, usdc_test_reserve.liquidity_host_pubkey).await;
+    assert_eq!(swept_fees, UNCLAIMED_FEES_FRACTIONAL);
+
+    let collateral_mint_account = banks_client
+        .get_account(usdc_test_reserve.collateral_mint_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    let collateral_mint = Mint::unpack(&collateral_mint_account.data[..]).unwrap();
+    assert_eq!(collateral_mint.mint_authority, COption::None);
+}
+
+#[tokio::test]
+async fn test_reserve_not_empty() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 1_000 * FRACTIONAL_TO_USDC,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            collateral_amount: 1_000 * FRACTIONAL_TO_USDC,
+            config: test_reserve_config(),
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_reserve_status(
+                solend_program::id(),
+                ReserveStatus::Deprecated,
+                usdc_test_reserve.pubkey,
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+            close_reserve(
+                solend_program::id(),
+                usdc_test_reserve.pubkey,
+                usdc_test_reserve.liquidity_supply_pubkey,
+                usdc_test_reserve.config.fee_receiver,
+                usdc_test_reserve.collateral_mint_pubkey,
+                usdc_test_reserve.collateral_supply_pubkey,
+                usdc_test_reserve.liquidity_host_pubkey,
+                Pubkey::new_unique(),
+                lending_market.pubkey,
+                lending_market.owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &lending_market.owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::ReserveNotEmpty as u32)
+        )
+    );
+}
@@ -0,0 +1,284 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{refresh_obligation, set_obligation_delegate, withdraw_obligation_collateral},
+    processor::process_instruction,
+    state::{
+        OBLIGATION_DELEGATE_BORROW, OBLIGATION_DELEGATE_DEPOSIT, OBLIGATION_DELEGATE_WITHDRAW,
+    },
+};
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const WITHDRAW_AMOUNT: u64 = 10 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+
+fn setup() -> (
+    ProgramTest,
+    TestLendingMarket,
+    TestReserve,
+    TestObligation,
+    Keypair,
+) {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: 2 * SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: test_reserve_config(),
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        lending_market,
+        sol_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (mut test, lending_market, _, test_obligation, user_accounts_owner) = setup();
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(12_000);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let delegate = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_obligation_delegate(
+            solend_program::id(),
+            test_obligation.pubkey,
+            lending_market.pubkey,
+            user_accounts_owner.pubkey(),
+            delegate.pubkey(),
+            OBLIGATION_DELEGATE_DEPOSIT | OBLIGATION_DELEGATE_BORROW,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.delegate, delegate.pubkey());
+    assert_eq!(
+        obligation.delegate_permissions,
+        OBLIGATION_DELEGATE_DEPOSIT | OBLIGATION_DELEGATE_BORROW
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_owner() {
+    let (test, lending_market, _, test_obligation, _) = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let invalid_owner = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[set_obligation_delegate(
+            solend_program::id(),
+            test_obligation.pubkey,
+            lending_market.pubkey,
+            invalid_owner.pubkey(),
+            invalid_owner.pubkey(),
+            OBLIGATION_DELEGATE_WITHDRAW,
+        )],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &invalid_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            0,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_delegate_withdraw() {
+    let (test, lending_market, sol_test_reserve, test_obligation, user_accounts_owner) = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let delegate = Keypair::new();
+    let initial_user_collateral_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_obligation_delegate(
+                solend_program::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                delegate.pubkey(),
+                OBLIGATION_DELEGATE_WITHDRAW,
+            ),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            withdraw_obligation_collateral(
+                solend_program::id(),
+                WITHDRAW_AMOUNT,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                delegate.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner, &delegate], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let user_collateral_balance =
+        get_token_balance(&mut banks_client, sol_test_reserve.user_collateral_pubkey).await;
+    assert_eq!(
+        user_collateral_balance,
+        initial_user_collateral_balance + WITHDRAW_AMOUNT
+    );
+
+    // delegate cannot withdraw to an account it owns
+    let delegate_collateral_pubkey = create_token_account(
+        &mut banks_client,
+        sol_test_reserve.collateral_mint_pubkey,
+        &payer,
+        Some(delegate.pubkey()),
+        None,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            withdraw_obligation_collateral(
+                solend_program::id(),
+                WITHDRAW_AMOUNT,
+                sol_test_reserve.collateral_supply_pubkey,
+                delegate_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                delegate.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    let recent_blockhash = banks_client.get_recent_blockhash().await.unwrap();
+    transaction.sign(&[&payer, &delegate], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_delegate_missing_permission() {
+    let (test, lending_market, sol_test_reserve, test_obligation, user_accounts_owner) = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let delegate = Keypair::new();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            set_obligation_delegate(
+                solend_program::id(),
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                delegate.pubkey(),
+                OBLIGATION_DELEGATE_DEPOSIT,
+            ),
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey],
+            ),
+            withdraw_obligation_collateral(
+                solend_program::id(),
+                WITHDRAW_AMOUNT,
+                sol_test_reserve.collateral_supply_pubkey,
+                sol_test_reserve.user_collateral_pubkey,
+                sol_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                delegate.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner, &delegate], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            2,
+            InstructionError::Custom(LendingError::InvalidObligationOwner as u32)
+        )
+    );
+}
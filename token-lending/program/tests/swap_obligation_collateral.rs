@@ -0,0 +1,316 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{refresh_obligation, swap_obligation_collateral},
+    processor::process_instruction,
+    state::ReserveConfig,
+};
+use std::u64;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 500 * FRACTIONAL_TO_USDC;
+
+fn reserve_config() -> ReserveConfig {
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+    reserve_config
+}
+
+fn setup(
+    deposit_reserve_config: ReserveConfig,
+) -> (
+    ProgramTest,
+    TestLendingMarket,
+    TestReserve,
+    TestReserve,
+    TestReserve,
+    TestObligation,
+    Keypair,
+) {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let reserve_config = reserve_config();
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let withdraw_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let deposit_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: 100 * LAMPORTS_TO_SOL,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: deposit_reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let usdc_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&withdraw_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&usdc_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        lending_market,
+        withdraw_test_reserve,
+        deposit_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (
+        mut test,
+        lending_market,
+        withdraw_test_reserve,
+        deposit_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    ) = setup(reserve_config());
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(70_000);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_withdraw_user_collateral_balance = get_token_balance(
+        &mut banks_client,
+        withdraw_test_reserve.user_collateral_pubkey,
+    )
+    .await;
+    let initial_deposit_collateral_supply_balance = get_token_balance(
+        &mut banks_client,
+        deposit_test_reserve.collateral_supply_pubkey,
+    )
+    .await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![withdraw_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            swap_obligation_collateral(
+                solend_program::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                u64::MAX,
+                deposit_test_reserve.user_collateral_pubkey,
+                deposit_test_reserve.collateral_supply_pubkey,
+                deposit_test_reserve.pubkey,
+                withdraw_test_reserve.collateral_supply_pubkey,
+                withdraw_test_reserve.user_collateral_pubkey,
+                withdraw_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    // check that collateral tokens were transferred
+    let withdraw_user_collateral_balance = get_token_balance(
+        &mut banks_client,
+        withdraw_test_reserve.user_collateral_pubkey,
+    )
+    .await;
+    assert_eq!(
+        withdraw_user_collateral_balance,
+        initial_withdraw_user_collateral_balance + SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+    let deposit_collateral_supply_balance = get_token_balance(
+        &mut banks_client,
+        deposit_test_reserve.collateral_supply_pubkey,
+    )
+    .await;
+    assert_eq!(
+        deposit_collateral_supply_balance,
+        initial_deposit_collateral_supply_balance + SOL_DEPOSIT_AMOUNT_LAMPORTS
+    );
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.deposits.len(), 1);
+    let collateral = &obligation.deposits[0];
+    assert_eq!(collateral.deposit_reserve, deposit_test_reserve.pubkey);
+    assert_eq!(collateral.deposited_amount, SOL_DEPOSIT_AMOUNT_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_withdraw_too_large() {
+    let (
+        test,
+        lending_market,
+        withdraw_test_reserve,
+        deposit_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    ) = setup(reserve_config());
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![withdraw_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            swap_obligation_collateral(
+                solend_program::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS / 10,
+                u64::MAX,
+                deposit_test_reserve.user_collateral_pubkey,
+                deposit_test_reserve.collateral_supply_pubkey,
+                deposit_test_reserve.pubkey,
+                withdraw_test_reserve.collateral_supply_pubkey,
+                withdraw_test_reserve.user_collateral_pubkey,
+                withdraw_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::WithdrawTooLarge as u32)
+        )
+    );
+}
+
+#[tokio::test]
+async fn test_max_obligation_deposit_percentage() {
+    // the swap would deposit all of the deposit reserve collateral into the obligation
+    let mut deposit_reserve_config = reserve_config();
+    deposit_reserve_config.max_obligation_deposit_percentage = 50;
+
+    let (
+        test,
+        lending_market,
+        withdraw_test_reserve,
+        deposit_test_reserve,
+        usdc_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    ) = setup(deposit_reserve_config);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![withdraw_test_reserve.pubkey, usdc_test_reserve.pubkey],
+            ),
+            swap_obligation_collateral(
+                solend_program::id(),
+                SOL_DEPOSIT_AMOUNT_LAMPORTS,
+                u64::MAX,
+                deposit_test_reserve.user_collateral_pubkey,
+                deposit_test_reserve.collateral_supply_pubkey,
+                deposit_test_reserve.pubkey,
+                withdraw_test_reserve.collateral_supply_pubkey,
+                withdraw_test_reserve.user_collateral_pubkey,
+                withdraw_test_reserve.pubkey,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAmount as u32)
+        )
+    );
+}
@@ -0,0 +1,264 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program_test::*;
+use solana_sdk::{
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::{refresh_obligation, swap_obligation_debt},
+    math::Decimal,
+    processor::process_instruction,
+    state::FeeCalculation,
+};
+use std::u64;
+
+const SOL_DEPOSIT_AMOUNT_LAMPORTS: u64 = 100 * LAMPORTS_TO_SOL * INITIAL_COLLATERAL_RATIO;
+const USDC_BORROW_AMOUNT_FRACTIONAL: u64 = 900 * FRACTIONAL_TO_USDC;
+const USDC_RESERVE_LIQUIDITY_FRACTIONAL: u64 = 2_000 * FRACTIONAL_TO_USDC;
+
+fn setup() -> (
+    ProgramTest,
+    TestLendingMarket,
+    TestReserve,
+    TestReserve,
+    TestReserve,
+    TestObligation,
+    Keypair,
+) {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+
+    let mut reserve_config = test_reserve_config();
+    reserve_config.loan_to_value_ratio = 50;
+
+    let sol_oracle = add_sol_oracle(&mut test);
+    let sol_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &sol_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            collateral_amount: SOL_DEPOSIT_AMOUNT_LAMPORTS,
+            liquidity_mint_pubkey: spl_token::native_mint::id(),
+            liquidity_mint_decimals: 9,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let usdc_mint = add_usdc_mint(&mut test);
+    let usdc_oracle = add_usdc_oracle(&mut test);
+    let repay_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            borrow_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            user_liquidity_amount: USDC_BORROW_AMOUNT_FRACTIONAL,
+            config: reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let mut borrow_reserve_config = test_reserve_config();
+    borrow_reserve_config.loan_to_value_ratio = 50;
+
+    let borrow_test_reserve = add_reserve(
+        &mut test,
+        &lending_market,
+        &usdc_oracle,
+        &user_accounts_owner,
+        AddReserveArgs {
+            liquidity_amount: USDC_RESERVE_LIQUIDITY_FRACTIONAL,
+            liquidity_mint_pubkey: usdc_mint.pubkey,
+            liquidity_mint_decimals: usdc_mint.decimals,
+            config: borrow_reserve_config,
+            mark_fresh: true,
+            ..AddReserveArgs::default()
+        },
+    );
+
+    let test_obligation = add_obligation(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        AddObligationArgs {
+            deposits: &[(&sol_test_reserve, SOL_DEPOSIT_AMOUNT_LAMPORTS)],
+            borrows: &[(&repay_test_reserve, USDC_BORROW_AMOUNT_FRACTIONAL)],
+            ..AddObligationArgs::default()
+        },
+    );
+
+    (
+        test,
+        lending_market,
+        sol_test_reserve,
+        repay_test_reserve,
+        borrow_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    )
+}
+
+#[tokio::test]
+async fn test_success() {
+    let (
+        mut test,
+        lending_market,
+        sol_test_reserve,
+        repay_test_reserve,
+        borrow_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    ) = setup();
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(80_000);
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let initial_source_liquidity_balance =
+        get_token_balance(&mut banks_client, repay_test_reserve.user_liquidity_pubkey).await;
+    let initial_destination_liquidity_balance =
+        get_token_balance(&mut banks_client, borrow_test_reserve.user_liquidity_pubkey).await;
+
+    // the borrow alone would exceed the remaining borrowing power
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey, repay_test_reserve.pubkey],
+            ),
+            swap_obligation_debt(
+                solend_program::id(),
+                u64::MAX,
+                USDC_BORROW_AMOUNT_FRACTIONAL,
+                repay_test_reserve.user_liquidity_pubkey,
+                repay_test_reserve.liquidity_supply_pubkey,
+                repay_test_reserve.pubkey,
+                borrow_test_reserve.liquidity_supply_pubkey,
+                borrow_test_reserve.user_liquidity_pubkey,
+                borrow_test_reserve.pubkey,
+                borrow_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_accounts_owner.pubkey(),
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let source_liquidity_balance =
+        get_token_balance(&mut banks_client, repay_test_reserve.user_liquidity_pubkey).await;
+    assert_eq!(
+        source_liquidity_balance,
+        initial_source_liquidity_balance - USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+    let destination_liquidity_balance =
+        get_token_balance(&mut banks_client, borrow_test_reserve.user_liquidity_pubkey).await;
+    assert_eq!(
+        destination_liquidity_balance,
+        initial_destination_liquidity_balance + USDC_BORROW_AMOUNT_FRACTIONAL
+    );
+
+    let (total_fee, _host_fee) = borrow_test_reserve
+        .config
+        .fees
+        .calculate_borrow_fees(
+            USDC_BORROW_AMOUNT_FRACTIONAL.into(),
+            FeeCalculation::Exclusive,
+        )
+        .unwrap();
+    let fee_balance =
+        get_token_balance(&mut banks_client, borrow_test_reserve.config.fee_receiver).await;
+    assert_eq!(fee_balance, total_fee);
+
+    let obligation = test_obligation.get_state(&mut banks_client).await;
+    assert_eq!(obligation.borrows.len(), 1);
+    let liquidity = &obligation.borrows[0];
+    assert_eq!(liquidity.borrow_reserve, borrow_test_reserve.pubkey);
+    assert_eq!(
+        liquidity.borrowed_amount_wads,
+        Decimal::from(USDC_BORROW_AMOUNT_FRACTIONAL + total_fee)
+    );
+}
+
+#[tokio::test]
+async fn test_borrow_too_large() {
+    let (
+        test,
+        lending_market,
+        sol_test_reserve,
+        repay_test_reserve,
+        borrow_test_reserve,
+        test_obligation,
+        user_accounts_owner,
+    ) = setup();
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            refresh_obligation(
+                solend_program::id(),
+                test_obligation.pubkey,
+                vec![sol_test_reserve.pubkey, repay_test_reserve.pubkey],
+            ),
+            swap_obligation_debt(
+                solend_program::id(),
+                u64::MAX,
+                USDC_RESERVE_LIQUIDITY_FRACTIONAL / 2 + FRACTIONAL_TO_USDC,
+                repay_test_reserve.user_liquidity_pubkey,
+                repay_test_reserve.liquidity_supply_pubkey,
+                repay_test_reserve.pubkey,
+                borrow_test_reserve.liquidity_supply_pubkey,
+                borrow_test_reserve.user_liquidity_pubkey,
+                borrow_test_reserve.pubkey,
+                borrow_test_reserve.config.fee_receiver,
+                test_obligation.pubkey,
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_accounts_owner.pubkey(),
+                None,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(&[&payer, &user_accounts_owner], recent_blockhash);
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::BorrowTooLarge as u32)
+        )
+    );
+}
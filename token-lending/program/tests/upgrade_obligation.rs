@@ -0,0 +1,195 @@
+#![cfg(feature = "test-bpf")]
+
+mod helpers;
+
+use helpers::*;
+use solana_program::{program_pack::Pack, pubkey::Pubkey};
+use solana_program_test::*;
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    signature::{Keypair, Signer},
+    system_instruction::create_account,
+    transaction::{Transaction, TransactionError},
+};
+use solend_program::{
+    error::LendingError,
+    instruction::upgrade_obligation,
+    math::Decimal,
+    processor::process_instruction,
+    state::{
+        InitObligationParams, Obligation, ObligationCollateral, ObligationLiquidity,
+        LEGACY_OBLIGATION_LEN, PROGRAM_VERSION,
+    },
+};
+
+const LEGACY_OBLIGATION_LAMPORTS: u64 = 10_000_000;
+
+fn add_obligation_with_len(
+    test: &mut ProgramTest,
+    lending_market: &TestLendingMarket,
+    user_accounts_owner: &Keypair,
+    data_len: usize,
+) -> (Pubkey, Obligation) {
+    let obligation_pubkey = Pubkey::new_unique();
+    let mut borrow = ObligationLiquidity::new(Pubkey::new_unique(), Decimal::one());
+    borrow.borrowed_amount_wads = Decimal::from(100u64);
+    let mut deposit = ObligationCollateral::new(Pubkey::new_unique());
+    deposit.deposited_amount = 1_000;
+
+    let mut obligation = Obligation::new(InitObligationParams {
+        current_slot: 1,
+        lending_market: lending_market.pubkey,
+        owner: user_accounts_owner.pubkey(),
+        deposits: vec![deposit],
+        borrows: vec![borrow],
+        referrer: Pubkey::default(),
+    });
+    // written by the first program version
+    obligation.version = 1;
+
+    let mut account = Account::new(LEGACY_OBLIGATION_LAMPORTS, data_len, &solend_program::id());
+    Obligation::pack(obligation.clone(), &mut account.data).unwrap();
+    test.add_account(obligation_pubkey, account);
+
+    (obligation_pubkey, obligation)
+}
+
+#[tokio::test]
+async fn test_success() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    // limit to track compute unit increase
+    test.set_bpf_compute_max_units(30_000);
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let (legacy_obligation_pubkey, legacy_obligation) = add_obligation_with_len(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        LEGACY_OBLIGATION_LEN,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let new_obligation_keypair = Keypair::new();
+    let destination_pubkey = Pubkey::new_unique();
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &new_obligation_keypair.pubkey(),
+                rent.minimum_balance(Obligation::LEN),
+                Obligation::LEN as u64,
+                &solend_program::id(),
+            ),
+            upgrade_obligation(
+                solend_program::id(),
+                legacy_obligation_pubkey,
+                new_obligation_keypair.pubkey(),
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                destination_pubkey,
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &new_obligation_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert!(banks_client.process_transaction(transaction).await.is_ok());
+
+    let new_obligation_account = banks_client
+        .get_account(new_obligation_keypair.pubkey())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(new_obligation_account.data.len(), Obligation::LEN);
+    assert_eq!(
+        Obligation::unpack(&new_obligation_account.data[..]).unwrap(),
+        Obligation {
+            version: PROGRAM_VERSION,
+            ..legacy_obligation
+        }
+    );
+
+    assert!(banks_client
+        .get_account(legacy_obligation_pubkey)
+        .await
+        .unwrap()
+        .is_none());
+
+    let destination_account = banks_client
+        .get_account(destination_pubkey)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(destination_account.lamports, LEGACY_OBLIGATION_LAMPORTS);
+}
+
+#[tokio::test]
+async fn test_already_upgraded() {
+    let mut test = ProgramTest::new(
+        "solend_program",
+        solend_program::id(),
+        processor!(process_instruction),
+    );
+
+    let user_accounts_owner = Keypair::new();
+    let lending_market = add_lending_market(&mut test);
+    let (obligation_pubkey, _) = add_obligation_with_len(
+        &mut test,
+        &lending_market,
+        &user_accounts_owner,
+        Obligation::LEN,
+    );
+
+    let (mut banks_client, payer, recent_blockhash) = test.start().await;
+
+    let new_obligation_keypair = Keypair::new();
+    let rent = banks_client.get_rent().await.unwrap();
+    let mut transaction = Transaction::new_with_payer(
+        &[
+            create_account(
+                &payer.pubkey(),
+                &new_obligation_keypair.pubkey(),
+                rent.minimum_balance(Obligation::LEN),
+                Obligation::LEN as u64,
+                &solend_program::id(),
+            ),
+            upgrade_obligation(
+                solend_program::id(),
+                obligation_pubkey,
+                new_obligation_keypair.pubkey(),
+                lending_market.pubkey,
+                user_accounts_owner.pubkey(),
+                user_accounts_owner.pubkey(),
+            ),
+        ],
+        Some(&payer.pubkey()),
+    );
+
+    transaction.sign(
+        &[&payer, &new_obligation_keypair, &user_accounts_owner],
+        recent_blockhash,
+    );
+    assert_eq!(
+        banks_client
+            .process_transaction(transaction)
+            .await
+            .unwrap_err()
+            .unwrap(),
+        TransactionError::InstructionError(
+            1,
+            InstructionError::Custom(LendingError::InvalidAccountInput as u32)
+        )
+    );
+}
@@ -14,7 +14,6 @@ use solend_program::{
     error::LendingError,
     instruction::{refresh_obligation, withdraw_obligation_collateral},
     processor::process_instruction,
-    state::INITIAL_COLLATERAL_RATIO,
 };
 use std::u64;
 